@@ -1,8 +1,9 @@
+use rustic_ray::consts::PI;
 use rustic_ray::{
-    patterns::*, ray_tracing::camera::AntiAlias, ray_tracing::color, shapes::Plane, shapes::Shape,
-    shapes::Sphere, Camera, Color, Point, PointLight, Transform, Vector, World,
+    patterns::*, ray_tracing::camera::AntiAlias, ray_tracing::color,
+    ray_tracing::tone_map::PostProcess, shapes::Plane, shapes::Shape, shapes::Sphere, Camera,
+    Color, Float, Point, PointLight, Transform, Vector, World,
 };
-use std::f64::consts::PI;
 
 // use std::{fs::File, io::Write, path::Path};
 
@@ -18,13 +19,13 @@ fn main() {
 
     let mut floor = Plane::new();
     floor.material.pattern = Some(Box::new(pattern));
-    world.add_shape(Box::new(floor));
+    world.add_shape(Box::new(floor)).unwrap();
 
     let mut ceiling = Plane::new();
     ceiling.transform = Transform::new().translation(0.0, 10.0, 0.0).build();
     ceiling.material.reflective = 0.1;
     ceiling.material.pattern = Some(Box::new(pattern));
-    world.add_shape(Box::new(ceiling));
+    world.add_shape(Box::new(ceiling)).unwrap();
 
     let mut checkers = Checkers::new(color::WHITE, color::BLACK);
     checkers.transform = Transform::new().translation(10.0, 0.0, 10.0).build();
@@ -36,7 +37,7 @@ fn main() {
         .translation(0.0, 0.0, 10.0)
         .build();
     front_wall.material.pattern = Some(Box::new(checkers));
-    world.add_shape(Box::new(front_wall));
+    world.add_shape(Box::new(front_wall)).unwrap();
 
     let mut right_wall = Plane::new();
     right_wall.transform = Transform::new()
@@ -45,7 +46,7 @@ fn main() {
         .translation(10.0, 0.0, 0.0)
         .build();
     right_wall.material.pattern = Some(Box::new(checkers));
-    world.add_shape(Box::new(right_wall));
+    world.add_shape(Box::new(right_wall)).unwrap();
 
     let mut ball1 = Sphere::new();
     ball1.transform = Transform::new().translation(-0.5, 1.0, -1.0).build();
@@ -53,7 +54,7 @@ fn main() {
     ball1.material.refractive_index = 1.5;
     ball1.material.ambient = 0.1;
     ball1.material.diffuse = 0.05;
-    world.add_shape(Box::new(ball1));
+    world.add_shape(Box::new(ball1)).unwrap();
 
     let mut ball2 = Sphere::new();
     ball2.transform = Transform::new()
@@ -63,7 +64,7 @@ fn main() {
     ball2.material.color = Color::new(1.0, 0.0, 0.0);
     ball2.material.ambient = 0.5;
     ball2.material.reflective = 0.25;
-    world.add_shape(Box::new(ball2));
+    world.add_shape(Box::new(ball2)).unwrap();
 
     let mut ball3 = Sphere::new();
     ball3.transform = Transform::new()
@@ -73,7 +74,7 @@ fn main() {
     ball3.material.color = Color::new(0.0, 1.0, 0.0);
     ball3.material.ambient = 0.8;
     ball3.material.reflective = 1.0;
-    world.add_shape(Box::new(ball3));
+    world.add_shape(Box::new(ball3)).unwrap();
 
     let light = PointLight::new(Point::new(10.0, 3.5, -10.0), Color::new(1.0, 1.0, 1.0));
     world.lights.push(light);
@@ -86,10 +87,17 @@ fn main() {
         Vector::new(0.0, 1.0, 0.0),
     );
 
-    c.render_to_file(&world, AntiAlias::None, 5, "fractal.png");
+    c.render_to_file(
+        &world,
+        AntiAlias::None,
+        5,
+        &PostProcess::display(),
+        "fractal.png",
+    )
+    .unwrap();
 }
 
-pub fn get_ball(x: f64, y: f64, z: f64) -> Box<dyn Shape> {
+pub fn get_ball(x: Float, y: Float, z: Float) -> Box<dyn Shape> {
     let mut left = Sphere::new();
     left.transform = Transform::new()
         .scaling(0.33, 0.33, 0.33)
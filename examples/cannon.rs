@@ -44,7 +44,7 @@ fn main() {
     let path = Path::new("cannon.ppm");
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => panic!("couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
@@ -63,9 +63,9 @@ fn tick(env: &Environment, proj: &mut Projectile) {
 
 fn draw(canvas: &mut Canvas, position: &Point) {
     let x = position.x as usize;
-    let y = canvas.height - (position.y as usize);
+    let y = canvas.height() - (position.y as usize);
 
-    if x <= canvas.width - 1 && y <= canvas.height - 1 {
-        canvas.pixels[x][y] = Color::new(1.0, 0.0, 0.0);
+    if x < canvas.width() && y < canvas.height() {
+        canvas.write_pixel(x, y, Color::new(1.0, 0.0, 0.0)).unwrap();
     }
 }
@@ -1,10 +1,13 @@
-use std::{f64::consts::PI, fs::File, io::Write, path::Path};
+use rustic_ray::consts::PI;
+use std::{fs::File, io::Write, path::Path};
 
-use rustic_ray::{Canvas, Color, Point, Transform};
+use rustic_ray::{Canvas, Color, Float, Point, Transform};
 
 fn main() {
     let canvas = &mut Canvas::new(200, 200);
-    canvas.pixels[100][100] = Color::new(1.0, 0.0, 0.0);
+    canvas
+        .write_pixel(100, 100, Color::new(1.0, 0.0, 0.0))
+        .unwrap();
 
     let mut hour = 1.0;
     loop {
@@ -12,7 +15,9 @@ fn main() {
 
         println!("{} {} {}", hour, xy.0, xy.1);
 
-        canvas.pixels[xy.0][xy.1] = Color::new(1.0, 0.0, 0.0);
+        canvas
+            .write_pixel(xy.0, xy.1, Color::new(1.0, 0.0, 0.0))
+            .unwrap();
         hour += 1.0;
 
         if hour > 12.0 {
@@ -23,7 +28,7 @@ fn main() {
     let path = Path::new("clock.ppm");
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => panic!("couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
@@ -35,7 +40,7 @@ fn main() {
     };
 }
 
-fn clock_hour(hour: f64) -> (usize, usize) {
+fn clock_hour(hour: Float) -> (usize, usize) {
     let r = Transform::new().rotation_y(hour * PI / 6.0).build();
     let hour_point = r * Point::new(0.0, 0.0, 1.0);
 
@@ -1,8 +1,8 @@
-use std::f64::consts::PI;
+use rustic_ray::consts::PI;
 
 use rustic_ray::{
     patterns::Checkers,
-    ray_tracing::{camera::AntiAlias, color},
+    ray_tracing::{camera::AntiAlias, color, tone_map::PostProcess},
     shapes::Cone,
     shapes::Cylinder,
     shapes::Plane,
@@ -24,7 +24,7 @@ fn main() {
 
     let mut floor = Plane::new();
     floor.material.pattern = Some(Box::new(pattern));
-    world.add_shape(Box::new(floor));
+    world.add_shape(Box::new(floor)).unwrap();
 
     let mut front_wall = Plane::new();
     front_wall.transform = Transform::new()
@@ -33,7 +33,7 @@ fn main() {
         .translation(0.0, 0.0, 10.0)
         .build();
     front_wall.material.pattern = Some(Box::new(checkers));
-    world.add_shape(Box::new(front_wall));
+    world.add_shape(Box::new(front_wall)).unwrap();
 
     let mut right_wall = Plane::new();
     right_wall.transform = Transform::new()
@@ -42,7 +42,7 @@ fn main() {
         .translation(10.0, 0.0, 0.0)
         .build();
     right_wall.material.pattern = Some(Box::new(checkers));
-    world.add_shape(Box::new(right_wall));
+    world.add_shape(Box::new(right_wall)).unwrap();
 
     let mut c1 = Cylinder::new();
     c1.maximum = 2.0;
@@ -69,7 +69,7 @@ fn main() {
         .rotation_z(PI / 2.0)
         .translation(0.0, 1.0, 0.0)
         .build();
-    world.add_shape(Box::new(cone));
+    world.add_shape(Box::new(cone)).unwrap();
 
     let light = PointLight::new(Point::new(10.0, 3.5, -10.0), Color::new(1.0, 1.0, 1.0));
     world.lights.push(light);
@@ -82,5 +82,12 @@ fn main() {
         Vector::new(0.0, 1.0, 0.0),
     );
 
-    c.render_to_file(&world, AntiAlias::None, 5, "cylinder.png");
+    c.render_to_file(
+        &world,
+        AntiAlias::None,
+        5,
+        &PostProcess::display(),
+        "cylinder.png",
+    )
+    .unwrap();
 }
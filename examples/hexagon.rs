@@ -1,9 +1,9 @@
-use std::f64::consts::PI;
+use rustic_ray::consts::PI;
 
 use rustic_ray::{
-    patterns::Checkers, ray_tracing::camera::AntiAlias, ray_tracing::color, shapes::Cylinder,
-    shapes::Group, shapes::Plane, shapes::Sphere, Camera, Color, Point, PointLight, Transform,
-    Vector, World,
+    patterns::Checkers, ray_tracing::camera::AntiAlias, ray_tracing::color,
+    ray_tracing::tone_map::PostProcess, shapes::Cylinder, shapes::Group, shapes::Plane,
+    shapes::Sphere, Camera, Color, Float, Point, PointLight, Transform, Vector, World,
 };
 
 fn main() {
@@ -18,7 +18,7 @@ fn main() {
 
     let mut floor = Plane::new();
     floor.material.pattern = Some(Box::new(pattern));
-    w.add_shape(Box::new(floor));
+    w.add_shape(Box::new(floor)).unwrap();
 
     let mut hex = hexagon();
     hex.transform = Transform::new()
@@ -26,7 +26,7 @@ fn main() {
         .translation(1.0, 1.0, 0.0)
         .build();
 
-    w.add_shape(Box::new(hex));
+    w.add_shape(Box::new(hex)).unwrap();
 
     let mut hex2 = hexagon();
     hex2.transform = Transform::new()
@@ -36,7 +36,7 @@ fn main() {
         .build();
     hex2.material.color = Color::new(0.0, 1.0, 0.0);
 
-    w.add_shape(Box::new(hex2));
+    w.add_shape(Box::new(hex2)).unwrap();
 
     let light = PointLight::new(Point::new(10.0, 3.5, -10.0), Color::new(1.0, 1.0, 1.0));
     w.lights.push(light);
@@ -51,7 +51,14 @@ fn main() {
         Vector::new(0.0, 1.0, 0.0),
     );
 
-    c.render_to_file(w, AntiAlias::None, 5, "hexagon.png");
+    c.render_to_file(
+        w,
+        AntiAlias::None,
+        5,
+        &PostProcess::display(),
+        "hexagon.png",
+    )
+    .unwrap();
 }
 
 fn hexagon_corner() -> Sphere {
@@ -85,14 +92,8 @@ pub fn hexagon_edge() -> Cylinder {
 pub fn hexagon_side() -> Group {
     let mut side = Group::new();
 
-    let mut corner = hexagon_corner();
-    corner.parent_id = Some(side.id);
-
-    let mut hexagon_edge = hexagon_edge();
-    hexagon_edge.parent_id = Some(side.id);
-
-    side.add_shape(Box::new(corner));
-    side.add_shape(Box::new(hexagon_edge));
+    side.add_shape(Box::new(hexagon_corner())).unwrap();
+    side.add_shape(Box::new(hexagon_edge())).unwrap();
 
     side
 }
@@ -102,10 +103,9 @@ pub fn hexagon() -> Group {
 
     for n in 0..6 {
         let mut side = hexagon_side();
-        side.transform = Transform::new().rotation_y(n as f64 * PI / 3.0).build();
-        side.parent_id = Some(hex.id);
+        side.transform = Transform::new().rotation_y(n as Float * PI / 3.0).build();
 
-        hex.add_shape(Box::new(side));
+        hex.add_shape(Box::new(side)).unwrap();
     }
 
     hex
@@ -2,16 +2,16 @@ use std::{fs::File, io::Write, path::Path};
 
 use rustic_ray::{
     shapes::{Shape, Sphere},
-    Canvas, Color, Intersection, Point, PointLight, Ray,
+    Canvas, Color, Float, Intersection, Point, PointLight, Ray,
 };
 
 fn main() {
     for i in 0..9 {
-        material(i, i as f64 / 10.0)
+        material(i, i as Float / 10.0)
     }
 }
 
-fn material(i: usize, a: f64) {
+fn material(i: usize, a: Float) {
     let ray_origin = Point::new(0.0, 0.0, -5.0);
     let wall_z = 10.0;
 
@@ -20,7 +20,7 @@ fn material(i: usize, a: f64) {
 
     let mut canvas = Canvas::new(canvas_size, canvas_size);
 
-    let pixel_size = wall_size / canvas_size as f64;
+    let pixel_size = wall_size / canvas_size as Float;
 
     let half = wall_size / 2.0;
 
@@ -28,35 +28,31 @@ fn material(i: usize, a: f64) {
     sphere.material.color = Color::new(1.0, 0.2, 1.0);
 
     // changes this to be the range value
-    sphere.material.ambient = a as f64;
+    sphere.material.ambient = a;
 
     let light = PointLight::new(Point::new(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
     for y in 0..canvas_size {
-        let world_y = half - pixel_size * y as f64;
+        let world_y = half - pixel_size * y as Float;
         for x in 0..canvas_size {
-            let world_x = -half + pixel_size * x as f64;
+            let world_x = -half + pixel_size * x as Float;
 
             let position = Point::new(world_x, world_y, wall_z);
 
             let direction = (position - ray_origin).normalize();
             let ray = Ray::new(ray_origin, direction);
 
-            match sphere.local_intersect(ray) {
-                Some(xs) => match Intersection::hit(&xs) {
-                    Some(hit) => {
-                        let point = ray.position(hit.t);
-                        let normal = hit.object.normal_at(point, None);
-                        let eye = -ray.direction;
-
-                        let color = sphere
-                            .material
-                            .lighting(&sphere, light, point, eye, normal, false);
-                        canvas.pixels[x][y] = color;
-                    }
-                    _ => (),
-                },
-                None => (),
+            if let Some(xs) = sphere.local_intersect(ray) {
+                if let Some(hit) = Intersection::hit(&xs) {
+                    let point = ray.position(hit.t);
+                    let normal = hit.object.normal_at(point, None).to_vector();
+                    let eye = -ray.direction;
+
+                    let color = sphere
+                        .material
+                        .lighting(&sphere, light, point, eye, normal, false);
+                    canvas.write_pixel(x, y, color).unwrap();
+                }
             }
         }
     }
@@ -65,7 +61,7 @@ fn material(i: usize, a: f64) {
     let path = Path::new(&file);
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => panic!("couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
@@ -0,0 +1,73 @@
+//! Renders the default world on several worker processes.
+//!
+//!     cargo run --example render_farm                 # four local workers over pipes
+//!     cargo run --example render_farm -- --listen 0.0.0.0:7878   # farm machine
+//!     cargo run --example render_farm -- host1:7878 host2:7878   # coordinator
+
+use rustic_ray::consts::PI;
+use rustic_ray::ray_tracing::camera::AntiAlias;
+use rustic_ray::ray_tracing::distributed::{self, ChildConnection, Connection, Coordinator};
+use rustic_ray::{Camera, Point, Vector, World};
+use std::env;
+use std::net::TcpListener;
+use std::process::Command;
+use std::time::Duration;
+
+const WIDTH: usize = 400;
+const HEIGHT: usize = 200;
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(60));
+
+// the scene is just its size here, a real farm would send a scene file
+fn load(scene: &[u8]) -> Result<(Camera, World), String> {
+    let text = String::from_utf8_lossy(scene);
+    let mut size = text.split('x').map(|v| v.parse::<usize>());
+    match (size.next(), size.next()) {
+        (Some(Ok(width)), Some(Ok(height))) => {
+            let camera = Camera::builder(width, height)
+                .look_at(
+                    Point::new(0.0, 1.5, -5.0),
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                )
+                .field_of_view(PI / 3.0)
+                .build();
+            Ok((camera, World::default()))
+        }
+        _ => Err(format!("invalid scene {:?}", text)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("--worker") => distributed::serve_stdio(load).unwrap(),
+        Some("--listen") => {
+            let listener = TcpListener::bind(&args[1]).unwrap();
+            distributed::serve_tcp(&listener, TIMEOUT, load, |e| {
+                eprintln!("coordinator connection failed: {}", e)
+            })
+            .unwrap();
+        }
+        _ => {
+            let workers: Vec<Box<dyn Connection>> = if args.is_empty() {
+                let exe = env::current_exe().unwrap();
+                (0..4)
+                    .map(|_| {
+                        let worker = ChildConnection::spawn(Command::new(&exe).arg("--worker"));
+                        Box::new(worker.unwrap()) as Box<dyn Connection>
+                    })
+                    .collect()
+            } else {
+                let addresses: Vec<&str> = args.iter().map(String::as_str).collect();
+                distributed::connect_tcp(&addresses, TIMEOUT).unwrap()
+            };
+
+            let scene = format!("{}x{}", WIDTH, HEIGHT);
+            let canvas = Coordinator::new(WIDTH, HEIGHT)
+                .sampler(AntiAlias::Random(4))
+                .render(scene.as_bytes(), workers)
+                .unwrap();
+            canvas.save("render_farm.png").unwrap();
+        }
+    }
+}
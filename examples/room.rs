@@ -1,8 +1,9 @@
-use std::f64::consts::PI;
+use rustic_ray::consts::PI;
 
 use rustic_ray::{
     patterns::Checkers, patterns::Stripe, ray_tracing::camera::AntiAlias, ray_tracing::color,
-    shapes::Cube, Camera, Color, Point, PointLight, Transform, Vector, World,
+    ray_tracing::tone_map::PostProcess, shapes::Cube, Camera, Color, Point, PointLight, Transform,
+    Vector, World,
 };
 
 fn main() {
@@ -26,7 +27,7 @@ fn main() {
         .translation(0.0, -0.1, 0.0)
         .build();
     floor.material.pattern = Some(Box::new(checkers));
-    world.add_shape(Box::new(floor));
+    world.add_shape(Box::new(floor)).unwrap();
 
     let mut right_wall = Cube::new();
     right_wall.transform = Transform::new()
@@ -34,7 +35,7 @@ fn main() {
         .translation(-5.1, 4.0, 0.0)
         .build();
     right_wall.material.pattern = Some(Box::new(stripes1));
-    world.add_shape(Box::new(right_wall));
+    world.add_shape(Box::new(right_wall)).unwrap();
 
     let mut left_wall = Cube::new();
     left_wall.transform = Transform::new()
@@ -42,7 +43,7 @@ fn main() {
         .translation(5.1, 4.0, 0.0)
         .build();
     left_wall.material.pattern = Some(Box::new(stripes1));
-    world.add_shape(Box::new(left_wall));
+    world.add_shape(Box::new(left_wall)).unwrap();
 
     let mut back_wall = Cube::new();
     back_wall.transform = Transform::new()
@@ -50,7 +51,7 @@ fn main() {
         .translation(0.0, 4.0, 5.1)
         .build();
     back_wall.material.pattern = Some(Box::new(stripes2));
-    world.add_shape(Box::new(back_wall));
+    world.add_shape(Box::new(back_wall)).unwrap();
 
     let mut painting = Cube::new();
     painting.transform = Transform::new()
@@ -58,7 +59,7 @@ fn main() {
         .translation(-1.5, 4.0, 4.9)
         .build();
     painting.material.color = Color::new(0.1, 1.0, 0.1);
-    world.add_shape(Box::new(painting));
+    world.add_shape(Box::new(painting)).unwrap();
 
     let mut painting2 = Cube::new();
     painting2.transform = Transform::new()
@@ -66,7 +67,7 @@ fn main() {
         .translation(1.5, 4.0, 4.9)
         .build();
     painting2.material.color = Color::new(1.0, 0.3, 0.3);
-    world.add_shape(Box::new(painting2));
+    world.add_shape(Box::new(painting2)).unwrap();
 
     let mut painting3 = Cube::new();
     painting3.transform = Transform::new()
@@ -74,7 +75,7 @@ fn main() {
         .translation(1.5, 2.75, 4.9)
         .build();
     painting3.material.color = Color::new(0.0, 0.3, 1.0);
-    world.add_shape(Box::new(painting3));
+    world.add_shape(Box::new(painting3)).unwrap();
 
     let mut mirror = Cube::new();
     mirror.transform = Transform::new()
@@ -83,7 +84,7 @@ fn main() {
         .build();
     mirror.material.reflective = 1.0;
     mirror.material.refractive_index = 1.458;
-    world.add_shape(Box::new(mirror));
+    world.add_shape(Box::new(mirror)).unwrap();
 
     let mut table_top = Cube::new();
     table_top.transform = Transform::new()
@@ -93,7 +94,7 @@ fn main() {
     table_top.material.pattern = Some(Box::new(stripes1));
     table_top.material.reflective = 0.02;
     table_top.material.refractive_index = 3.45;
-    world.add_shape(Box::new(table_top));
+    world.add_shape(Box::new(table_top)).unwrap();
 
     let mut leg1 = Cube::new();
     leg1.transform = Transform::new()
@@ -101,7 +102,7 @@ fn main() {
         .translation(-1.9, 0.65, -2.9)
         .build();
     leg1.material.color = Color::new_rgb(161, 64, 5);
-    world.add_shape(Box::new(leg1));
+    world.add_shape(Box::new(leg1)).unwrap();
 
     let mut leg2 = Cube::new();
     leg2.transform = Transform::new()
@@ -109,7 +110,7 @@ fn main() {
         .translation(2.9, 0.65, -2.9)
         .build();
     leg2.material.color = Color::new_rgb(161, 64, 5);
-    world.add_shape(Box::new(leg2));
+    world.add_shape(Box::new(leg2)).unwrap();
 
     let mut leg3 = Cube::new();
     leg3.transform = Transform::new()
@@ -117,7 +118,7 @@ fn main() {
         .translation(2.9, 0.65, 2.9)
         .build();
     leg3.material.color = Color::new_rgb(161, 64, 5);
-    world.add_shape(Box::new(leg3));
+    world.add_shape(Box::new(leg3)).unwrap();
 
     let mut leg4 = Cube::new();
     leg4.transform = Transform::new()
@@ -125,7 +126,7 @@ fn main() {
         .translation(-1.9, 0.65, 2.9)
         .build();
     leg4.material.color = Color::new_rgb(161, 64, 5);
-    world.add_shape(Box::new(leg4));
+    world.add_shape(Box::new(leg4)).unwrap();
 
     let mut block1 = Cube::new();
     block1.transform = Transform::new()
@@ -135,7 +136,7 @@ fn main() {
     block1.material.color = Color::new_rgb(211, 102, 151);
     block1.material.refractive_index = 2.417;
     block1.material.reflective = 0.45;
-    world.add_shape(Box::new(block1));
+    world.add_shape(Box::new(block1)).unwrap();
 
     let mut block1 = Cube::new();
     block1.transform = Transform::new()
@@ -143,7 +144,7 @@ fn main() {
         .translation(0.5, 1.45, -2.0)
         .build();
     block1.material.color = Color::new_rgb(213, 14, 151);
-    world.add_shape(Box::new(block1));
+    world.add_shape(Box::new(block1)).unwrap();
 
     let mut block3 = Cube::new();
     block3.transform = Transform::new()
@@ -151,7 +152,7 @@ fn main() {
         .translation(1.75, 1.55, -1.0)
         .build();
     block3.material.color = Color::new_rgb(10, 234, 36);
-    world.add_shape(Box::new(block3));
+    world.add_shape(Box::new(block3)).unwrap();
 
     let mut block3 = Cube::new();
     block3.transform = Transform::new()
@@ -163,7 +164,7 @@ fn main() {
     block3.material.ambient = 0.025;
     block3.material.diffuse = 0.25;
     block3.material.color = Color::new_rgb(237, 234, 36);
-    world.add_shape(Box::new(block3));
+    world.add_shape(Box::new(block3)).unwrap();
 
     let light = PointLight::new(Point::new(3.0, 11.0, -10.0), Color::new(1.0, 1.0, 1.0));
     world.lights.push(light);
@@ -178,5 +179,12 @@ fn main() {
         Vector::new(0.0, 1.0, 0.0),
     );
 
-    c.render_to_file(&world, AntiAlias::None, 5, "room.png");
+    c.render_to_file(
+        &world,
+        AntiAlias::None,
+        5,
+        &PostProcess::display(),
+        "room.png",
+    )
+    .unwrap();
 }
@@ -1,6 +1,7 @@
 pub mod ray_tracing;
 
 pub use ray_tracing::camera::Camera;
+pub use ray_tracing::camera::CameraBuilder;
 pub use ray_tracing::canvas::Canvas;
 pub use ray_tracing::color::Color;
 pub use ray_tracing::intersection::Computations;
@@ -8,9 +9,12 @@ pub use ray_tracing::intersection::Intersection;
 pub use ray_tracing::light::PointLight;
 pub use ray_tracing::material::Material;
 pub use ray_tracing::matrix::Matrix;
+pub use ray_tracing::normal::Normal;
 pub use ray_tracing::patterns;
 pub use ray_tracing::point::Point;
+pub use ray_tracing::quaternion::Quaternion;
 pub use ray_tracing::ray::Ray;
+pub use ray_tracing::sampler::Sampler;
 pub use ray_tracing::shape_container::ShapeContainer;
 pub use ray_tracing::shapes;
 pub use ray_tracing::transform::Transform;
@@ -20,13 +24,40 @@ pub use ray_tracing::xs::XS;
 
 use std::cmp::Ordering;
 
-pub const EPSILON: f64 = 0.0001;
+/// Scalar type of all math, `f32` with the `f32` feature for large scenes
+/// where memory and speed matter more than precision.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
 
-pub fn float_eq(a: f64, b: f64) -> bool {
-    (a - b).abs() < EPSILON
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+/// Tolerance for values around 1.0, see `epsilon_at` for larger values.
+#[cfg(not(feature = "f32"))]
+pub const EPSILON: Float = 0.0001;
+#[cfg(feature = "f32")]
+pub const EPSILON: Float = 0.001;
+
+/// Tolerance for values of the given magnitude. With `f64` it is always
+/// `EPSILON`. With `f32` it grows with the magnitude, the spacing of `f32`
+/// values passes a fixed `EPSILON` in the low thousands.
+pub fn epsilon_at(magnitude: Float) -> Float {
+    if cfg!(feature = "f32") {
+        EPSILON * magnitude.abs().max(1.0)
+    } else {
+        EPSILON
+    }
 }
 
-pub fn float_cmp(a: f64, b: f64) -> Ordering {
+pub fn float_eq(a: Float, b: Float) -> bool {
+    (a - b).abs() < epsilon_at(a.abs().max(b.abs()))
+}
+
+pub fn float_cmp(a: Float, b: Float) -> Ordering {
     if float_eq(a, b) {
         Ordering::Equal
     } else if a < b {
@@ -36,6 +67,16 @@ pub fn float_cmp(a: f64, b: f64) -> Ordering {
     }
 }
 
+/// A path in the temporary directory that no other test, or concurrent
+/// test run, uses.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("rustic_ray_{}_{}_{}", std::process::id(), n, name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -47,12 +88,12 @@ mod tests {
 
     #[test]
     fn equals2() {
-        assert!(float_eq(0.21804511278195488, 0.21804999999999999));
+        assert!(float_eq(0.218_045_1, 0.218_05));
     }
 
     #[test]
     fn equals3() {
-        assert!(float_eq(0.0, 0.00000000000000006123233995736766));
+        assert!(float_eq(0.0, 6.123_234e-17));
     }
 
     #[test]
@@ -64,4 +105,22 @@ mod tests {
     fn greater_than() {
         assert_eq!(float_cmp(6.0, 4.5), Ordering::Greater);
     }
+
+    #[test]
+    #[cfg(not(feature = "f32"))]
+    fn epsilon_is_absolute() {
+        assert_eq!(epsilon_at(0.5), EPSILON);
+        assert_eq!(epsilon_at(-1000.0), EPSILON);
+        assert!(!float_eq(10000.0, 10000.0 + EPSILON * 5.0));
+        assert!(float_eq(10000.0, 10000.0 + EPSILON * 0.5));
+    }
+
+    #[test]
+    #[cfg(feature = "f32")]
+    fn epsilon_grows_with_magnitude() {
+        assert_eq!(epsilon_at(0.5), EPSILON);
+        assert_eq!(epsilon_at(-1000.0), EPSILON * 1000.0);
+        assert!(float_eq(10000.0, 10000.0 + EPSILON * 5.0));
+        assert!(!float_eq(1.0, 1.0 + EPSILON * 5.0));
+    }
 }
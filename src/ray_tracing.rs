@@ -1,16 +1,35 @@
+pub mod aov;
 pub mod camera;
 pub mod canvas;
 pub mod color;
+pub mod compare;
+pub mod denoise;
+pub mod distributed;
+pub mod golden;
+pub mod hdr;
 pub mod intersection;
 pub mod light;
 pub mod material;
 pub mod matrix;
+pub mod normal;
+pub mod packet;
 pub mod patterns;
 pub mod point;
+pub mod ppm;
+pub mod progressive;
+pub mod quaternion;
 pub mod ray;
+pub mod sampler;
+#[cfg(feature = "serde")]
+pub mod serialization;
 pub mod shape_container;
 pub mod shapes;
+pub mod simd;
+pub mod stable_hash;
+pub mod tiles;
+pub mod tone_map;
 pub mod transform;
+mod tuple;
 pub mod vector;
 pub mod world;
 pub mod xs;
@@ -0,0 +1,174 @@
+use super::stable_hash::StableHasher;
+use crate::{Canvas, Color, Float, Vector};
+use std::hash::{Hash, Hasher};
+use uuid::Uuid;
+
+/// Auxiliary values of a single hit, see `World::aov_at`.
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    pub color: Color,
+    /// Light reflected directly by the surface, `color` without the
+    /// reflection and refraction.
+    pub direct: Color,
+    /// Distance along the ray, `t` of the hit.
+    pub depth: Float,
+    /// World space normal facing the eye.
+    pub normal: Vector,
+    pub albedo: Color,
+    pub object_id: Uuid,
+    pub material_id: u64,
+    pub in_shadow: bool,
+    pub reflection: Color,
+    pub refraction: Color,
+}
+
+/// Auxiliary output buffers rendered next to the beauty image. The direct,
+/// reflection and refraction passes are filtered like the beauty image and
+/// add up to it. The other passes are taken from the hit closest to the pixel
+/// center so IDs and depth are never blended across edges.
+#[derive(Debug, Clone)]
+pub struct RenderPasses {
+    pub beauty: Canvas,
+    /// Direct lighting, the background where nothing was hit.
+    pub direct: Canvas,
+    /// `t` of the hit in every channel, infinite where nothing was hit.
+    pub depth: Canvas,
+    /// World space normal in x, y and z, black where nothing was hit.
+    pub normal: Canvas,
+    pub albedo: Canvas,
+    /// White where the surface is in shadow.
+    pub shadow: Canvas,
+    pub reflection: Canvas,
+    pub refraction: Canvas,
+    object_ids: Vec<Option<Uuid>>,
+    material_ids: Vec<Option<u64>>,
+}
+
+impl RenderPasses {
+    pub fn new(width: usize, height: usize) -> RenderPasses {
+        let infinity = Color::new(Float::INFINITY, Float::INFINITY, Float::INFINITY);
+        let mut depth = Canvas::new(width, height);
+        for p in depth.pixels_mut() {
+            *p = infinity;
+        }
+        RenderPasses {
+            beauty: Canvas::new(width, height),
+            direct: Canvas::new(width, height),
+            depth,
+            normal: Canvas::new(width, height),
+            albedo: Canvas::new(width, height),
+            shadow: Canvas::new(width, height),
+            reflection: Canvas::new(width, height),
+            refraction: Canvas::new(width, height),
+            object_ids: vec![None; width * height],
+            material_ids: vec![None; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.beauty.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.beauty.height()
+    }
+
+    /// Stores the values of the pixel at x, y that are not filtered, depth,
+    /// normal, albedo, shadow and the IDs.
+    pub fn write_sample(&mut self, x: usize, y: usize, sample: &AovSample) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let n = sample.normal;
+        let shadow = if sample.in_shadow { 1.0 } else { 0.0 };
+        self.depth
+            .write_pixel(x, y, Color::new(sample.depth, sample.depth, sample.depth))
+            .unwrap();
+        self.normal
+            .write_pixel(x, y, Color::new(n.x, n.y, n.z))
+            .unwrap();
+        self.albedo.write_pixel(x, y, sample.albedo).unwrap();
+        self.shadow
+            .write_pixel(x, y, Color::new(shadow, shadow, shadow))
+            .unwrap();
+
+        let i = y * self.width() + x;
+        self.object_ids[i] = Some(sample.object_id);
+        self.material_ids[i] = Some(sample.material_id);
+    }
+
+    pub fn depth_at(&self, x: usize, y: usize) -> Option<Float> {
+        self.depth.pixel_at(x, y).map(|c| c.red)
+    }
+
+    pub fn object_id_at(&self, x: usize, y: usize) -> Option<Uuid> {
+        self.index(x, y).and_then(|i| self.object_ids[i])
+    }
+
+    pub fn material_id_at(&self, x: usize, y: usize) -> Option<u64> {
+        self.index(x, y).and_then(|i| self.material_ids[i])
+    }
+
+    /// Gives every object a random looking flat color for picking mattes.
+    /// The colors are the same in every run and build.
+    pub fn object_id_matte(&self) -> Canvas {
+        self.matte(&self.object_ids)
+    }
+
+    pub fn material_id_matte(&self) -> Canvas {
+        self.matte(&self.material_ids)
+    }
+
+    fn matte<T: Hash>(&self, ids: &[Option<T>]) -> Canvas {
+        let mut canvas = Canvas::new(self.width(), self.height());
+        for (pixel, id) in canvas.pixels_mut().iter_mut().zip(ids) {
+            if let Some(id) = id {
+                let mut hasher = StableHasher::new();
+                id.hash(&mut hasher);
+                let h = hasher.finish();
+                let channel = |shift: u64| ((h >> shift) & 0xff) as Float / 255.0;
+                *pixel = Color::new(channel(0), channel(8), channel(16));
+            }
+        }
+        canvas
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width() && y < self.height() {
+            Some(y * self.width() + x)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracing::color;
+
+    #[test]
+    fn matte_colors_do_not_change_between_runs() {
+        let sample = AovSample {
+            color: color::WHITE,
+            direct: color::WHITE,
+            depth: 1.0,
+            normal: Vector::new(0.0, 0.0, -1.0),
+            albedo: color::WHITE,
+            object_id: Uuid::nil(),
+            material_id: 0,
+            in_shadow: false,
+            reflection: color::BLACK,
+            refraction: color::BLACK,
+        };
+        let mut passes = RenderPasses::new(2, 1);
+        passes.write_sample(0, 0, &sample);
+
+        let matte = passes.material_id_matte();
+        assert_eq!(
+            matte.pixel_at(0, 0),
+            Some(Color::new(197.0 / 255.0, 57.0 / 255.0, 26.0 / 255.0))
+        );
+        assert_eq!(matte.pixel_at(1, 0), Some(color::BLACK));
+    }
+}
@@ -1,18 +1,75 @@
-use crate::{Canvas, Color, Matrix, Point, Ray, World};
-use image::{Rgb, RgbImage};
-use rand::prelude::*;
+use crate::consts::PI;
+use crate::{Canvas, Color, Float, Matrix, Point, Ray, Transform, Vector, World};
 
+use super::aov::{AovSample, RenderPasses};
+use super::color;
 use super::matrix::IDENTITY;
-
+use super::sampler::{pixel_rng, Sampler};
+use super::tone_map::PostProcess;
+use super::world::SingularTransform;
+use image::ImageResult;
+use rand::Rng;
+
+/// Serialised without the sizes derived from the projection, they are
+/// recomputed when loaded.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SavedCamera")
+)]
 pub struct Camera {
     hsize: usize,
     vsize: usize,
     pub transform: Matrix,
-    half_width: f64,
-    half_height: f64,
-    pixel_size: f64,
+    projection: Projection,
+    seed: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    half_width: Float,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    half_height: Float,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pixel_size: Float,
+}
+
+/// The saved fields of a `Camera`.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SavedCamera {
+    hsize: usize,
+    vsize: usize,
+    transform: Matrix,
+    projection: Projection,
+    seed: u64,
+}
+
+#[cfg(feature = "serde")]
+impl From<SavedCamera> for Camera {
+    fn from(saved: SavedCamera) -> Self {
+        let mut camera = Camera::with_projection(saved.hsize, saved.vsize, saved.projection);
+        camera.transform = saved.transform;
+        camera.seed = saved.seed;
+        camera
+    }
 }
 
+/// How rays leave the camera. Angles are in radians and widths are in world
+/// units measured in camera space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Projection {
+    /// Pinhole camera, `field_of_view` spans the longer side of the image.
+    Perspective { field_of_view: Float },
+    /// Parallel rays, `width` is the extent of the view along the longer side.
+    Orthographic { width: Float },
+    /// Equidistant fisheye, the angle from the view axis grows linearly with
+    /// the distance from the image center. `field_of_view` spans the longer
+    /// side of the image and may exceed PI.
+    Fisheye { field_of_view: Float },
+    /// Full 360 by 180 degree panorama in latitude/longitude layout.
+    Equirectangular,
+}
+
+/// Shorthand presets for a `Sampler`.
 pub enum AntiAlias {
     Random(i32),
     FourByFour,
@@ -21,132 +78,304 @@ pub enum AntiAlias {
 }
 
 impl Camera {
-    pub fn new(hsize: usize, vsize: usize, field_of_view: f64) -> Camera {
-        let half_view = (field_of_view / 2.0).tan();
-        let aspect = hsize as f64 / vsize as f64;
+    pub fn new(hsize: usize, vsize: usize, field_of_view: Float) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Perspective { field_of_view })
+    }
 
-        let mut half_width = half_view * aspect;
-        let mut half_height = half_view;
+    pub fn orthographic(hsize: usize, vsize: usize, width: Float) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Orthographic { width })
+    }
 
-        if aspect >= 1.0 {
-            half_width = half_view;
-            half_height = half_view / aspect;
-        }
+    pub fn fisheye(hsize: usize, vsize: usize, field_of_view: Float) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Fisheye { field_of_view })
+    }
 
-        let pixel_size = (half_width * 2.0) / hsize as f64;
+    pub fn equirectangular(hsize: usize, vsize: usize) -> Camera {
+        Camera::with_projection(hsize, vsize, Projection::Equirectangular)
+    }
 
-        Camera {
+    pub fn with_projection(hsize: usize, vsize: usize, projection: Projection) -> Camera {
+        let mut camera = Camera {
             hsize,
             vsize,
             transform: IDENTITY,
-            half_width,
-            half_height,
-            pixel_size,
+            projection,
+            seed: 0,
+            half_width: 0.0,
+            half_height: 0.0,
+            pixel_size: 0.0,
+        };
+        camera.compute_pixel_size();
+        camera
+    }
+
+    pub fn builder(hsize: usize, vsize: usize) -> CameraBuilder {
+        CameraBuilder::new(hsize, vsize)
+    }
+
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
+    pub fn pixel_size(&self) -> Float {
+        self.pixel_size
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Seeds every stochastic sample taken by the camera, renders with the
+    /// same seed produce identical canvases.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.compute_pixel_size();
+    }
+
+    /// Changes the image size while keeping the projection, the field of view
+    /// keeps spanning the longer side of the image.
+    pub fn set_resolution(&mut self, hsize: usize, vsize: usize) {
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self.compute_pixel_size();
+    }
+
+    /// Sets the field of view across the longer side of the image. Only
+    /// perspective and fisheye cameras have one, orthographic and
+    /// equirectangular cameras are left unchanged.
+    pub fn set_field_of_view(&mut self, field_of_view: Float) {
+        self.projection = match self.projection {
+            Projection::Perspective { .. } => Projection::Perspective { field_of_view },
+            Projection::Fisheye { .. } => Projection::Fisheye { field_of_view },
+            projection => projection,
+        };
+        self.compute_pixel_size();
+    }
+
+    pub fn set_horizontal_field_of_view(&mut self, field_of_view: Float) {
+        let (along, across) = (self.hsize as Float, self.vsize as Float);
+        self.set_field_of_view(self.longer_side_fov(field_of_view, along, across));
+    }
+
+    pub fn set_vertical_field_of_view(&mut self, field_of_view: Float) {
+        let (along, across) = (self.vsize as Float, self.hsize as Float);
+        self.set_field_of_view(self.longer_side_fov(field_of_view, along, across));
+    }
+
+    // Converts a field of view measured along one side of the image into the
+    // field of view along the longer side.
+    fn longer_side_fov(&self, field_of_view: Float, along: Float, across: Float) -> Float {
+        if along >= across {
+            field_of_view
+        } else if let Projection::Fisheye { .. } = self.projection {
+            field_of_view * across / along
+        } else {
+            2.0 * ((field_of_view / 2.0).tan() * across / along).atan()
+        }
+    }
+
+    fn compute_pixel_size(&mut self) {
+        let half_view = match self.projection {
+            Projection::Perspective { field_of_view } => (field_of_view / 2.0).tan(),
+            Projection::Orthographic { width } => width / 2.0,
+            Projection::Fisheye { .. } | Projection::Equirectangular => 1.0,
+        };
+        let aspect = self.hsize as Float / self.vsize as Float;
+
+        if aspect >= 1.0 {
+            self.half_width = half_view;
+            self.half_height = half_view / aspect;
+        } else {
+            self.half_width = half_view * aspect;
+            self.half_height = half_view;
         }
+
+        self.pixel_size = (self.half_width * 2.0) / self.hsize as Float;
     }
 
-    pub fn ray_for_pixel(&mut self, px: f64, py: f64) -> Ray {
+    pub fn ray_for_pixel(&self, px: Float, py: Float) -> Ray {
         let x_offset = (px + 0.5) * self.pixel_size;
         let y_offset = (py + 0.5) * self.pixel_size;
 
         let world_x = self.half_width - x_offset;
         let world_y = self.half_height - y_offset;
 
-        let pixel = self.transform.inverse() * Point::new(world_x, world_y, -1.0);
-        let origin = self.transform.inverse() * Point::new(0.0, 0.0, 0.0);
-        let direction = (pixel - origin).normalize();
+        let (origin, direction) = match self.projection {
+            Projection::Perspective { .. } => {
+                let pixel = self.transform.inverse() * Point::new(world_x, world_y, -1.0);
+                let origin = self.transform.inverse() * Point::new(0.0, 0.0, 0.0);
+                (origin, pixel - origin)
+            }
+            Projection::Orthographic { .. } => (
+                self.transform.inverse() * Point::new(world_x, world_y, 0.0),
+                self.transform.inverse() * Vector::new(0.0, 0.0, -1.0),
+            ),
+            Projection::Fisheye { field_of_view } => {
+                let r = (world_x.powf(2.0) + world_y.powf(2.0)).sqrt();
+                let theta = r * field_of_view / 2.0;
+                let local = if r == 0.0 {
+                    Vector::new(0.0, 0.0, -1.0)
+                } else {
+                    Vector::new(
+                        theta.sin() * world_x / r,
+                        theta.sin() * world_y / r,
+                        -theta.cos(),
+                    )
+                };
+                (
+                    self.transform.inverse() * Point::new(0.0, 0.0, 0.0),
+                    self.transform.inverse() * local,
+                )
+            }
+            Projection::Equirectangular => {
+                let longitude = (0.5 - (px + 0.5) / self.hsize as Float) * 2.0 * PI;
+                let latitude = (0.5 - (py + 0.5) / self.vsize as Float) * PI;
+                let local = Vector::new(
+                    longitude.sin() * latitude.cos(),
+                    latitude.sin(),
+                    -longitude.cos() * latitude.cos(),
+                );
+                (
+                    self.transform.inverse() * Point::new(0.0, 0.0, 0.0),
+                    self.transform.inverse() * local,
+                )
+            }
+        };
 
-        Ray::new(origin, direction)
+        Ray::new(origin, direction.normalize())
     }
 
-    pub fn render(&mut self, world: World, ss: usize, rd: usize) -> Canvas {
+    /// Renders the world into a canvas, every pixel is estimated with the
+    /// given sampler and rays recurse at most `rd` times.
+    ///
+    /// Panics when a shape has a transform that can not be inverted, see
+    /// `try_render`.
+    pub fn render<S: Into<Sampler>>(&self, world: &World, sampler: S, rd: usize) -> Canvas {
+        match self.try_render(world, sampler, rd) {
+            Ok(canvas) => canvas,
+            Err(e) => panic!("can not render the world: {}", e),
+        }
+    }
+
+    /// Like `render`, but a shape with a singular transform is an error.
+    pub fn try_render<S: Into<Sampler>>(
+        &self,
+        world: &World,
+        sampler: S,
+        rd: usize,
+    ) -> Result<Canvas, SingularTransform> {
+        world.validate()?;
+        let sampler = sampler.into();
         let mut canvas = Canvas::new(self.hsize, self.vsize);
 
-        for y in 0..self.vsize {
-            for x in 0..self.hsize {
-                let ray = self.ray_for_pixel(x as f64, y as f64);
-                let mut color = world.color_at(ray, rd);
-                if ss > 0 {
-                    for _s in 0..ss {
-                        let ro = self.get_ray_offset(x as f64, y as f64);
-                        let co = world.color_at(ro, rd);
-                        color = color + co;
-                    }
-
-                    color = Color::new(
-                        color.red / ss as f64,
-                        color.green / ss as f64,
-                        color.blue / ss as f64,
-                    );
-                }
-                canvas.pixels[x][y] = color;
+        for (y, row) in canvas.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.sample_pixel(world, &sampler, x, y, rd);
             }
         }
 
-        canvas
+        Ok(canvas)
     }
 
-    pub fn render_to_file(
-        &mut self,
+    /// Renders the beauty image together with the auxiliary passes. Every
+    /// sample is shaded once and feeds all of the passes. A shape with a
+    /// singular transform is an error, like in `try_render`.
+    pub fn render_passes<S: Into<Sampler>>(
+        &self,
         world: &World,
-        aa_type: AntiAlias,
+        sampler: S,
         rd: usize,
-        file_name: &str,
-    ) {
-        let mut img = RgbImage::new(self.hsize as u32, self.vsize as u32);
-        for (x, y, pixel) in img.enumerate_pixels_mut() {
-            let ray = self.ray_for_pixel(x as f64, y as f64);
-
-            let mut color = world.color_at(ray, rd);
-            match aa_type {
-                AntiAlias::FourByFour => {
-                    let mut f = 0.9;
-                    loop {
-                        let aa = self.get_ray_4x4(&world, color, f, x as f64, y as f64, rd);
-                        color = aa.1;
-                        f = f - 0.2;
-                        if !aa.0 || f <= 0.0 {
-                            break;
-                        }
-                    }
-                }
-                AntiAlias::EightByEight => {
-                    let mut f = 0.9;
-                    loop {
-                        let aa = self.get_ray_8x8(&world, color, f, x as f64, y as f64, rd);
-                        color = aa.1;
-                        f = f - 0.2;
-                        if !aa.0 || f <= 0.0 {
-                            break;
+    ) -> Result<RenderPasses, SingularTransform> {
+        world.validate()?;
+        let sampler = sampler.into();
+        let mut passes = RenderPasses::new(self.hsize, self.vsize);
+
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let mut rng = pixel_rng(self.seed, x, y);
+                // (distance² from the pixel center, sample) of the hit closest to it
+                let mut center: Option<(Float, AovSample)> = None;
+                let [beauty, direct, reflection, refraction] =
+                    sampler.sample_pixel_passes(&mut rng, |dx, dy| {
+                        let ray = self.ray_for_pixel(x as Float + dx, y as Float + dy);
+                        match world.aov_at(ray, rd) {
+                            Some(sample) => {
+                                let distance = dx * dx + dy * dy;
+                                if center.is_none_or(|(closest, _)| distance < closest) {
+                                    center = Some((distance, sample));
+                                }
+                                [
+                                    sample.color,
+                                    sample.direct,
+                                    sample.reflection,
+                                    sample.refraction,
+                                ]
+                            }
+                            None => [color::WHITE, color::WHITE, color::BLACK, color::BLACK],
                         }
-                    }
-                }
-                AntiAlias::Random(points) => {
-                    for _s in 0..points {
-                        let ro = self.get_ray_offset(x as f64, y as f64);
-                        let co = world.color_at(ro, rd);
-                        color = color + co;
-                    }
-
-                    color = color / points as f64;
+                    });
+                passes.beauty.write_pixel(x, y, beauty).unwrap();
+                passes.direct.write_pixel(x, y, direct).unwrap();
+                passes.reflection.write_pixel(x, y, reflection).unwrap();
+                passes.refraction.write_pixel(x, y, refraction).unwrap();
+                if let Some((_, sample)) = center {
+                    passes.write_sample(x, y, &sample);
                 }
-                _ => (),
             }
-
-            let pixel_color = color.to_rgb();
-            *pixel = Rgb([pixel_color.0, pixel_color.1, pixel_color.2]);
         }
 
-        img.save(file_name).unwrap();
+        Ok(passes)
+    }
+
+    /// Renders the world and saves it after post processing, the image
+    /// format is taken from the file extension.
+    pub fn render_to_file<S: Into<Sampler>>(
+        &self,
+        world: &World,
+        sampler: S,
+        rd: usize,
+        settings: &PostProcess,
+        file_name: &str,
+    ) -> ImageResult<()> {
+        self.render(world, sampler, rd)
+            .save_with(file_name, settings)
     }
 
+    pub fn sample_pixel(
+        &self,
+        world: &World,
+        sampler: &Sampler,
+        x: usize,
+        y: usize,
+        rd: usize,
+    ) -> Color {
+        let mut rng = pixel_rng(self.seed, x, y);
+        sampler.sample_pixel(&mut rng, |dx, dy| {
+            let ray = self.ray_for_pixel(x as Float + dx, y as Float + dy);
+            world.color_at(ray, rd)
+        })
+    }
+
+    #[deprecated(note = "use a `Sampler`, e.g. `AntiAlias::FourByFour.into()`")]
     pub fn get_ray_4x4(
         &mut self,
         w: &World,
         color: Color,
-        f: f64,
-        x: f64,
-        y: f64,
+        f: Float,
+        x: Float,
+        y: Float,
         d: usize,
     ) -> (bool, Color) {
         let c1 = w.color_at(self.ray_for_pixel(x - f, y + f), d);
@@ -161,13 +390,14 @@ impl Camera {
         }
     }
 
+    #[deprecated(note = "use a `Sampler`, e.g. `AntiAlias::EightByEight.into()`")]
     pub fn get_ray_8x8(
         &mut self,
         w: &World,
         color: Color,
-        f: f64,
-        x: f64,
-        y: f64,
+        f: Float,
+        x: Float,
+        y: Float,
         d: usize,
     ) -> (bool, Color) {
         let c1 = w.color_at(self.ray_for_pixel(x - f, y + f), d);
@@ -180,33 +410,155 @@ impl Camera {
         let c7 = w.color_at(self.ray_for_pixel(x + f, y), d);
         let c8 = w.color_at(self.ray_for_pixel(x, y - f), d);
 
-        if c1 != color
-            || c2 != color
-            || c3 != color
-            || c4 != color
-            || c5 != color
-            || c6 != color
-            || c7 != color
-            || c8 != color
-        {
-            (true, (color + c1 + c2 + c3 + c4 + c5 + c6 + c7 + c8) / 9.0)
+        let corners = [c1, c2, c3, c4, c5, c6, c7, c8];
+        if corners.iter().any(|c| *c != color) {
+            (true, corners.iter().fold(color, |sum, c| sum + *c) / 9.0)
         } else {
             (false, color)
         }
     }
 
-    pub fn get_ray_offset(&mut self, x: f64, y: f64) -> Ray {
-        let mut rng = thread_rng();
+    #[deprecated(note = "use `Sampler::fixed` with `sample_pixel`, which is seeded")]
+    pub fn get_ray_offset(&mut self, x: Float, y: Float) -> Ray {
+        let mut rng = rand::thread_rng();
         let xo = rng.gen_range(-0.99, 0.99);
         let yo = rng.gen_range(-0.99, 0.99);
         self.ray_for_pixel(x + xo, y + yo)
     }
 }
 
+impl From<AntiAlias> for Sampler {
+    fn from(aa: AntiAlias) -> Sampler {
+        match aa {
+            AntiAlias::Random(points) => Sampler::fixed(points.max(1) as usize),
+            AntiAlias::FourByFour => Sampler::adaptive(4, 16, 0.005),
+            AntiAlias::EightByEight => Sampler::adaptive(16, 64, 0.002),
+            AntiAlias::None => Sampler::center(),
+        }
+    }
+}
+
+enum FieldOfView {
+    Longer(Float),
+    Horizontal(Float),
+    Vertical(Float),
+}
+
+/// Builds a `Camera` from a look-at description instead of assigning the
+/// view transformation by hand.
+pub struct CameraBuilder {
+    hsize: usize,
+    vsize: usize,
+    transform: Matrix,
+    projection: Projection,
+    field_of_view: Option<FieldOfView>,
+    sensor_width: Float,
+    focal_length: Option<Float>,
+    seed: u64,
+}
+
+impl CameraBuilder {
+    pub fn new(hsize: usize, vsize: usize) -> CameraBuilder {
+        CameraBuilder {
+            hsize,
+            vsize,
+            transform: IDENTITY,
+            projection: Projection::Perspective {
+                field_of_view: PI / 3.0,
+            },
+            field_of_view: None,
+            sensor_width: 36.0,
+            focal_length: None,
+            seed: 0,
+        }
+    }
+
+    pub fn look_at(mut self, from: Point, to: Point, up: Vector) -> CameraBuilder {
+        self.transform = Transform::view_transformation(from, to, up);
+        self
+    }
+
+    pub fn transform(mut self, transform: Matrix) -> CameraBuilder {
+        self.transform = transform;
+        self
+    }
+
+    pub fn projection(mut self, projection: Projection) -> CameraBuilder {
+        self.projection = projection;
+        self
+    }
+
+    pub fn resolution(mut self, hsize: usize, vsize: usize) -> CameraBuilder {
+        self.hsize = hsize;
+        self.vsize = vsize;
+        self
+    }
+
+    /// Field of view in radians across the longer side of the image, ignored
+    /// by orthographic and equirectangular projections.
+    pub fn field_of_view(mut self, field_of_view: Float) -> CameraBuilder {
+        self.field_of_view = Some(FieldOfView::Longer(field_of_view));
+        self
+    }
+
+    pub fn horizontal_fov_degrees(mut self, degrees: Float) -> CameraBuilder {
+        self.field_of_view = Some(FieldOfView::Horizontal(degrees.to_radians()));
+        self
+    }
+
+    pub fn vertical_fov_degrees(mut self, degrees: Float) -> CameraBuilder {
+        self.field_of_view = Some(FieldOfView::Vertical(degrees.to_radians()));
+        self
+    }
+
+    /// Width of the film back in millimeters, defaults to 36mm (full frame).
+    pub fn sensor_width(mut self, millimeters: Float) -> CameraBuilder {
+        self.sensor_width = millimeters;
+        self
+    }
+
+    /// Derives the horizontal field of view from a lens focal length in
+    /// millimeters and the sensor width. Like the field of view it only
+    /// applies to perspective and fisheye projections.
+    pub fn focal_length(mut self, millimeters: Float) -> CameraBuilder {
+        self.focal_length = Some(millimeters);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> CameraBuilder {
+        self.seed = seed;
+        self
+    }
+
+    pub fn build(&self) -> Camera {
+        let mut camera = Camera::with_projection(self.hsize, self.vsize, self.projection);
+        camera.transform = self.transform;
+        camera.seed = self.seed;
+
+        if let Some(focal_length) = self.focal_length {
+            let field_of_view = 2.0 * (self.sensor_width / (2.0 * focal_length)).atan();
+            camera.set_horizontal_field_of_view(field_of_view);
+        }
+
+        match self.field_of_view {
+            Some(FieldOfView::Longer(fov)) => camera.set_field_of_view(fov),
+            Some(FieldOfView::Horizontal(fov)) => camera.set_horizontal_field_of_view(fov),
+            Some(FieldOfView::Vertical(fov)) => camera.set_vertical_field_of_view(fov),
+            None => (),
+        }
+
+        camera
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{float_eq, Camera, Color, Point, Transform, Vector, World};
-    use std::f64::consts::PI;
+    use super::{AntiAlias, Projection};
+    use crate::consts::{FRAC_1_SQRT_2, PI};
+    use crate::ray_tracing::tiles::{Region, TiledRender};
+    use crate::ray_tracing::tone_map::PostProcess;
+    use crate::ray_tracing::world::SingularTransform;
+    use crate::{float_eq, Camera, Color, Float, Point, Transform, Vector, World};
 
     #[test]
     fn constructing_camera() {
@@ -223,19 +575,19 @@ mod tests {
     fn the_pixel_size_for_horizontal_canvas() {
         let c = Camera::new(200, 125, PI / 2.0);
 
-        assert_eq!(float_eq(c.pixel_size, 0.01), true);
+        assert!(float_eq(c.pixel_size, 0.01));
     }
 
     #[test]
     fn the_pixel_size_for_vertical_canvas() {
         let c = Camera::new(125, 200, PI / 2.0);
 
-        assert_eq!(float_eq(c.pixel_size, 0.01), true);
+        assert!(float_eq(c.pixel_size, 0.01));
     }
 
     #[test]
     fn constructing_a_ray_through_the_center_of_canvas() {
-        let mut c = Camera::new(201, 101, PI / 2.0);
+        let c = Camera::new(201, 101, PI / 2.0);
         let r = c.ray_for_pixel(100.0, 50.0);
 
         assert_eq!(r.origin, Point::new(0.0, 0.0, 0.0));
@@ -244,7 +596,7 @@ mod tests {
 
     #[test]
     fn constructing_a_ray_through_a_center_of_canvas() {
-        let mut c = Camera::new(201, 101, PI / 2.0);
+        let c = Camera::new(201, 101, PI / 2.0);
         let r = c.ray_for_pixel(0.0, 0.0);
 
         assert_eq!(r.origin, Point::new(0.0, 0.0, 0.0));
@@ -263,7 +615,7 @@ mod tests {
         assert_eq!(r.origin, Point::new(0.0, 2.0, -5.0));
         assert_eq!(
             r.direction,
-            Vector::new(2.0_f64.sqrt() / 2.0, 0.0, -2.0_f64.sqrt() / 2.0)
+            Vector::new(Float::sqrt(2.0) / 2.0, 0.0, -Float::sqrt(2.0) / 2.0)
         );
     }
 
@@ -275,8 +627,325 @@ mod tests {
         let to = Point::new(0.0, 0.0, 0.0);
         let up = Vector::new(0.0, 1.0, 0.0);
         c.transform = Transform::view_transformation(from, to, up);
-        let image = c.render(w, 0, 5);
+        let image = c.render(&w, AntiAlias::None, 5);
+
+        assert_eq!(
+            image.pixel_at(5, 5).unwrap(),
+            Color::new(0.38066, 0.47583, 0.2855)
+        );
+    }
+
+    #[test]
+    fn perspective_is_the_default_projection() {
+        let c = Camera::new(160, 120, PI / 2.0);
+
+        assert_eq!(
+            c.projection(),
+            Projection::Perspective {
+                field_of_view: PI / 2.0
+            }
+        );
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::orthographic(201, 101, 4.0);
+        let center = c.ray_for_pixel(100.0, 50.0);
+        let corner = c.ray_for_pixel(0.0, 0.0);
+
+        assert_eq!(center.origin, Point::new(0.0, 0.0, 0.0));
+        assert_eq!(center.direction, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(corner.origin, Point::new(1.99005, 0.99502, 0.0));
+        assert_eq!(corner.direction, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn orthographic_ray_through_a_transformed_camera() {
+        let mut c = Camera::orthographic(201, 101, 4.0);
+        c.transform = Transform::new().translation(0.0, -2.0, 5.0).build();
+        let r = c.ray_for_pixel(100.0, 50.0);
+
+        assert_eq!(r.origin, Point::new(0.0, 2.0, -5.0));
+        assert_eq!(r.direction, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn fisheye_ray_through_the_center_of_canvas() {
+        let c = Camera::fisheye(201, 101, PI);
+        let r = c.ray_for_pixel(100.0, 50.0);
+
+        assert_eq!(r.origin, Point::new(0.0, 0.0, 0.0));
+        assert_eq!(r.direction, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly_to_the_edge() {
+        let c = Camera::fisheye(200, 100, PI);
+        let r = c.ray_for_pixel(-0.5, 49.5);
+
+        assert_eq!(r.direction, Vector::new(1.0, 0.0, 0.0));
+
+        let r = c.ray_for_pixel(49.5, 49.5);
+        assert_eq!(r.direction, Vector::new(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn equirectangular_covers_the_full_sphere() {
+        let c = Camera::equirectangular(200, 100);
+
+        let forward = c.ray_for_pixel(99.5, 49.5);
+        assert_eq!(forward.direction, Vector::new(0.0, 0.0, -1.0));
+
+        let behind = c.ray_for_pixel(-0.5, 49.5);
+        assert_eq!(behind.direction, Vector::new(0.0, 0.0, 1.0));
+
+        let left = c.ray_for_pixel(49.5, 49.5);
+        assert_eq!(left.direction, Vector::new(1.0, 0.0, 0.0));
+
+        let up = c.ray_for_pixel(99.5, -0.5);
+        assert_eq!(up.direction, Vector::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn changing_the_resolution_recomputes_the_pixel_size() {
+        let mut c = Camera::new(200, 125, PI / 2.0);
+        c.set_resolution(400, 250);
+
+        assert_eq!(c.hsize(), 400);
+        assert_eq!(c.vsize(), 250);
+        assert!(float_eq(c.pixel_size(), 0.005));
+    }
+
+    #[test]
+    fn horizontal_field_of_view_on_a_vertical_canvas() {
+        let mut c = Camera::new(125, 200, PI / 2.0);
+        c.set_horizontal_field_of_view(PI / 2.0);
+
+        assert!(float_eq(c.pixel_size(), 0.016));
+    }
+
+    #[test]
+    fn vertical_field_of_view_on_a_horizontal_canvas() {
+        let mut c = Camera::new(200, 100, PI / 2.0);
+        c.set_vertical_field_of_view(PI / 2.0);
+
+        assert!(float_eq(c.pixel_size(), 0.02));
+        let r = c.ray_for_pixel(99.5, -0.5);
+        assert_eq!(r.direction, Vector::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn builder_matches_view_transformation() {
+        let from = Point::new(0.0, 0.0, -5.0);
+        let to = Point::new(0.0, 0.0, 0.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let c = Camera::builder(11, 11)
+            .look_at(from, to, up)
+            .horizontal_fov_degrees(90.0)
+            .build();
 
-        assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(c.transform, Transform::view_transformation(from, to, up));
+        assert_eq!(
+            c.projection(),
+            Projection::Perspective {
+                field_of_view: PI / 2.0
+            }
+        );
+        let r = c.ray_for_pixel(5.0, 5.0);
+        assert_eq!(r.origin, from);
+    }
+
+    #[test]
+    fn builder_focal_length_gives_horizontal_field_of_view() {
+        let c = Camera::builder(200, 100)
+            .sensor_width(36.0)
+            .focal_length(18.0)
+            .build();
+
+        assert_eq!(
+            c.projection(),
+            Projection::Perspective {
+                field_of_view: PI / 2.0
+            }
+        );
+        assert!(float_eq(c.pixel_size(), 0.01));
+    }
+
+    #[test]
+    fn field_of_view_does_not_change_non_angular_projections() {
+        let orthographic = Projection::Orthographic { width: 4.0 };
+        let c = Camera::builder(200, 100)
+            .projection(orthographic)
+            .field_of_view(PI / 2.0)
+            .focal_length(18.0)
+            .build();
+        assert_eq!(c.projection(), orthographic);
+        assert!(float_eq(c.pixel_size(), 0.02));
+
+        let mut c = Camera::equirectangular(200, 100);
+        c.set_vertical_field_of_view(PI / 3.0);
+        assert_eq!(c.projection(), Projection::Equirectangular);
+
+        let mut c = Camera::fisheye(200, 100, PI);
+        c.set_field_of_view(PI / 2.0);
+        assert_eq!(
+            c.projection(),
+            Projection::Fisheye {
+                field_of_view: PI / 2.0
+            }
+        );
+    }
+
+    #[test]
+    fn renders_with_the_same_seed_are_identical() {
+        let mut c = Camera::builder(11, 11)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .seed(42)
+            .build();
+        let w = World::default();
+        let a = c.render(&w, AntiAlias::Random(4), 5);
+        let b = c.render(&w, AntiAlias::Random(4), 5);
+        c.set_seed(43);
+        let d = c.render(&w, AntiAlias::Random(4), 5);
+
+        let bits = |canvas: &crate::Canvas| {
+            let mut bits = Vec::new();
+            for p in canvas.pixels() {
+                bits.push((p.red.to_bits(), p.green.to_bits(), p.blue.to_bits()));
+            }
+            bits
+        };
+        assert_eq!(bits(&a), bits(&b));
+        assert_ne!(bits(&a), bits(&d));
+    }
+
+    #[test]
+    fn rendering_a_singular_transform_is_an_error() {
+        let mut w = World::default();
+        let id = w.get_shape_at(0).id();
+        w.get_shape_mut(id)
+            .unwrap()
+            .set_transform(Transform::new().scaling(1.0, 0.0, 1.0).build());
+        let c = Camera::builder(3, 3).build();
+        let error = Some(SingularTransform { shape: id });
+        assert_eq!(c.try_render(&w, AntiAlias::None, 5).err(), error);
+        assert_eq!(c.render_passes(&w, AntiAlias::None, 5).err(), error);
+        assert_eq!(c.render_session(&w, 5).err(), error);
+        let region = Region::new(0, 0, 2, 2);
+        assert_eq!(c.render_region(&w, AntiAlias::None, 5, region).err(), error);
+        assert_eq!(
+            TiledRender::new(&c, &w, AntiAlias::None, 5, 2, 2).err(),
+            error
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "non-invertible transform")]
+    fn render_panics_on_a_singular_transform() {
+        let mut w = World::default();
+        let id = w.get_shape_at(0).id();
+        w.get_shape_mut(id)
+            .unwrap()
+            .set_transform(Transform::new().scaling(1.0, 0.0, 1.0).build());
+        Camera::builder(3, 3).build().render(&w, AntiAlias::None, 5);
+    }
+
+    #[test]
+    fn rendering_to_a_file_matches_the_rendered_canvas() {
+        let w = World::default();
+        let c = Camera::builder(11, 11)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build();
+        let file_name = crate::temp_path("render_to_file.png");
+        let file_name = file_name.to_str().unwrap();
+
+        let settings = PostProcess::display();
+        c.render_to_file(&w, AntiAlias::Random(4), 5, &settings, file_name)
+            .unwrap();
+        let expected = c
+            .render(&w, AntiAlias::Random(4), 5)
+            .to_rgb_image_with(&settings);
+        let actual = image::open(file_name).unwrap().to_rgb8();
+        std::fs::remove_file(file_name).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn filtered_passes_add_up_to_the_beauty_image() {
+        let mut w = World::default();
+        let outer = w.get_shape_at(0).id();
+        w.get_shape_mut(outer).unwrap().material_mut().reflective = 0.5;
+        let c = Camera::builder(9, 9)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build();
+        let passes = c.render_passes(&w, AntiAlias::Random(4), 5).unwrap();
+        let beauty = c.render(&w, AntiAlias::Random(4), 5);
+
+        for (x, y, color) in passes.beauty.enumerate_pixels() {
+            assert_eq!(color, beauty.pixel_at(x, y).unwrap());
+            let parts = passes.direct.pixel_at(x, y).unwrap()
+                + passes.reflection.pixel_at(x, y).unwrap()
+                + passes.refraction.pixel_at(x, y).unwrap();
+            assert_eq!(parts, color);
+        }
+        assert_ne!(
+            passes.reflection.pixel_at(4, 4),
+            Some(Color::new(0.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn rendering_auxiliary_passes() {
+        let w = World::default();
+        let c = Camera::builder(11, 11)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build();
+        let passes = c.render_passes(&w, AntiAlias::None, 5).unwrap();
+
+        assert_eq!(
+            passes.beauty.pixel_at(5, 5).unwrap(),
+            Color::new(0.38066, 0.47583, 0.2855)
+        );
+        assert!(crate::float_eq(passes.depth_at(5, 5).unwrap(), 4.0));
+        assert_eq!(
+            passes.normal.pixel_at(5, 5).unwrap(),
+            Color::new(0.0, 0.0, -1.0)
+        );
+        assert_eq!(
+            passes.albedo.pixel_at(5, 5).unwrap(),
+            Color::new(0.8, 1.0, 0.6)
+        );
+        let outer = w.get_shape_at(0);
+        assert_eq!(passes.object_id_at(5, 5), Some(outer.id()));
+        assert_eq!(passes.material_id_at(5, 5), Some(outer.material().id()));
+
+        // the corner ray misses both spheres
+        assert_eq!(passes.depth_at(0, 0), Some(Float::INFINITY));
+        assert_eq!(passes.object_id_at(0, 0), None);
+        assert_eq!(
+            passes.object_id_matte().pixel_at(0, 0),
+            Some(Color::new(0.0, 0.0, 0.0))
+        );
     }
 }
@@ -1,9 +1,44 @@
-use crate::Color;
+use super::compare::{self, Comparison, SizeMismatch};
+use super::hdr;
+use super::ppm::{self, PpmError};
+use super::tone_map::PostProcess;
+use crate::{Color, Float};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, ImageFormat, ImageResult, Rgb, RgbImage};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 
-pub struct Canvas {
+/// A pixel coordinate outside of the canvas.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
     pub width: usize,
     pub height: usize,
-    pub pixels: Vec<Vec<Color>>,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pixel ({}, {}) is outside of the {}x{} canvas",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+impl Error for OutOfBounds {}
+
+/// Image buffer, pixels are stored in one row-major `Vec` starting at the
+/// top left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
 }
 
 impl Canvas {
@@ -11,75 +46,377 @@ impl Canvas {
         Canvas {
             width,
             height,
-            pixels: vec![vec![Color::new(0.0, 0.0, 0.0); height]; width],
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn pixel_at(&self, x: usize, y: usize) -> Option<Color> {
+        self.index(x, y).map(|i| self.pixels[i])
+    }
+
+    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) -> Result<(), OutOfBounds> {
+        match self.index(x, y) {
+            Some(i) => {
+                self.pixels[i] = color;
+                Ok(())
+            }
+            None => Err(OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            }),
+        }
+    }
+
+    /// All pixels in row-major order.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// Iterates over the rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        self.pixels.chunks_exact(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> {
+        self.pixels.chunks_exact_mut(self.width.max(1))
+    }
+
+    /// Iterates over `(x, y, color)` of every pixel in row-major order.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        let width = self.width.max(1);
+        self.pixels
+            .iter()
+            .enumerate()
+            .map(move |(i, &c)| (i % width, i / width, c))
+    }
+
+    /// A read only view of the rectangle at x, y. Returns `None` unless the
+    /// rectangle lies completely inside the canvas.
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<CanvasView<'_>> {
+        if x.checked_add(width)? <= self.width && y.checked_add(height)? <= self.height {
+            Some(CanvasView {
+                canvas: self,
+                x,
+                y,
+                width,
+                height,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Copies the rectangle at x, y into a new canvas.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Option<Canvas> {
+        self.view(x, y, width, height).map(|v| v.to_canvas())
+    }
+
+    /// Copies `source` into this canvas with its top left corner at x, y.
+    /// Fails without changing anything unless the source fits completely.
+    pub fn paste(&mut self, source: &Canvas, x: usize, y: usize) -> Result<(), OutOfBounds> {
+        let right = x.saturating_add(source.width);
+        let bottom = y.saturating_add(source.height);
+        if right > self.width || bottom > self.height {
+            return Err(OutOfBounds {
+                x: right.saturating_sub(1),
+                y: bottom.saturating_sub(1),
+                width: self.width,
+                height: self.height,
+            });
+        }
+        for (row, source_row) in self.rows_mut().skip(y).zip(source.rows()) {
+            row[x..right].copy_from_slice(source_row);
         }
+        Ok(())
+    }
+
+    /// Splits the canvas into tiles of at most `tile_width` by
+    /// `tile_height` pixels, row by row. Tiles on the right and bottom
+    /// edges are smaller when the size is not a multiple of the tile size.
+    pub fn tiles(
+        &self,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> impl Iterator<Item = CanvasView<'_>> {
+        let tile_width = tile_width.max(1);
+        let tile_height = tile_height.max(1);
+        (0..self.height)
+            .step_by(tile_height)
+            .flat_map(move |y| (0..self.width).step_by(tile_width).map(move |x| (x, y)))
+            .map(move |(x, y)| CanvasView {
+                canvas: self,
+                x,
+                y,
+                width: tile_width.min(self.width - x),
+                height: tile_height.min(self.height - y),
+            })
+    }
+
+    /// Measures how much `other` differs from this canvas.
+    pub fn compare(&self, other: &Canvas) -> Result<Comparison, SizeMismatch> {
+        compare::compare(self, other)
+    }
+
+    /// Builds a canvas from an 8 bit image, channels are mapped to 0..1.
+    pub fn from_rgb_image(img: &RgbImage) -> Canvas {
+        let mut canvas = Canvas::new(img.width() as usize, img.height() as usize);
+        for (x, y, pixel) in img.enumerate_pixels() {
+            let [r, g, b] = pixel.0;
+            let c = Color::new(r as Float / 255.0, g as Float / 255.0, b as Float / 255.0);
+            canvas.pixels[y as usize * canvas.width + x as usize] = c;
+        }
+        canvas
+    }
+
+    /// Parses a plain (`P3`) or binary (`P6`) PPM image.
+    pub fn from_ppm(data: &[u8]) -> Result<Canvas, PpmError> {
+        ppm::parse(data)
+    }
+
+    /// Loads an image file. PPM files are read by `from_ppm`, every other
+    /// format supported by the `image` crate (PNG, JPEG, ...) is converted
+    /// to 8 bit RGB first.
+    pub fn load(file_name: &str) -> ImageResult<Canvas> {
+        if has_extension(file_name, "ppm") {
+            let data = std::fs::read(file_name)?;
+            return Canvas::from_ppm(&data).map_err(|e| {
+                ImageError::Decoding(DecodingError::new(
+                    ImageFormatHint::Exact(ImageFormat::Pnm),
+                    e,
+                ))
+            });
+        }
+        Ok(Canvas::from_rgb_image(&image::open(file_name)?.to_rgb8()))
+    }
+
+    /// Quantizes the canvas to 8 bits per channel, values are clamped to
+    /// 0..1 and written linearly.
+    pub fn to_rgb_image(&self) -> RgbImage {
+        self.to_rgb_image_with(&PostProcess::new())
+    }
+
+    /// Returns a copy with exposure, tone mapping and the transfer curve
+    /// applied, values stay unquantized.
+    pub fn post_process(&self, settings: &PostProcess) -> Canvas {
+        let mut canvas = self.clone();
+        for pixel in canvas.pixels.iter_mut() {
+            *pixel = settings.apply(*pixel);
+        }
+        canvas
+    }
+
+    /// Quantizes the canvas to 8 bits per channel after post processing.
+    pub fn to_rgb_image_with(&self, settings: &PostProcess) -> RgbImage {
+        let mut img = RgbImage::new(self.width as u32, self.height as u32);
+        for (x, y, color) in self.enumerate_pixels() {
+            let (r, g, b) = settings.quantize(color, x, y);
+            img.put_pixel(x as u32, y as u32, Rgb([r, g, b]));
+        }
+        img
+    }
+
+    /// Saves an 8 bit image after post processing. `.hdr` and `.exr` files
+    /// keep the linear values and ignore the settings.
+    pub fn save_with(&self, file_name: &str, settings: &PostProcess) -> ImageResult<()> {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hdr") | Some("exr") => self.save(file_name),
+            _ => self.to_rgb_image_with(settings).save(file_name),
+        }
+    }
+
+    /// Saves the canvas, the format is taken from the file extension. `.hdr`
+    /// and `.exr` files keep the linear high dynamic range values, every
+    /// other format is quantized to 8 bits per channel.
+    pub fn save(&self, file_name: &str) -> ImageResult<()> {
+        if has_extension(file_name, "hdr") {
+            Ok(self.save_hdr(file_name)?)
+        } else if has_extension(file_name, "exr") {
+            Ok(self.save_exr(file_name)?)
+        } else if has_extension(file_name, "ppm") {
+            Ok(self.save_ppm(file_name)?)
+        } else {
+            self.to_rgb_image().save(file_name)
+        }
+    }
+
+    /// Saves a binary (`P6`) PPM image.
+    pub fn save_ppm(&self, file_name: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(file_name)?);
+        ppm::write_p6(self, &mut w)?;
+        w.flush()
+    }
+
+    pub fn save_hdr(&self, file_name: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(file_name)?);
+        hdr::write_radiance(self, &mut w)?;
+        w.flush()
+    }
+
+    pub fn save_exr(&self, file_name: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(file_name)?);
+        hdr::write_open_exr(self, &mut w)?;
+        w.flush()
     }
 
-    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
-        self.pixels[x][y]
+    pub fn canvas_to_ppm_binary(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        ppm::write_p6(self, &mut buffer).unwrap();
+        buffer
     }
 
     pub fn canvas_to_ppm(&self) -> String {
         let mut buffer = ["P3", &format!("{} {}", self.width, self.height), "255"].join("\n");
-        buffer.push_str("\n");
+        buffer.push('\n');
 
+        let settings = PostProcess::new();
         let mut col_counter = 0;
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let p = &self.pixels[x][y];
-                let red = Color::rgb_string(p.red);
-                let green = Color::rgb_string(p.green);
-                let blue = Color::rgb_string(p.blue);
+        for (y, row) in self.rows().enumerate() {
+            for (x, p) in row.iter().enumerate() {
+                let (red, green, blue) = settings.quantize(*p, x, y);
+                let red = red.to_string();
+                let green = green.to_string();
+                let blue = blue.to_string();
 
                 buffer.push_str(&Canvas::write_color(red, &mut col_counter));
                 buffer.push_str(&Canvas::write_color(green, &mut col_counter));
                 if x == self.width - 1 {
-                    buffer.push_str(&Canvas::write_color(blue, &mut col_counter).trim());
+                    buffer.push_str(Canvas::write_color(blue, &mut col_counter).trim());
                 } else {
                     buffer.push_str(&Canvas::write_color(blue, &mut col_counter));
                 }
             }
-            buffer.push_str("\n");
+            buffer.push('\n');
             col_counter = 0;
         }
-        buffer.push_str("\n");
+        buffer.push('\n');
         buffer
     }
 
     fn write_color(color: String, col_count: &mut usize) -> String {
         let mut color_buffer = String::new();
         if *col_count + color.len() > 70 {
-            color_buffer.push_str("\n");
+            color_buffer.push('\n');
             *col_count = 0;
         }
         color_buffer.push_str(&color);
         *col_count += color.len();
 
         if *col_count + 4 > 70 {
-            color_buffer.push_str("\n");
+            color_buffer.push('\n');
             *col_count = 0;
         } else {
-            color_buffer.push_str(" ");
+            color_buffer.push(' ');
             *col_count += 1;
         }
         color_buffer
     }
 }
 
+/// Borrowed rectangular region of a canvas, see `Canvas::view` and
+/// `Canvas::tiles`.
+#[derive(Debug, Copy, Clone)]
+pub struct CanvasView<'a> {
+    canvas: &'a Canvas,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> CanvasView<'a> {
+    /// Position of the top left corner in the canvas.
+    pub fn origin(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixel at x, y relative to the view's origin.
+    pub fn pixel_at(&self, x: usize, y: usize) -> Option<Color> {
+        if x < self.width && y < self.height {
+            self.canvas.pixel_at(self.x + x, self.y + y)
+        } else {
+            None
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [Color]> {
+        let (x, width) = (self.x, self.width);
+        self.canvas
+            .rows()
+            .skip(self.y)
+            .take(self.height)
+            .map(move |row| &row[x..x + width])
+    }
+
+    pub fn to_canvas(&self) -> Canvas {
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for row in self.rows() {
+            pixels.extend_from_slice(row);
+        }
+        Canvas {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+fn has_extension(file_name: &str, extension: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ray_tracing::color;
+    use crate::ray_tracing::tone_map::ToneMap;
     use crate::Color;
 
     #[test]
     fn create_canvas() {
         let c = Canvas::new(10, 20);
-        assert_eq!(10, c.width);
-        assert_eq!(20, c.height);
+        assert_eq!(10, c.width());
+        assert_eq!(20, c.height());
         let expected = Color::new(0.0, 0.0, 0.0);
         for x in 0..10 {
             for y in 0..20 {
-                let color = c.pixels[x][y];
+                let color = c.pixel_at(x, y).unwrap();
                 assert_eq!(expected, color);
             }
         }
@@ -88,9 +425,9 @@ mod tests {
     #[test]
     fn write_canvas() {
         let mut c = Canvas::new(10, 20);
-        c.pixels[2][3] = Color::new(1.0, 0.0, 0.0);
+        c.write_pixel(2, 3, Color::new(1.0, 0.0, 0.0)).unwrap();
         let expected = Color::new(1.0, 0.0, 0.0);
-        assert_eq!(expected, c.pixels[2][3]);
+        assert_eq!(expected, c.pixel_at(2, 3).unwrap());
     }
 
     #[test]
@@ -110,9 +447,9 @@ mod tests {
         let c1 = Color::new(1.5, 0.0, 0.0);
         let c2 = Color::new(0.0, 0.5, 0.0);
         let c3 = Color::new(-0.5, 0.0, 1.0);
-        c.pixels[0][0] = c1;
-        c.pixels[2][1] = c2;
-        c.pixels[4][2] = c3;
+        c.write_pixel(0, 0, c1).unwrap();
+        c.write_pixel(2, 1, c2).unwrap();
+        c.write_pixel(4, 2, c3).unwrap();
         let actual = c.canvas_to_ppm();
         let split = actual.split("\n").collect::<Vec<_>>();
         assert_eq!("255 0 0 0 0 0 0 0 0 0 0 0 0 0 0", split[3]);
@@ -125,7 +462,7 @@ mod tests {
         let mut c = Canvas::new(10, 2);
         for x in 0..10 {
             for y in 0..2 {
-                c.pixels[x][y] = Color::new(1.0, 0.8, 0.6);
+                c.write_pixel(x, y, Color::new(1.0, 0.8, 0.6)).unwrap();
             }
         }
         let actual = c.canvas_to_ppm();
@@ -148,4 +485,157 @@ mod tests {
             split[6]
         );
     }
+
+    #[test]
+    fn save_picks_the_hdr_writer_from_the_extension() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(1, 0, Color::new(8.0, 0.0, 0.0)).unwrap();
+        let file_name = crate::temp_path("canvas_save.hdr");
+        let file_name = file_name.to_str().unwrap();
+
+        c.save(file_name).unwrap();
+        let bytes = std::fs::read(file_name).unwrap();
+        std::fs::remove_file(file_name).unwrap();
+
+        assert!(bytes.starts_with(b"#?RADIANCE"));
+        assert_eq!(&bytes[bytes.len() - 4..], &[128, 0, 0, 132]);
+    }
+
+    #[test]
+    fn post_processing_a_canvas() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 1.0, 1.0)).unwrap();
+        c.write_pixel(1, 0, Color::new(3.0, 3.0, 3.0)).unwrap();
+        let mut settings = PostProcess::new();
+        settings.tone_map = ToneMap::Reinhard;
+
+        let p = c.post_process(&settings);
+        assert_eq!(p.pixel_at(0, 0).unwrap(), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(p.pixel_at(1, 0).unwrap(), Color::new(0.75, 0.75, 0.75));
+
+        settings.srgb = true;
+        let img = c.to_rgb_image_with(&settings);
+        assert_eq!(img.get_pixel(0, 0).0, [188, 188, 188]);
+    }
+
+    #[test]
+    fn every_8_bit_writer_quantizes_the_same_way() {
+        let color = Color::new(0.3, 0.5, 1.2);
+        let mut c = Canvas::new(1, 1);
+        c.write_pixel(0, 0, color).unwrap();
+        let expected = PostProcess::new().quantize(color, 0, 0);
+        assert_eq!(expected, (77, 128, 255));
+
+        assert_eq!(color.to_rgb(), expected);
+        assert_eq!(c.to_rgb_image().get_pixel(0, 0).0, [77, 128, 255]);
+        assert_eq!(c.canvas_to_ppm().lines().nth(3), Some("77 128 255"));
+        assert!(c.canvas_to_ppm_binary().ends_with(&[77, 128, 255]));
+    }
+
+    #[test]
+    fn png_and_ppm_files_round_trip() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0)).unwrap();
+        c.write_pixel(2, 1, Color::new(0.0, 0.2, 1.0)).unwrap();
+
+        for extension in &["png", "ppm"] {
+            let file_name = crate::temp_path(&format!("round_trip.{}", extension));
+            let file_name = file_name.to_str().unwrap();
+            c.save(file_name).unwrap();
+            let loaded = Canvas::load(file_name).unwrap();
+            std::fs::remove_file(file_name).unwrap();
+
+            assert_eq!(loaded.width(), 3);
+            assert_eq!(loaded.height(), 2);
+            assert_eq!(loaded.pixel_at(0, 0).unwrap(), Color::new(1.0, 0.0, 0.0));
+            assert_eq!(loaded.pixel_at(2, 1).unwrap(), Color::new(0.0, 0.2, 1.0));
+        }
+    }
+
+    #[test]
+    fn loading_a_broken_ppm_reports_the_error() {
+        let file_name = crate::temp_path("broken.ppm");
+        let file_name = file_name.to_str().unwrap();
+        std::fs::write(file_name, "P3 2 2 255 0 0").unwrap();
+        let result = Canvas::load(file_name);
+        std::fs::remove_file(file_name).unwrap();
+
+        assert!(matches!(result, Err(ImageError::Decoding(_))));
+    }
+
+    #[test]
+    fn accessing_pixels_outside_of_the_canvas() {
+        let mut c = Canvas::new(4, 3);
+        assert_eq!(c.pixel_at(4, 0), None);
+        assert_eq!(c.pixel_at(0, 3), None);
+        assert_eq!(
+            c.write_pixel(4, 0, color::RED),
+            Err(OutOfBounds {
+                x: 4,
+                y: 0,
+                width: 4,
+                height: 3
+            })
+        );
+    }
+
+    #[test]
+    fn pixels_are_stored_row_by_row() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(1, 0, color::RED).unwrap();
+        c.write_pixel(2, 1, color::WHITE).unwrap();
+        assert_eq!(c.pixels()[1], color::RED);
+        assert_eq!(c.pixels()[5], color::WHITE);
+
+        let rows = c.rows().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], &[color::BLACK, color::BLACK, color::WHITE]);
+
+        let (x, y, _) = c.enumerate_pixels().nth(5).unwrap();
+        assert_eq!((x, y), (2, 1));
+    }
+
+    #[test]
+    fn tiles_cover_the_canvas_once() {
+        let c = Canvas::new(5, 3);
+        let tiles = c.tiles(2, 2).collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[2].origin(), (4, 0));
+        assert_eq!((tiles[2].width(), tiles[2].height()), (1, 2));
+        assert_eq!((tiles[5].width(), tiles[5].height()), (1, 1));
+
+        let area: usize = tiles.iter().map(|t| t.width() * t.height()).sum();
+        assert_eq!(area, 15);
+    }
+
+    #[test]
+    fn views_and_crops_are_relative_to_their_origin() {
+        let mut c = Canvas::new(4, 4);
+        c.write_pixel(2, 1, color::RED).unwrap();
+
+        let view = c.view(1, 1, 2, 3).unwrap();
+        assert_eq!(view.pixel_at(1, 0), Some(color::RED));
+        assert_eq!(view.pixel_at(2, 0), None);
+        assert_eq!(view.rows().count(), 3);
+
+        let cropped = c.crop(2, 1, 2, 2).unwrap();
+        assert_eq!(cropped.width(), 2);
+        assert_eq!(cropped.pixel_at(0, 0), Some(color::RED));
+
+        assert!(c.view(3, 0, 2, 1).is_none());
+        assert!(c.crop(0, 0, 5, 4).is_none());
+    }
+
+    #[test]
+    fn pasting_a_canvas() {
+        let mut c = Canvas::new(4, 3);
+        let mut patch = Canvas::new(2, 2);
+        patch.write_pixel(1, 1, color::RED).unwrap();
+
+        c.paste(&patch, 2, 1).unwrap();
+        assert_eq!(c.pixel_at(3, 2), Some(color::RED));
+        assert_eq!(c.crop(2, 1, 2, 2).unwrap(), patch);
+
+        assert!(c.paste(&patch, 3, 0).is_err());
+    }
 }
@@ -1,4 +1,5 @@
-use crate::float_eq;
+use super::tone_map::PostProcess;
+use crate::{float_eq, Float};
 use rand::thread_rng;
 use rand::Rng;
 use std::ops::{Add, Div, Mul, Neg, Sub};
@@ -22,34 +23,40 @@ pub const RED: Color = Color {
 };
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
-    pub red: f64,
-    pub green: f64,
-    pub blue: f64,
+    pub red: Float,
+    pub green: Float,
+    pub blue: Float,
 }
 
 impl Color {
-    pub fn new(red: f64, green: f64, blue: f64) -> Self {
+    pub fn new(red: Float, green: Float, blue: Float) -> Self {
         Color { red, green, blue }
     }
 
     pub fn new_rgb(red: i32, green: i32, blue: i32) -> Self {
         Color {
-            red: red as f64 / 255.0,
-            green: green as f64 / 255.0,
-            blue: blue as f64 / 255.0,
+            red: red as Float / 255.0,
+            green: green as Float / 255.0,
+            blue: blue as Float / 255.0,
         }
     }
 
     pub fn random() -> Self {
-        let mut rng = thread_rng();
-        let red: f64 = rng.gen_range(0.0, 1.0);
-        let green: f64 = rng.gen_range(0.0, 1.0);
-        let blue: f64 = rng.gen_range(0.0, 1.0);
+        Color::random_from(&mut thread_rng())
+    }
+
+    /// Random color drawn from the given generator, pass a seeded generator
+    /// to get the same colors on every run.
+    pub fn random_from<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let red: Float = rng.gen_range(0.0, 1.0);
+        let green: Float = rng.gen_range(0.0, 1.0);
+        let blue: Float = rng.gen_range(0.0, 1.0);
         Color { red, green, blue }
     }
 
-    pub fn from_tuple(color: (f64, f64, f64)) -> Self {
+    pub fn from_tuple(color: (Float, Float, Float)) -> Self {
         Color {
             red: color.0,
             green: color.1,
@@ -57,28 +64,21 @@ impl Color {
         }
     }
 
-    pub fn rgb_string(color: f64) -> String {
-        let mut rgb = color * 256.;
-        if rgb < 0.0 {
-            rgb = 0.0;
-        }
-        if rgb > 255.0 {
-            rgb = 255.0;
-        }
-        let rgb = rgb as i64;
+    /// One channel quantized like `to_rgb`.
+    pub fn rgb_string(color: Float) -> String {
+        let (rgb, _, _) = Color::new(color, 0.0, 0.0).to_rgb();
         format!("{}", rgb)
     }
 
-    pub fn to_rgb(&self) -> (u8, u8, u8) {
-        (
-            Color::color_to_u8(self.red),
-            Color::color_to_u8(self.green),
-            Color::color_to_u8(self.blue),
-        )
+    /// Relative luminance using the Rec. 709 primaries.
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
     }
 
-    fn color_to_u8(c: f64) -> u8 {
-        (255.0 * c) as u8
+    /// Clamps to 0..1 and rounds to 8 bits per channel, `PostProcess::new()`
+    /// quantization.
+    pub fn to_rgb(&self) -> (u8, u8, u8) {
+        PostProcess::new().quantize(*self, 0, 0)
     }
 }
 
@@ -118,10 +118,10 @@ impl Mul for Color {
     }
 }
 
-impl Mul<f64> for Color {
+impl Mul<Float> for Color {
     type Output = Color;
 
-    fn mul(self, other: f64) -> Color {
+    fn mul(self, other: Float) -> Color {
         Color {
             red: self.red * other,
             green: self.green * other,
@@ -130,7 +130,7 @@ impl Mul<f64> for Color {
     }
 }
 
-impl Mul<Color> for f64 {
+impl Mul<Color> for Float {
     type Output = Color;
 
     fn mul(self, other: Color) -> Color {
@@ -142,10 +142,10 @@ impl Mul<Color> for f64 {
     }
 }
 
-impl Div<f64> for Color {
+impl Div<Float> for Color {
     type Output = Color;
 
-    fn div(self, other: f64) -> Color {
+    fn div(self, other: Float) -> Color {
         Color {
             red: self.red / other,
             green: self.green / other,
@@ -229,4 +229,12 @@ mod tests {
         let expected = Color::new(0.9, 0.2, 0.04);
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn random_colors_from_the_same_seed_match() {
+        use rand::SeedableRng;
+        let mut a = rand_pcg::Pcg32::seed_from_u64(1);
+        let mut b = rand_pcg::Pcg32::seed_from_u64(1);
+        assert_eq!(Color::random_from(&mut a), Color::random_from(&mut b));
+    }
 }
@@ -0,0 +1,282 @@
+use crate::{Canvas, Color, Float};
+use std::error::Error;
+use std::fmt;
+
+/// The two canvases have different dimensions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub expected: (usize, usize),
+    pub actual: (usize, usize),
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a {}x{} canvas, found {}x{}",
+            self.expected.0, self.expected.1, self.actual.0, self.actual.1
+        )
+    }
+}
+
+impl Error for SizeMismatch {}
+
+/// Error metrics between a reference and a test canvas. Errors are measured
+/// on the linear channel values, PSNR and SSIM assume a peak value of 1.0.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// Per channel absolute difference of every pixel.
+    pub errors: Canvas,
+    pub max_error: Float,
+    pub mean_error: Float,
+    pub rmse: Float,
+    /// Peak signal to noise ratio in dB, infinite for identical canvases.
+    pub psnr: Float,
+    /// Mean structural similarity of the luminance, 1.0 for identical
+    /// canvases.
+    pub ssim: Float,
+}
+
+/// Limits a comparison has to stay within.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tolerance {
+    pub max_error: Float,
+    pub rmse: Float,
+    pub min_ssim: Float,
+}
+
+impl Tolerance {
+    pub fn new(max_error: Float, rmse: Float, min_ssim: Float) -> Tolerance {
+        Tolerance {
+            max_error,
+            rmse,
+            min_ssim,
+        }
+    }
+
+    pub fn exact() -> Tolerance {
+        Tolerance::new(0.0, 0.0, 1.0)
+    }
+}
+
+impl Comparison {
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        self.max_error <= tolerance.max_error
+            && self.rmse <= tolerance.rmse
+            && self.ssim >= tolerance.min_ssim
+    }
+
+    /// Visualizes the largest channel error of every pixel. Errors of
+    /// `scale` and above are white, smaller ones fade through yellow and
+    /// red to black.
+    pub fn heatmap(&self, scale: Float) -> Canvas {
+        let mut heatmap = Canvas::new(self.errors.width(), self.errors.height());
+        for (pixel, e) in heatmap.pixels_mut().iter_mut().zip(self.errors.pixels()) {
+            let t = (e.red.max(e.green).max(e.blue) / scale).clamp(0.0, 1.0) * 3.0;
+            *pixel = Color::new(
+                t.clamp(0.0, 1.0),
+                (t - 1.0).clamp(0.0, 1.0),
+                (t - 2.0).clamp(0.0, 1.0),
+            );
+        }
+        heatmap
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "max error {:.5}, mean error {:.5}, rmse {:.5}, psnr {:.2} dB, ssim {:.5}",
+            self.max_error, self.mean_error, self.rmse, self.psnr, self.ssim
+        )
+    }
+}
+
+pub fn compare(expected: &Canvas, actual: &Canvas) -> Result<Comparison, SizeMismatch> {
+    let size = |c: &Canvas| (c.width(), c.height());
+    if size(expected) != size(actual) {
+        return Err(SizeMismatch {
+            expected: size(expected),
+            actual: size(actual),
+        });
+    }
+
+    let mut errors = Canvas::new(expected.width(), expected.height());
+    let mut max_error: Float = 0.0;
+    let mut sum = 0.0;
+    let mut squared_sum = 0.0;
+    for ((e, a), error) in expected
+        .pixels()
+        .iter()
+        .zip(actual.pixels())
+        .zip(errors.pixels_mut())
+    {
+        *error = Color::new(
+            (e.red - a.red).abs(),
+            (e.green - a.green).abs(),
+            (e.blue - a.blue).abs(),
+        );
+        for &d in &[error.red, error.green, error.blue] {
+            max_error = max_error.max(d);
+            sum += d;
+            squared_sum += d * d;
+        }
+    }
+
+    let samples = (expected.pixels().len() * 3).max(1) as Float;
+    let mse = squared_sum / samples;
+    Ok(Comparison {
+        errors,
+        max_error,
+        mean_error: sum / samples,
+        rmse: mse.sqrt(),
+        psnr: if mse > 0.0 {
+            -10.0 * mse.log10()
+        } else {
+            Float::INFINITY
+        },
+        ssim: ssim(expected, actual),
+    })
+}
+
+/// Mean SSIM over all 7x7 windows of the luminance. The luminance is clamped
+/// to 0..1 first, the result lies in -1..1 and is 1 for identical images.
+fn ssim(a: &Canvas, b: &Canvas) -> Float {
+    let (width, height) = (a.width(), a.height());
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let luminance = |c: &Canvas| -> Vec<Float> {
+        c.pixels()
+            .iter()
+            .map(|p| p.luminance().clamp(0.0, 1.0))
+            .collect()
+    };
+    let la = luminance(a);
+    let lb = luminance(b);
+
+    let c1 = Float::powi(0.01, 2);
+    let c2 = Float::powi(0.03, 2);
+    let window_width = width.min(7);
+    let window_height = height.min(7);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y in 0..=height - window_height {
+        for x in 0..=width - window_width {
+            let n = (window_width * window_height) as Float;
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for wy in y..y + window_height {
+                for wx in x..x + window_width {
+                    let va = la[wy * width + wx];
+                    let vb = lb[wy * width + wx];
+                    sa += va;
+                    sb += vb;
+                    saa += va * va;
+                    sbb += vb * vb;
+                    sab += va * vb;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let var_a = saa / n - ma * ma;
+            let var_b = sbb / n - mb * mb;
+            let cov = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + c1) * (2.0 * cov + c2))
+                / ((ma * ma + mb * mb + c1) * (var_a + var_b + c2));
+            windows += 1;
+        }
+    }
+    total / windows as Float
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq;
+    use crate::ray_tracing::color;
+
+    fn gradient(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for (x, y, _) in c.clone().enumerate_pixels() {
+            let v = (x + y) as Float / (width + height) as Float;
+            c.write_pixel(x, y, Color::new(v, v, v)).unwrap();
+        }
+        c
+    }
+
+    #[test]
+    fn identical_canvases() {
+        let c = gradient(16, 16);
+        let result = compare(&c, &c).unwrap();
+        assert_eq!(result.max_error, 0.0);
+        assert_eq!(result.rmse, 0.0);
+        assert_eq!(result.psnr, Float::INFINITY);
+        assert!(float_eq(result.ssim, 1.0));
+        assert!(result.within(&Tolerance::exact()));
+    }
+
+    #[test]
+    fn error_metrics_of_a_single_pixel() {
+        let a = Canvas::new(2, 2);
+        let mut b = Canvas::new(2, 2);
+        b.write_pixel(1, 0, Color::new(0.5, 0.0, 0.0)).unwrap();
+
+        let result = compare(&a, &b).unwrap();
+        assert_eq!(
+            result.errors.pixel_at(1, 0),
+            Some(Color::new(0.5, 0.0, 0.0))
+        );
+        assert_eq!(result.max_error, 0.5);
+        assert!(float_eq(result.mean_error, 0.5 / 12.0));
+        assert!(float_eq(result.rmse, Float::sqrt(0.25 / 12.0)));
+        assert!(float_eq(result.psnr, 10.0 * (48.0 as Float).log10()));
+        assert!(!result.within(&Tolerance::new(0.1, 1.0, 0.0)));
+        assert!(result.within(&Tolerance::new(0.5, 0.2, 0.0)));
+    }
+
+    #[test]
+    fn ssim_drops_with_structural_changes() {
+        let a = gradient(16, 16);
+        let mut brighter = a.clone();
+        let mut noisy = a.clone();
+        for p in brighter.pixels_mut() {
+            *p = *p + Color::new(0.02, 0.02, 0.02);
+        }
+        for (i, p) in noisy.pixels_mut().iter_mut().enumerate() {
+            if i % 2 == 0 {
+                *p = *p + Color::new(0.2, 0.2, 0.2);
+            }
+        }
+
+        let brighter = compare(&a, &brighter).unwrap().ssim;
+        let noisy = compare(&a, &noisy).unwrap().ssim;
+        assert!(brighter > 0.95);
+        assert!(noisy < brighter);
+    }
+
+    #[test]
+    fn comparing_different_sizes() {
+        let result = compare(&Canvas::new(2, 2), &Canvas::new(3, 2));
+        assert_eq!(
+            result.err(),
+            Some(SizeMismatch {
+                expected: (2, 2),
+                actual: (3, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn heatmap_of_the_differences() {
+        let a = Canvas::new(3, 1);
+        let mut b = Canvas::new(3, 1);
+        b.write_pixel(1, 0, Color::new(0.0, 0.05, 0.0)).unwrap();
+        b.write_pixel(2, 0, Color::new(0.0, 0.0, 0.3)).unwrap();
+
+        let heatmap = compare(&a, &b).unwrap().heatmap(0.1);
+        assert_eq!(heatmap.pixel_at(0, 0), Some(color::BLACK));
+        assert_eq!(heatmap.pixel_at(1, 0), Some(Color::new(1.0, 0.5, 0.0)));
+        assert_eq!(heatmap.pixel_at(2, 0), Some(color::WHITE));
+    }
+}
@@ -0,0 +1,254 @@
+use super::aov::RenderPasses;
+use super::compare::SizeMismatch;
+use crate::{Canvas, Color, Float};
+
+/// Feature buffers steering the denoiser, each must have the size of the
+/// noisy canvas. Pixels are only averaged with neighbours that look alike
+/// in every supplied buffer so edges in the geometry and texture survive.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Guides<'a> {
+    pub normal: Option<&'a Canvas>,
+    pub albedo: Option<&'a Canvas>,
+    pub depth: Option<&'a Canvas>,
+}
+
+impl<'a> Guides<'a> {
+    pub fn from_passes(passes: &'a RenderPasses) -> Guides<'a> {
+        Guides {
+            normal: Some(&passes.normal),
+            albedo: Some(&passes.albedo),
+            depth: Some(&passes.depth),
+        }
+    }
+}
+
+/// Joint bilateral filter. Every sigma is the standard deviation of the
+/// gaussian falloff for its feature, the depth difference is relative to
+/// the depth of the center pixel.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Denoiser {
+    pub radius: usize,
+    pub sigma_spatial: Float,
+    pub sigma_color: Float,
+    pub sigma_normal: Float,
+    pub sigma_albedo: Float,
+    pub sigma_depth: Float,
+}
+
+impl Denoiser {
+    pub fn new() -> Denoiser {
+        Denoiser {
+            radius: 3,
+            sigma_spatial: 2.0,
+            sigma_color: 0.5,
+            sigma_normal: 0.2,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: usize) -> Denoiser {
+        self.radius = radius;
+        self
+    }
+
+    /// Fails when a guide buffer does not have the size of the noisy canvas.
+    pub fn denoise(&self, noisy: &Canvas, guides: &Guides) -> Result<Canvas, SizeMismatch> {
+        let (width, height) = (noisy.width(), noisy.height());
+        let Guides {
+            normal,
+            albedo,
+            depth,
+        } = *guides;
+        for guide in [normal, albedo, depth].iter().flatten() {
+            if guide.width() != width || guide.height() != height {
+                return Err(SizeMismatch {
+                    expected: (width, height),
+                    actual: (guide.width(), guide.height()),
+                });
+            }
+        }
+
+        let falloff = |d2: Float, sigma: Float| (-d2 / (2.0 * sigma * sigma)).exp();
+        let r = self.radius as isize;
+        let mut output = Canvas::new(width, height);
+
+        for (x, y, center) in noisy.enumerate_pixels() {
+            let i = y * width + x;
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut weight_sum = 0.0;
+
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let nx = x as isize + dx;
+                    let ny = y as isize + dy;
+                    if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                        continue;
+                    }
+                    let j = ny as usize * width + nx as usize;
+                    let sample = noisy.pixels()[j];
+
+                    let mut weight = falloff((dx * dx + dy * dy) as Float, self.sigma_spatial)
+                        * falloff(distance2(center, sample), self.sigma_color);
+                    if let Some(n) = normal {
+                        weight *=
+                            falloff(distance2(n.pixels()[i], n.pixels()[j]), self.sigma_normal);
+                    }
+                    if let Some(a) = albedo {
+                        weight *=
+                            falloff(distance2(a.pixels()[i], a.pixels()[j]), self.sigma_albedo);
+                    }
+                    if let Some(d) = depth {
+                        weight *= falloff(depth_distance2(d, i, j), self.sigma_depth);
+                    }
+
+                    sum = sum + sample * weight;
+                    weight_sum += weight;
+                }
+            }
+
+            let color = if weight_sum > 0.0 {
+                sum / weight_sum
+            } else {
+                center
+            };
+            output.pixels_mut()[i] = color;
+        }
+
+        Ok(output)
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderPasses {
+    /// Denoises the beauty pass using the normal, albedo and depth passes.
+    pub fn denoised(&self, denoiser: &Denoiser) -> Result<Canvas, SizeMismatch> {
+        denoiser.denoise(&self.beauty, &Guides::from_passes(self))
+    }
+}
+
+fn distance2(a: Color, b: Color) -> Float {
+    let d = a - b;
+    d.red * d.red + d.green * d.green + d.blue * d.blue
+}
+
+fn depth_distance2(depth: &Canvas, i: usize, j: usize) -> Float {
+    let a = depth.pixels()[i].red;
+    let b = depth.pixels()[j].red;
+    if a.is_infinite() || b.is_infinite() {
+        // background only blends with background
+        return if a == b { 0.0 } else { Float::INFINITY };
+    }
+    let d = (a - b) / a.abs().max(Float::EPSILON);
+    d * d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracing::sampler::pixel_rng;
+    use rand::Rng;
+
+    // left half dark, right half bright, both with uniform noise
+    fn noisy_halves() -> (Canvas, Canvas) {
+        let mut noisy = Canvas::new(16, 16);
+        let mut albedo = Canvas::new(16, 16);
+        for (x, y, _) in noisy.clone().enumerate_pixels() {
+            let base = if x < 8 { 0.2 } else { 0.8 };
+            let n = pixel_rng(1, x, y).gen::<Float>() * 0.2 - 0.1;
+            noisy
+                .write_pixel(x, y, Color::new(base + n, base + n, base + n))
+                .unwrap();
+            albedo
+                .write_pixel(x, y, Color::new(base, base, base))
+                .unwrap();
+        }
+        (noisy, albedo)
+    }
+
+    fn variance(c: &Canvas, xs: std::ops::Range<usize>) -> Float {
+        let values: Vec<Float> = c
+            .enumerate_pixels()
+            .filter(|(x, _, _)| xs.contains(x))
+            .map(|(_, _, p)| p.red)
+            .collect();
+        let mean = values.iter().sum::<Float>() / values.len() as Float;
+        values
+            .iter()
+            .map(|v| (v - mean) * (v - mean))
+            .sum::<Float>()
+            / values.len() as Float
+    }
+
+    #[test]
+    fn denoising_reduces_noise() {
+        let (noisy, albedo) = noisy_halves();
+        let guides = Guides {
+            albedo: Some(&albedo),
+            ..Guides::default()
+        };
+        let output = Denoiser::new().denoise(&noisy, &guides).unwrap();
+
+        assert!(variance(&output, 0..8) < variance(&noisy, 0..8) / 4.0);
+        assert!(variance(&output, 8..16) < variance(&noisy, 8..16) / 4.0);
+    }
+
+    #[test]
+    fn albedo_edges_are_preserved() {
+        let (noisy, albedo) = noisy_halves();
+        let guides = Guides {
+            albedo: Some(&albedo),
+            ..Guides::default()
+        };
+        let output = Denoiser::new().denoise(&noisy, &guides).unwrap();
+
+        for y in 0..16 {
+            assert!((output.pixel_at(7, y).unwrap().red - 0.2).abs() < 0.1);
+            assert!((output.pixel_at(8, y).unwrap().red - 0.8).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn background_does_not_bleed_into_surfaces() {
+        let mut noisy = Canvas::new(3, 1);
+        let mut depth = Canvas::new(3, 1);
+        let far = Color::new(Float::INFINITY, Float::INFINITY, Float::INFINITY);
+        noisy.write_pixel(0, 0, Color::new(1.0, 1.0, 1.0)).unwrap();
+        depth.write_pixel(0, 0, far).unwrap();
+        depth.write_pixel(1, 0, Color::new(4.0, 4.0, 4.0)).unwrap();
+        depth.write_pixel(2, 0, Color::new(4.0, 4.0, 4.0)).unwrap();
+        let guides = Guides {
+            depth: Some(&depth),
+            ..Guides::default()
+        };
+
+        let output = Denoiser::new()
+            .with_radius(1)
+            .denoise(&noisy, &guides)
+            .unwrap();
+        assert_eq!(output.pixel_at(0, 0), Some(Color::new(1.0, 1.0, 1.0)));
+        assert_eq!(output.pixel_at(1, 0), Some(Color::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn a_guide_of_the_wrong_size_is_an_error() {
+        let (noisy, _) = noisy_halves();
+        let albedo = Canvas::new(8, 16);
+        let guides = Guides {
+            albedo: Some(&albedo),
+            ..Guides::default()
+        };
+        assert_eq!(
+            Denoiser::new().denoise(&noisy, &guides),
+            Err(SizeMismatch {
+                expected: (16, 16),
+                actual: (8, 16),
+            })
+        );
+    }
+}
@@ -0,0 +1,855 @@
+//! Rendering tiles on worker processes.
+//!
+//! The coordinator sends an opaque scene description to every worker, then
+//! hands out tiles until the image is complete. Workers answer the scene
+//! with the size of their camera, a worker that would render an image of
+//! another size is dropped. Workers turn the scene bytes
+//! into a `Camera` and `World` with a user supplied loader, so any scene
+//! format can be used. Messages travel over any byte stream, a `TcpStream`
+//! for remote machines or the stdin/stdout pipes of a child process.
+//!
+//! Pixels are seeded by their position, so the assembled canvas is identical
+//! to a local render no matter which worker renders which tile.
+//!
+//! TCP connections take a timeout. A worker that does not answer in time is
+//! dropped and its tile goes to another worker, so the timeout has to be
+//! longer than rendering a single tile takes.
+
+use super::sampler::{Filter, SamplePattern, Sampler};
+use super::tiles::Region;
+use crate::{Camera, Canvas, Color, Float, World};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Byte stream to a worker.
+pub trait Connection: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Connection for T {}
+
+#[derive(Debug)]
+pub enum DistributedError {
+    Io(io::Error),
+    /// A worker reported an error, for example when the scene failed to load.
+    Worker(String),
+    /// A message did not follow the protocol.
+    Protocol(String),
+    /// Every worker failed before the image was complete.
+    NoWorkers,
+}
+
+impl fmt::Display for DistributedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistributedError::Io(e) => write!(f, "worker connection failed: {}", e),
+            DistributedError::Worker(message) => write!(f, "worker error: {}", message),
+            DistributedError::Protocol(message) => write!(f, "protocol error: {}", message),
+            DistributedError::NoWorkers => write!(f, "no worker left to render the image"),
+        }
+    }
+}
+
+impl Error for DistributedError {}
+
+impl From<io::Error> for DistributedError {
+    fn from(e: io::Error) -> Self {
+        DistributedError::Io(e)
+    }
+}
+
+enum Request {
+    Scene {
+        scene: Vec<u8>,
+        sampler: Sampler,
+        rd: usize,
+    },
+    Tile(Region),
+    Shutdown,
+}
+
+enum Response {
+    /// The scene loaded, its camera renders `width` by `height` pixels.
+    Ready(usize, usize),
+    Tile(Region, Canvas),
+    Error(String),
+}
+
+const SCENE: u8 = 1;
+const TILE: u8 = 2;
+const SHUTDOWN: u8 = 3;
+const ERROR: u8 = 4;
+
+fn write_u64<W: Write>(w: &mut W, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Floats always travel as f64 so `f32` and `f64` builds can work together.
+#[allow(clippy::unnecessary_cast)]
+fn write_float<W: Write>(w: &mut W, v: Float) -> io::Result<()> {
+    write_u64(w, (v as f64).to_bits())
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(w, bytes.len() as u64)?;
+    w.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u64(r)?;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn write_region<W: Write>(w: &mut W, r: Region) -> io::Result<()> {
+    for &v in &[r.x, r.y, r.width, r.height] {
+        write_u64(w, v as u64)?;
+    }
+    Ok(())
+}
+
+fn read_region<R: Read>(r: &mut R) -> io::Result<Region> {
+    Ok(Region::new(
+        read_u64(r)? as usize,
+        read_u64(r)? as usize,
+        read_u64(r)? as usize,
+        read_u64(r)? as usize,
+    ))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_request<W: Write>(w: &mut W, request: &Request) -> io::Result<()> {
+    match request {
+        Request::Scene { scene, sampler, rd } => {
+            w.write_all(&[SCENE])?;
+            write_bytes(w, scene)?;
+            let pattern = match sampler.pattern {
+                SamplePattern::Jittered => 0,
+                SamplePattern::Halton => 1,
+            };
+            let filter = match sampler.filter {
+                Filter::Box => 0,
+                Filter::Tent => 1,
+                Filter::Gaussian => 2,
+                Filter::Mitchell => 3,
+            };
+            w.write_all(&[pattern, filter])?;
+            write_u64(w, sampler.min_samples as u64)?;
+            write_u64(w, sampler.max_samples as u64)?;
+            write_float(w, sampler.variance_threshold)?;
+            write_u64(w, *rd as u64)?;
+        }
+        Request::Tile(region) => {
+            w.write_all(&[TILE])?;
+            write_region(w, *region)?;
+        }
+        Request::Shutdown => w.write_all(&[SHUTDOWN])?,
+    }
+    w.flush()
+}
+
+/// Reads the next request, `None` when the coordinator closed the stream.
+fn read_request<R: Read>(r: &mut R) -> io::Result<Option<Request>> {
+    let tag = match read_u8(r) {
+        Ok(tag) => tag,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let request = match tag {
+        SCENE => {
+            let scene = read_bytes(r)?;
+            let pattern = match read_u8(r)? {
+                0 => SamplePattern::Jittered,
+                1 => SamplePattern::Halton,
+                _ => return Err(invalid("unknown sample pattern")),
+            };
+            let filter = match read_u8(r)? {
+                0 => Filter::Box,
+                1 => Filter::Tent,
+                2 => Filter::Gaussian,
+                3 => Filter::Mitchell,
+                _ => return Err(invalid("unknown filter")),
+            };
+            let sampler = Sampler::new(pattern, filter)
+                .with_samples(read_u64(r)? as usize, read_u64(r)? as usize)
+                .with_threshold(f64::from_bits(read_u64(r)?) as Float);
+            let rd = read_u64(r)? as usize;
+            Request::Scene { scene, sampler, rd }
+        }
+        TILE => Request::Tile(read_region(r)?),
+        SHUTDOWN => Request::Shutdown,
+        _ => return Err(invalid("unknown request")),
+    };
+    Ok(Some(request))
+}
+
+fn write_response<W: Write>(w: &mut W, response: &Response) -> io::Result<()> {
+    match response {
+        Response::Ready(width, height) => {
+            w.write_all(&[SCENE])?;
+            write_u64(w, *width as u64)?;
+            write_u64(w, *height as u64)?;
+        }
+        Response::Tile(region, canvas) => {
+            w.write_all(&[TILE])?;
+            write_region(w, *region)?;
+            for c in canvas.pixels() {
+                for v in &[c.red, c.green, c.blue] {
+                    write_float(w, *v)?;
+                }
+            }
+        }
+        Response::Error(message) => {
+            w.write_all(&[ERROR])?;
+            write_bytes(w, message.as_bytes())?;
+        }
+    }
+    w.flush()
+}
+
+/// Reads the answer to a request for the `expected` tile. The size of the
+/// pixel data is checked against the request before anything is allocated.
+fn read_response<R: Read>(r: &mut R, expected: Region) -> Result<Response, DistributedError> {
+    match read_u8(r)? {
+        TILE => {
+            let region = read_region(r)?;
+            if region != expected {
+                return Err(DistributedError::Protocol(
+                    "worker answered with another tile".to_string(),
+                ));
+            }
+            let len = region
+                .width
+                .checked_mul(region.height)
+                .and_then(|pixels| pixels.checked_mul(3 * 8))
+                .ok_or_else(|| DistributedError::Protocol("tile too large".to_string()))?;
+            let mut bytes = vec![0; len];
+            r.read_exact(&mut bytes)?;
+            let mut values = bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as Float);
+            let mut canvas = Canvas::new(region.width, region.height);
+            for pixel in canvas.pixels_mut() {
+                let mut next = || values.next().unwrap();
+                *pixel = Color::new(next(), next(), next());
+            }
+            Ok(Response::Tile(region, canvas))
+        }
+        ERROR => Ok(Response::Error(
+            String::from_utf8_lossy(&read_bytes(r)?).into_owned(),
+        )),
+        _ => Err(DistributedError::Protocol("unknown response".to_string())),
+    }
+}
+
+/// Reads the answer to a scene, the size of the worker's camera.
+fn read_ready<R: Read>(r: &mut R) -> Result<(usize, usize), DistributedError> {
+    match read_u8(r)? {
+        SCENE => Ok((read_u64(r)? as usize, read_u64(r)? as usize)),
+        ERROR => Err(DistributedError::Worker(
+            String::from_utf8_lossy(&read_bytes(r)?).into_owned(),
+        )),
+        _ => Err(DistributedError::Protocol("unknown response".to_string())),
+    }
+}
+
+/// Answers requests from a coordinator until it shuts the worker down or
+/// closes the stream. `load` turns the scene bytes into the camera and world
+/// to render, the camera size answers the scene. A failure, or a shape with
+/// a singular transform in the world, is the answer to the scene and to the
+/// following tile requests.
+pub fn serve<R, W, L>(reader: R, writer: W, load: L) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    L: Fn(&[u8]) -> Result<(Camera, World), String>,
+{
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+    let mut scene = Err("no scene loaded".to_string());
+
+    while let Some(request) = read_request(&mut reader)? {
+        match request {
+            Request::Scene {
+                scene: bytes,
+                sampler,
+                rd,
+            } => {
+                scene = load(&bytes).and_then(|(camera, world)| {
+                    world.validate().map_err(|e| e.to_string())?;
+                    Ok((camera, world, sampler, rd))
+                });
+                let response = match &scene {
+                    Ok((camera, ..)) => Response::Ready(camera.hsize(), camera.vsize()),
+                    Err(message) => Response::Error(message.clone()),
+                };
+                write_response(&mut writer, &response)?;
+            }
+            Request::Tile(region) => {
+                let response = match &scene {
+                    Ok((camera, world, sampler, rd)) => {
+                        match camera.render_region(world, *sampler, *rd, region) {
+                            Ok(canvas)
+                                if canvas.width() == region.width
+                                    && canvas.height() == region.height =>
+                            {
+                                Response::Tile(region, canvas)
+                            }
+                            Ok(_) => {
+                                Response::Error("tile outside of the camera image".to_string())
+                            }
+                            Err(e) => Response::Error(e.to_string()),
+                        }
+                    }
+                    Err(message) => Response::Error(message.clone()),
+                };
+                write_response(&mut writer, &response)?;
+            }
+            Request::Shutdown => break,
+        }
+    }
+    Ok(())
+}
+
+/// Serves a coordinator over this process' stdin and stdout.
+pub fn serve_stdio<L>(load: L) -> io::Result<()>
+where
+    L: Fn(&[u8]) -> Result<(Camera, World), String>,
+{
+    serve(io::stdin().lock(), io::stdout().lock(), load)
+}
+
+/// Serves every coordinator connecting to the listener, one at a time. A
+/// coordinator that sends nothing for `timeout` is disconnected. A failed
+/// connection is passed to `on_error` and the next one is accepted, only
+/// errors of the listener itself end the loop.
+pub fn serve_tcp<L, E>(
+    listener: &TcpListener,
+    timeout: Option<Duration>,
+    load: L,
+    mut on_error: E,
+) -> io::Result<()>
+where
+    L: Fn(&[u8]) -> Result<(Camera, World), String>,
+    E: FnMut(io::Error),
+{
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let served = stream
+            .set_read_timeout(timeout)
+            .and_then(|_| stream.set_write_timeout(timeout))
+            .and_then(|_| serve(stream.try_clone()?, &stream, &load));
+        if let Err(e) = served {
+            on_error(e);
+        }
+    }
+    Ok(())
+}
+
+/// Worker running as a child process, requests go to its stdin and
+/// responses come from its stdout.
+pub struct ChildConnection {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: ChildStdout,
+}
+
+impl ChildConnection {
+    pub fn spawn(command: &mut Command) -> io::Result<ChildConnection> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().unwrap();
+        Ok(ChildConnection {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Read for ChildConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Write for ChildConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ChildConnection {
+    fn drop(&mut self) {
+        // closing stdin ends the worker's request loop
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+/// Connects to workers listening with `serve_tcp`. A worker that does not
+/// answer a request within `timeout` counts as failed.
+pub fn connect_tcp(
+    addresses: &[&str],
+    timeout: Option<Duration>,
+) -> io::Result<Vec<Box<dyn Connection>>> {
+    let mut workers: Vec<Box<dyn Connection>> = Vec::new();
+    for address in addresses {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        workers.push(Box::new(stream));
+    }
+    Ok(workers)
+}
+
+/// Tiles waiting to be rendered. A tile handed to a worker that fails goes
+/// back into the queue for the remaining workers.
+struct TileQueue {
+    state: Mutex<(Vec<Region>, usize)>,
+    changed: Condvar,
+}
+
+impl TileQueue {
+    fn next(&self) -> Option<Region> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(region) = state.0.pop() {
+                state.1 += 1;
+                return Some(region);
+            }
+            if state.1 == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn finish(&self, retry: Option<Region>) {
+        let mut state = self.state.lock().unwrap();
+        state.0.extend(retry);
+        state.1 -= 1;
+        self.changed.notify_all();
+    }
+}
+
+/// Splits an image into tiles and distributes them over workers.
+pub struct Coordinator {
+    width: usize,
+    height: usize,
+    tile_width: usize,
+    tile_height: usize,
+    sampler: Sampler,
+    rd: usize,
+}
+
+impl Coordinator {
+    /// `width` and `height` must match the camera of the scene, workers
+    /// whose camera has another size are dropped.
+    pub fn new(width: usize, height: usize) -> Coordinator {
+        Coordinator {
+            width,
+            height,
+            tile_width: 32,
+            tile_height: 32,
+            sampler: Sampler::center(),
+            rd: 5,
+        }
+    }
+
+    pub fn tile_size(mut self, tile_width: usize, tile_height: usize) -> Coordinator {
+        self.tile_width = tile_width;
+        self.tile_height = tile_height;
+        self
+    }
+
+    pub fn sampler<S: Into<Sampler>>(mut self, sampler: S) -> Coordinator {
+        self.sampler = sampler.into();
+        self
+    }
+
+    pub fn depth(mut self, rd: usize) -> Coordinator {
+        self.rd = rd;
+        self
+    }
+
+    /// Renders the scene on the workers. Workers that fail are dropped and
+    /// their tiles are rendered by the others.
+    pub fn render(
+        &self,
+        scene: &[u8],
+        workers: Vec<Box<dyn Connection>>,
+    ) -> Result<Canvas, DistributedError> {
+        let mut tiles = Region::tiles(self.width, self.height, self.tile_width, self.tile_height);
+        tiles.reverse();
+        let queue = TileQueue {
+            state: Mutex::new((tiles, 0)),
+            changed: Condvar::new(),
+        };
+        let canvas = Mutex::new(Canvas::new(self.width, self.height));
+        let scene = Request::Scene {
+            scene: scene.to_vec(),
+            sampler: self.sampler,
+            rd: self.rd,
+        };
+
+        let errors: Vec<DistributedError> = thread::scope(|s| {
+            let handles: Vec<_> = workers
+                .into_iter()
+                .map(|worker| s.spawn(|| self.drive(worker, &scene, &queue, &canvas)))
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|h| h.join().unwrap().err())
+                .collect()
+        });
+
+        if queue.state.lock().unwrap().0.is_empty() {
+            Ok(canvas.into_inner().unwrap())
+        } else {
+            Err(errors
+                .into_iter()
+                .last()
+                .unwrap_or(DistributedError::NoWorkers))
+        }
+    }
+
+    fn drive(
+        &self,
+        mut worker: Box<dyn Connection>,
+        scene: &Request,
+        queue: &TileQueue,
+        canvas: &Mutex<Canvas>,
+    ) -> Result<(), DistributedError> {
+        write_request(&mut worker, scene)?;
+        let (width, height) = read_ready(&mut worker)?;
+        if (width, height) != (self.width, self.height) {
+            return Err(DistributedError::Protocol(format!(
+                "worker camera is {}x{}, the image is {}x{}",
+                width, height, self.width, self.height
+            )));
+        }
+
+        while let Some(region) = queue.next() {
+            let result = render_tile(&mut worker, region);
+            match result {
+                Ok(tile) => {
+                    canvas
+                        .lock()
+                        .unwrap()
+                        .paste(&tile, region.x, region.y)
+                        .unwrap();
+                    queue.finish(None);
+                }
+                Err(e) => {
+                    queue.finish(Some(region));
+                    return Err(e);
+                }
+            }
+        }
+
+        write_request(&mut worker, &Request::Shutdown)?;
+        Ok(())
+    }
+}
+
+fn render_tile(
+    worker: &mut Box<dyn Connection>,
+    region: Region,
+) -> Result<Canvas, DistributedError> {
+    write_request(worker, &Request::Tile(region))?;
+    match read_response(worker, region)? {
+        Response::Tile(_, tile) => Ok(tile),
+        Response::Error(message) => Err(DistributedError::Worker(message)),
+        Response::Ready(..) => Err(DistributedError::Protocol(
+            "unexpected response".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::ray_tracing::camera::AntiAlias;
+    use crate::{Point, Transform, Vector};
+
+    fn load(scene: &[u8]) -> Result<(Camera, World), String> {
+        if scene != b"default" && scene != b"singular" {
+            return Err(format!(
+                "unknown scene {:?}",
+                String::from_utf8_lossy(scene)
+            ));
+        }
+        let mut world = World::default();
+        if scene == b"singular" {
+            let id = world.get_shape_at(0).id();
+            world
+                .get_shape_mut(id)
+                .unwrap()
+                .set_transform(Transform::new().scaling(0.0, 1.0, 1.0).build());
+        }
+        let camera = Camera::builder(13, 9)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build();
+        Ok((camera, world))
+    }
+
+    fn tcp_worker() -> Box<dyn Connection> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream.try_clone().unwrap(), stream, load).unwrap();
+        });
+        Box::new(TcpStream::connect(address).unwrap())
+    }
+
+    /// Connects to a worker that hangs up as soon as it gets a tile.
+    fn failing_worker() -> Box<dyn Connection> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            read_request(&mut reader).unwrap();
+            write_response(&mut stream, &Response::Ready(13, 9)).unwrap();
+            read_request(&mut reader).unwrap();
+            stream.shutdown(std::net::Shutdown::Both).unwrap();
+        });
+        Box::new(TcpStream::connect(address).unwrap())
+    }
+
+    /// Connects to a worker that takes requests but never answers them.
+    fn silent_worker(timeout: Duration) -> Box<dyn Connection> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            io::copy(&mut stream, &mut io::sink()).ok();
+        });
+        connect_tcp(&[&address], Some(timeout)).unwrap().remove(0)
+    }
+
+    #[test]
+    fn workers_assemble_the_full_image() {
+        let (camera, world) = load(b"default").unwrap();
+        let expected = camera.render(&world, AntiAlias::Random(4), 5);
+
+        let canvas = Coordinator::new(13, 9)
+            .tile_size(4, 4)
+            .sampler(AntiAlias::Random(4))
+            .render(b"default", vec![tcp_worker(), tcp_worker(), tcp_worker()])
+            .unwrap();
+
+        assert_eq!(canvas, expected);
+    }
+
+    #[test]
+    fn tiles_of_failed_workers_are_rendered_by_others() {
+        let (camera, world) = load(b"default").unwrap();
+        let expected = camera.render(&world, AntiAlias::None, 5);
+
+        let canvas = Coordinator::new(13, 9)
+            .tile_size(4, 4)
+            .sampler(AntiAlias::None)
+            .render(b"default", vec![failing_worker(), tcp_worker()])
+            .unwrap();
+
+        assert_eq!(canvas, expected);
+    }
+
+    #[test]
+    fn tiles_of_workers_that_time_out_are_rendered_by_others() {
+        let (camera, world) = load(b"default").unwrap();
+        let expected = camera.render(&world, AntiAlias::None, 5);
+
+        let canvas = Coordinator::new(13, 9)
+            .tile_size(4, 4)
+            .sampler(AntiAlias::None)
+            .render(
+                b"default",
+                vec![silent_worker(Duration::from_millis(100)), tcp_worker()],
+            )
+            .unwrap();
+
+        assert_eq!(canvas, expected);
+    }
+
+    #[test]
+    fn responses_for_other_tiles_are_rejected_before_allocating() {
+        let mut buffer = vec![TILE];
+        write_region(&mut buffer, Region::new(4, 0, 4, 4)).unwrap();
+        let result = read_response(&mut buffer.as_slice(), Region::new(0, 0, 4, 4));
+        assert!(matches!(result, Err(DistributedError::Protocol(_))));
+
+        let mut buffer = vec![TILE];
+        let huge = Region::new(0, 0, usize::MAX / 4, 3);
+        write_region(&mut buffer, huge).unwrap();
+        let result = read_response(&mut buffer.as_slice(), huge);
+        assert!(matches!(result, Err(DistributedError::Protocol(_))));
+    }
+
+    #[test]
+    fn scene_errors_answer_the_tile_requests() {
+        let mut requests = Vec::new();
+        for request in &[
+            Request::Scene {
+                scene: b"missing".to_vec(),
+                sampler: Sampler::center(),
+                rd: 5,
+            },
+            Request::Tile(Region::new(0, 0, 2, 2)),
+            Request::Tile(Region::new(2, 0, 2, 2)),
+        ] {
+            write_request(&mut requests, request).unwrap();
+        }
+        let mut responses = Vec::new();
+        serve(requests.as_slice(), &mut responses, load).unwrap();
+
+        let mut reader = responses.as_slice();
+        match read_ready(&mut reader) {
+            Err(DistributedError::Worker(message)) => assert!(message.contains("missing")),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        for &region in &[Region::new(0, 0, 2, 2), Region::new(2, 0, 2, 2)] {
+            match read_response(&mut reader, region).unwrap() {
+                Response::Error(message) => assert!(message.contains("missing")),
+                _ => panic!("expected an error"),
+            }
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn the_tcp_server_survives_a_broken_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (errors, failures) = std::sync::mpsc::channel();
+        thread::spawn(move || serve_tcp(&listener, None, load, |e| errors.send(e).unwrap()));
+
+        let mut broken = TcpStream::connect(&address).unwrap();
+        broken.write_all(&[0xff]).unwrap();
+        drop(broken);
+
+        let workers = connect_tcp(&[&address], Some(Duration::from_secs(10))).unwrap();
+        let canvas = Coordinator::new(13, 9).render(b"default", workers).unwrap();
+        assert_eq!(canvas.width(), 13);
+        let failure = failures.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(failure.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn scene_errors_are_reported() {
+        let result = Coordinator::new(13, 9).render(b"missing", vec![tcp_worker()]);
+        match result {
+            Err(DistributedError::Worker(message)) => assert!(message.contains("missing")),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn singular_transforms_are_reported_by_the_worker() {
+        let result = Coordinator::new(13, 9).render(b"singular", vec![tcp_worker()]);
+        match result {
+            Err(DistributedError::Worker(message)) => {
+                assert!(message.contains("non-invertible"))
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn workers_with_another_camera_size_are_rejected() {
+        let result = Coordinator::new(12, 9).render(b"default", vec![tcp_worker()]);
+        match result {
+            Err(DistributedError::Protocol(message)) => {
+                assert!(message.contains("13x9") && message.contains("12x9"))
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn rendering_without_workers() {
+        let result = Coordinator::new(2, 2).render(b"default", Vec::new());
+        assert!(matches!(result, Err(DistributedError::NoWorkers)));
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let sampler = Sampler::adaptive(4, 16, 0.005).with_filter(Filter::Mitchell);
+        let mut buffer = Vec::new();
+        write_request(
+            &mut buffer,
+            &Request::Scene {
+                scene: b"scene".to_vec(),
+                sampler,
+                rd: 3,
+            },
+        )
+        .unwrap();
+        write_request(&mut buffer, &Request::Tile(Region::new(1, 2, 3, 4))).unwrap();
+
+        let mut reader = buffer.as_slice();
+        match read_request(&mut reader).unwrap() {
+            Some(Request::Scene {
+                scene,
+                sampler: s,
+                rd,
+            }) => {
+                assert_eq!(scene, b"scene");
+                assert_eq!(s, sampler);
+                assert_eq!(rd, 3);
+            }
+            _ => panic!("expected a scene"),
+        }
+        assert!(matches!(
+            read_request(&mut reader).unwrap(),
+            Some(Request::Tile(r)) if r == Region::new(1, 2, 3, 4)
+        ));
+        assert!(read_request(&mut reader).unwrap().is_none());
+    }
+}
@@ -0,0 +1,185 @@
+//! Regression testing against stored reference images.
+//!
+//! References are 8 bit PNG files. Set `RUSTIC_RAY_UPDATE_GOLDEN=1` to
+//! make the `assert_*` helpers write the current output as the new
+//! reference instead of comparing. The panicking `assert_*` helpers are only
+//! built for tests and with the `testing` feature.
+
+use super::compare::{Comparison, Tolerance};
+#[cfg(any(test, feature = "testing"))]
+use super::sampler::Sampler;
+use crate::Canvas;
+#[cfg(any(test, feature = "testing"))]
+use crate::{Camera, World};
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub const UPDATE_VARIABLE: &str = "RUSTIC_RAY_UPDATE_GOLDEN";
+
+/// Compares the canvas with the reference image at `path`. Both are compared
+/// after quantizing to 8 bits so an unchanged render matches exactly.
+///
+/// With `update` the canvas replaces the reference. A missing reference is
+/// written and reported as a failure so it is not silently accepted. When
+/// the comparison fails the quantized render and a heatmap of the
+/// differences are saved next to the reference as `<name>.actual.png` and
+/// `<name>.diff.png`.
+pub fn check<P: AsRef<Path>>(
+    canvas: &Canvas,
+    path: P,
+    tolerance: &Tolerance,
+    update: bool,
+) -> Result<Comparison, String> {
+    let path = path.as_ref();
+    let actual = Canvas::from_rgb_image(&canvas.to_rgb_image());
+
+    if update || !path.exists() {
+        let existed = path.exists();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        actual.save(path_str(path)?).map_err(|e| e.to_string())?;
+        if !existed && !update {
+            return Err(format!(
+                "no reference image at {}, wrote the current render",
+                path.display()
+            ));
+        }
+    }
+
+    let expected = Canvas::load(path_str(path)?).map_err(|e| e.to_string())?;
+    let comparison = expected.compare(&actual).map_err(|e| e.to_string())?;
+    if comparison.within(tolerance) {
+        return Ok(comparison);
+    }
+
+    let actual_path = sibling(path, "actual");
+    let diff_path = sibling(path, "diff");
+    actual
+        .save(path_str(&actual_path)?)
+        .map_err(|e| e.to_string())?;
+    comparison
+        .heatmap(tolerance.max_error.max(1.0 / 255.0) * 4.0)
+        .save(path_str(&diff_path)?)
+        .map_err(|e| e.to_string())?;
+    Err(format!(
+        "{} differs from the reference: {}, see {} and {}",
+        path.display(),
+        comparison,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+/// Panics with the comparison report unless the canvas matches the
+/// reference image. The reference is updated when `RUSTIC_RAY_UPDATE_GOLDEN`
+/// is set to `1`.
+#[cfg(any(test, feature = "testing"))]
+pub fn assert_matches<P: AsRef<Path>>(canvas: &Canvas, path: P, tolerance: &Tolerance) {
+    if let Err(message) = check(canvas, path, tolerance, update_requested()) {
+        panic!("{}", message);
+    }
+}
+
+/// Renders the world with the camera, rays recurse at most `rd` times, and
+/// compares it with the reference.
+#[cfg(any(test, feature = "testing"))]
+pub fn assert_render_matches<S: Into<Sampler>, P: AsRef<Path>>(
+    camera: &Camera,
+    world: &World,
+    sampler: S,
+    rd: usize,
+    path: P,
+    tolerance: &Tolerance,
+) {
+    assert_matches(&camera.render(world, sampler, rd), path, tolerance);
+}
+
+/// Whether `RUSTIC_RAY_UPDATE_GOLDEN` is set to `1`.
+pub fn update_requested() -> bool {
+    env::var(UPDATE_VARIABLE).is_ok_and(|v| v == "1")
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("golden");
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+fn path_str(path: &Path) -> Result<&str, String> {
+    path.to_str()
+        .ok_or_else(|| format!("{} is not valid unicode", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::ray_tracing::camera::AntiAlias;
+    use crate::{Point, Vector};
+
+    fn golden_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+    }
+
+    #[test]
+    fn default_world_matches_the_reference() {
+        let c = Camera::builder(32, 32)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 3.0)
+            .build();
+        assert_render_matches(
+            &c,
+            &World::default(),
+            AntiAlias::None,
+            5,
+            golden_dir().join("default_world.png"),
+            &Tolerance::new(2.0 / 255.0, 0.5 / 255.0, 0.99),
+        );
+    }
+
+    #[test]
+    fn a_changed_render_fails_and_writes_a_heatmap() {
+        let dir = crate::temp_path("golden");
+        let reference = dir.join("square.png");
+        let mut canvas = Canvas::new(4, 4);
+        std::fs::create_dir_all(&dir).unwrap();
+        canvas.save(path_str(&reference).unwrap()).unwrap();
+
+        for p in canvas.pixels_mut() {
+            p.red = 0.5;
+        }
+        let result = check(&canvas, &reference, &Tolerance::exact(), false);
+        let heatmap = sibling(&reference, "diff");
+        let heatmap_written = heatmap.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.unwrap_err().contains("differs from the reference"));
+        assert!(heatmap_written);
+    }
+
+    #[test]
+    fn updating_replaces_the_reference() {
+        let dir = crate::temp_path("golden_update");
+        let reference = dir.join("square.png");
+        let mut canvas = Canvas::new(4, 4);
+        std::fs::create_dir_all(&dir).unwrap();
+        canvas.save(path_str(&reference).unwrap()).unwrap();
+
+        for p in canvas.pixels_mut() {
+            p.green = 1.0;
+        }
+        let updated = check(&canvas, &reference, &Tolerance::exact(), true);
+        let compared = check(&canvas, &reference, &Tolerance::exact(), false);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(updated.is_ok());
+        assert!(compared.is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{Canvas, Color, Float};
+use std::io::{self, Write};
+
+/// Writes the canvas as a Radiance RGBE image (`.hdr`). Channels are stored
+/// linearly without clamping bright values, negative values become zero.
+pub fn write_radiance<W: Write>(canvas: &Canvas, w: &mut W) -> io::Result<()> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        canvas.height(),
+        canvas.width()
+    )?;
+
+    let mut line = Vec::with_capacity(canvas.width() * 4);
+    for row in canvas.rows() {
+        line.clear();
+        for &p in row {
+            line.extend_from_slice(&to_rgbe(p));
+        }
+        w.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Largest value RGBE can hold, a mantissa of 255 with an exponent of 127.
+/// Anything brighter, infinities included, is clamped to it.
+fn rgbe_max() -> Float {
+    255.0 / 256.0 * Float::powi(2.0, 127)
+}
+
+fn to_rgbe(color: Color) -> [u8; 4] {
+    // NaN becomes 0 through `max`
+    let red = color.red.max(0.0).min(rgbe_max());
+    let green = color.green.max(0.0).min(rgbe_max());
+    let blue = color.blue.max(0.0).min(rgbe_max());
+    let v = red.max(green).max(blue);
+
+    if v < 1e-32 {
+        [0, 0, 0, 0]
+    } else {
+        // v = m * 2^e with m in [0.5, 1)
+        let e = v.log2().floor() as i32 + 1;
+        let scale = 256.0 / Float::powi(2.0, e);
+        [
+            (red * scale) as u8,
+            (green * scale) as u8,
+            (blue * scale) as u8,
+            (e + 128) as u8,
+        ]
+    }
+}
+
+/// Writes the canvas as a single part, uncompressed scanline OpenEXR image
+/// with 32 bit float R, G and B channels.
+pub fn write_open_exr<W: Write>(canvas: &Canvas, w: &mut W) -> io::Result<()> {
+    let width = canvas.width() as i32;
+    let height = canvas.height() as i32;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&20_000_630_i32.to_le_bytes());
+    header.extend_from_slice(&2_i32.to_le_bytes());
+
+    // channels are stored in alphabetical order
+    let mut channels = Vec::new();
+    for name in &["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&2_i32.to_le_bytes()); // FLOAT
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+    }
+    channels.push(0);
+    exr_attribute(&mut header, "channels", "chlist", &channels);
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width - 1, height - 1].iter() {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1_f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1_f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // one scanline per block: y, byte count, then B, G and R for every pixel
+    let line_size = canvas.width() * 3 * 4;
+    let block_size = 8 + line_size;
+    let first_block = header.len() + canvas.height() * 8;
+
+    w.write_all(&header)?;
+    for y in 0..canvas.height() {
+        let offset = (first_block + y * block_size) as u64;
+        w.write_all(&offset.to_le_bytes())?;
+    }
+
+    let mut block = Vec::with_capacity(block_size);
+    for (y, row) in canvas.rows().enumerate() {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in 0..3 {
+            for c in row {
+                let v = match channel {
+                    0 => c.blue,
+                    1 => c.green,
+                    _ => c.red,
+                };
+                #[allow(clippy::unnecessary_cast)]
+                block.extend_from_slice(&(v as f32).to_le_bytes());
+            }
+        }
+        w.write_all(&block)?;
+    }
+
+    Ok(())
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::hdr::HdrDecoder;
+    use std::convert::TryInto;
+
+    #[test]
+    fn rgbe_keeps_values_above_one() {
+        let rgbe = to_rgbe(Color::new(4.0, 2.0, 1.0));
+        assert_eq!(rgbe, [128, 64, 32, 131]);
+        assert_eq!(to_rgbe(Color::new(-1.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rgbe_clamps_values_it_cannot_hold() {
+        let largest = [255, 255, 255, 255];
+        assert_eq!(
+            to_rgbe(Color::new(
+                Float::INFINITY,
+                Float::INFINITY,
+                Float::INFINITY
+            )),
+            largest
+        );
+        let huge = 1e40_f64 as Float;
+        assert_eq!(to_rgbe(Color::new(huge, huge, huge)), largest);
+        assert_eq!(
+            to_rgbe(Color::new(Float::INFINITY, 0.0, 0.0)),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(to_rgbe(Color::new(Float::NAN, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn radiance_image_round_trips_through_a_decoder() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(10.0, 0.5, 0.25)).unwrap();
+        c.write_pixel(2, 1, Color::new(0.0, 3.0, 0.0)).unwrap();
+
+        let mut buffer = Vec::new();
+        write_radiance(&c, &mut buffer).unwrap();
+        let decoder = HdrDecoder::new(buffer.as_slice()).unwrap();
+        assert_eq!(decoder.metadata().width, 3);
+        assert_eq!(decoder.metadata().height, 2);
+        let pixels = decoder.read_image_hdr().unwrap();
+
+        assert_eq!(pixels[0].0, [10.0, 0.5, 0.25]);
+        assert_eq!(pixels[5].0, [0.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn open_exr_header_and_scanlines() {
+        let mut c = Canvas::new(2, 2);
+        c.write_pixel(1, 1, Color::new(12.5, -0.5, 0.25)).unwrap();
+
+        let mut buffer = Vec::new();
+        write_open_exr(&c, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[0..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // the offset table entry of the last scanline points at its block
+        let block_size = 8 + 2 * 3 * 4;
+        let last_offset = buffer.len() - block_size;
+        let table = last_offset - block_size - 16;
+        let offset = u64::from_le_bytes(buffer[table + 8..table + 16].try_into().unwrap());
+        assert_eq!(offset as usize, last_offset);
+
+        let block = &buffer[last_offset..];
+        assert_eq!(i32::from_le_bytes(block[0..4].try_into().unwrap()), 1);
+        let float_at =
+            |i: usize| f32::from_le_bytes(block[8 + i * 4..12 + i * 4].try_into().unwrap());
+        // B0 B1 G0 G1 R0 R1
+        assert_eq!(float_at(1), 0.25);
+        assert_eq!(float_at(3), -0.5);
+        assert_eq!(float_at(5), 12.5);
+    }
+}
@@ -1,15 +1,15 @@
 use crate::shapes::Shape;
-use crate::{float_cmp, Point, Ray, ShapeContainer, Vector, EPSILON};
+use crate::{epsilon_at, float_cmp, Float, Point, Ray, ShapeContainer, Vector};
 use std::cmp::Ordering;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Intersection<'a> {
-    pub t: f64,
+    pub t: Float,
     pub object: &'a dyn Shape,
 }
 
 pub struct Computations<'a> {
-    pub t: f64,
+    pub t: Float,
     pub object: &'a dyn Shape,
     pub point: Point,
     pub eyev: Vector,
@@ -18,22 +18,18 @@ pub struct Computations<'a> {
     pub over_point: Point,
     pub under_point: Point,
     pub reflectv: Vector,
-    pub n1: f64,
-    pub n2: f64,
+    pub n1: Float,
+    pub n2: Float,
 }
 
 impl Intersection<'_> {
     pub fn hit<'a>(xs: &'a [Intersection]) -> Option<Intersection<'a>> {
-        if let Some(hit) = xs.iter().filter(|x| x.t >= 0.0).min() {
-            Some(*hit)
-        } else {
-            None
-        }
+        xs.iter().filter(|x| x.t >= 0.0).min().copied()
     }
 }
 
 impl<'a> Intersection<'a> {
-    pub fn new(t: f64, object: &dyn Shape) -> Intersection {
+    pub fn new(t: Float, object: &dyn Shape) -> Intersection<'_> {
         Intersection { t, object }
     }
 
@@ -44,14 +40,15 @@ impl<'a> Intersection<'a> {
         sc: Option<&ShapeContainer>,
     ) -> Computations<'h> {
         let point = r.position(hit.t);
-        let mut normalv = hit.object.normal_at(point, sc);
+        let mut normalv = hit.object.normal_at(point, sc).to_vector();
         let mut inside = false;
         if normalv.dot(-r.direction) < 0.0 {
             inside = true;
             normalv = -normalv;
         }
-        let over_point = point + normalv * EPSILON;
-        let under_point = point - normalv * EPSILON;
+        let offset = epsilon_at(point.x.abs().max(point.y.abs()).max(point.z.abs()));
+        let over_point = point + normalv * offset;
+        let under_point = point - normalv * offset;
 
         let reflectv = r.direction.reflect(normalv);
 
@@ -68,7 +65,7 @@ impl<'a> Intersection<'a> {
             }
 
             if container.contains(&i.object) {
-                container = container.into_iter().filter(|o| *o != i.object).collect();
+                container.retain(|o| *o != i.object);
             } else {
                 container.push(i.object);
             }
@@ -108,7 +105,7 @@ impl PartialEq for Intersection<'_> {
 
 impl PartialOrd for Intersection<'_> {
     fn partial_cmp(&self, other: &Intersection) -> Option<Ordering> {
-        Some(float_cmp(self.t, other.t))
+        Some(self.cmp(other))
     }
 }
 
@@ -121,7 +118,7 @@ impl Ord for Intersection<'_> {
 }
 
 impl Computations<'_> {
-    pub fn schlick(&self) -> f64 {
+    pub fn schlick(&self) -> Float {
         // find the cosine of the angle between the eye and normal vector
         let mut cos = self.eyev.dot(self.normalv);
 
@@ -163,7 +160,7 @@ mod tests {
     #[test]
     fn aggregating_intersections() {
         let s = Sphere::new();
-        let xs = vec![Intersection::new(1.0, &s), Intersection::new(2.0, &s)];
+        let xs = [Intersection::new(1.0, &s), Intersection::new(2.0, &s)];
         assert_eq!(2, xs.len());
         assert_eq!(1.0, xs[0].t);
         assert_eq!(2.0, xs[1].t);
@@ -227,7 +224,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Sphere::new();
         let i = Intersection::new(4.0, &shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         assert_eq!(comps.t, i.t);
         assert_eq!(comps.point, Point::new(0.0, 0.0, -1.0));
         assert_eq!(comps.eyev, Vector::new(0.0, 0.0, -1.0));
@@ -241,8 +238,8 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Sphere::new();
         let i = Intersection::new(4.0, &shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
-        assert_eq!(comps.inside, false);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
+        assert!(!comps.inside);
     }
 
     // Chapter 7 Making a Scene
@@ -252,10 +249,10 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let shape = Sphere::new();
         let i = Intersection::new(1.0, &shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         assert_eq!(comps.point, Point::new(0.0, 0.0, 1.0));
         assert_eq!(comps.eyev, Vector::new(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, true);
+        assert!(comps.inside);
         assert_eq!(comps.normalv, Vector::new(0.0, 0.0, -1.0));
     }
 
@@ -267,7 +264,7 @@ mod tests {
         let mut shape = Sphere::new();
         shape.transform = Transform::new().translation(0.0, 0.0, 1.0).build();
         let i = Intersection::new(5.0, &shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         assert!(comps.over_point.z < -EPSILON / 2.0);
         assert!(comps.point.z > comps.over_point.z);
     }
@@ -279,13 +276,13 @@ mod tests {
         let shape = Plane::new();
         let r = Ray::new(
             Point::new(0.0, 1.0, -1.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
-        let i = Intersection::new(2_f64.sqrt(), &shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let i = Intersection::new(Float::sqrt(2.0), &shape);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         assert_eq!(
             comps.reflectv,
-            Vector::new(0.0, 2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0)
+            Vector::new(0.0, Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0)
         );
     }
 
@@ -314,7 +311,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -4.0), Vector::new(0.0, 0.0, 1.0));
         let xs = vec![ia1, ib1, ic1, ib2, ic2, ia2];
 
-        let expected = vec![
+        let expected = [
             (1.0, 1.5),
             (1.5, 2.0),
             (2.0, 2.5),
@@ -338,7 +335,7 @@ mod tests {
         let mut shape = Sphere::glass_sphere();
         shape.transform = Transform::new().translation(0.0, 0.0, 1.0).build();
         let hit = Intersection::new(5.0, &shape);
-        let xs = vec![hit.clone()];
+        let xs = vec![hit];
         let comps = Intersection::prepare_computations(&hit, r, &xs, None);
         assert!(comps.under_point.z > EPSILON / 2.0);
         assert!(comps.point.z < comps.under_point.z);
@@ -350,12 +347,12 @@ mod tests {
     fn the_schlick_approximation_under_total_internal_reflection() {
         let shape = Sphere::glass_sphere();
         let r = Ray::new(
-            Point::new(0.0, 0.0, 2_f64.sqrt()),
+            Point::new(0.0, 0.0, Float::sqrt(2.0)),
             Vector::new(0.0, 1.0, 0.0),
         );
-        let i1 = Intersection::new(-2_f64.sqrt() / 2.0, &shape);
-        let i2 = Intersection::new(2_f64.sqrt() / 2.0, &shape);
-        let xs = vec![i1.clone(), i2.clone()];
+        let i1 = Intersection::new(-Float::sqrt(2.0) / 2.0, &shape);
+        let i2 = Intersection::new(Float::sqrt(2.0) / 2.0, &shape);
+        let xs = vec![i1, i2];
         let comps = Intersection::prepare_computations(&i2, r, &xs, None);
         let reflectance = comps.schlick();
         assert_eq!(reflectance, 1.0);
@@ -369,7 +366,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
         let i1 = Intersection::new(-1.0, &shape);
         let i2 = Intersection::new(1.0, &shape);
-        let xs = vec![i1.clone(), i2.clone()];
+        let xs = vec![i1, i2];
         let comps = Intersection::prepare_computations(&i2, r, &xs, None);
         let reflectance = comps.schlick();
         assert!(float_eq(reflectance, 0.04));
@@ -382,7 +379,7 @@ mod tests {
         let shape = Sphere::glass_sphere();
         let r = Ray::new(Point::new(0.0, 0.99, -2.0), Vector::new(0.0, 0.0, 1.0));
         let i1 = Intersection::new(1.8589, &shape);
-        let xs = vec![i1.clone()];
+        let xs = vec![i1];
         let comps = Intersection::prepare_computations(&i1, r, &xs, None);
         let reflectance = comps.schlick();
         assert!(float_eq(reflectance, 0.48873));
@@ -1,6 +1,7 @@
 use crate::{Color, Point};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointLight {
     pub intensity: Color,
     pub position: Point,
@@ -1,15 +1,19 @@
-use crate::{patterns::Pattern, shapes::Shape, Color, Point, PointLight, Vector};
+use crate::ray_tracing::stable_hash::StableHasher;
+use crate::{patterns::Pattern, shapes::Shape, Color, Float, Point, PointLight, Vector};
+use std::fmt::Write;
+use std::hash::Hasher;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Material {
     pub color: Color,
-    pub ambient: f64,
-    pub diffuse: f64,
-    pub specular: f64,
-    pub shininess: f64,
-    pub reflective: f64,
-    pub transparency: f64,
-    pub refractive_index: f64,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    pub reflective: Float,
+    pub transparency: Float,
+    pub refractive_index: Float,
     pub pattern: Option<Box<dyn Pattern>>,
 }
 
@@ -28,6 +32,41 @@ impl Material {
         }
     }
 
+    /// Surface color at a point, taken from the pattern when there is one.
+    pub fn color_at(&self, object: &dyn Shape, point: Point) -> Color {
+        match self.pattern.as_ref() {
+            Some(pattern) => pattern.pattern_at_object(object, point),
+            None => self.color,
+        }
+    }
+
+    /// Identifier derived from every parameter of the material, identical
+    /// materials share an id in every run.
+    pub fn id(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        let Color { red, green, blue } = self.color;
+        for value in [
+            red,
+            green,
+            blue,
+            self.ambient,
+            self.diffuse,
+            self.specular,
+            self.shininess,
+            self.reflective,
+            self.transparency,
+            self.refractive_index,
+        ] {
+            hasher.write(&value.to_le_bytes());
+        }
+        if let Some(pattern) = &self.pattern {
+            // patterns are only known through the trait, their Debug output
+            // lists the type and every field
+            write!(hasher, "{:?}", pattern).unwrap();
+        }
+        hasher.finish()
+    }
+
     pub fn lighting(
         &self,
         object: &dyn Shape,
@@ -37,11 +76,7 @@ impl Material {
         normalv: Vector,
         in_shadow: bool,
     ) -> Color {
-        let color = match self.pattern.as_ref() {
-            Some(pattern) => pattern.pattern_at_object(object, point),
-            None => self.color,
-        };
-        let effective_color = color * light.intensity;
+        let effective_color = self.color_at(object, point) * light.intensity;
         let lightv = (light.position - point).normalize();
         let ambient = effective_color * self.ambient;
         let light_dot_normal = lightv.dot(normalv);
@@ -90,7 +125,7 @@ mod tests {
     fn lighting_eye_between_light_and_surface_eye_offset_45() {
         let m = Material::new();
         let position = Point::new(0.0, 0.0, 0.0);
-        let eyev = Vector::new(0.0, 2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0);
+        let eyev = Vector::new(0.0, Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0);
         let normalv = Vector::new(0.0, 0.0, -1.0);
         let light = PointLight::new(Point::new(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
         let results = m.lighting(&Sphere::new(), light, position, eyev, normalv, false);
@@ -112,7 +147,7 @@ mod tests {
     fn lighting_eye_in_path_reflection_vector() {
         let m = Material::new();
         let position = Point::new(0.0, 0.0, 0.0);
-        let eyev = Vector::new(0.0, -2_f64.sqrt() / 2.0, -2_f64.sqrt() / 2.0);
+        let eyev = Vector::new(0.0, -Float::sqrt(2.0) / 2.0, -Float::sqrt(2.0) / 2.0);
         let normalv = Vector::new(0.0, 0.0, -1.0);
         let light = PointLight::new(Point::new(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
         let results = m.lighting(&Sphere::new(), light, position, eyev, normalv, false);
@@ -187,4 +222,19 @@ mod tests {
         assert_eq!(m.transparency, 0.0);
         assert_eq!(m.refractive_index, 1.0);
     }
+
+    #[test]
+    fn equal_materials_share_an_id() {
+        let mut a = Material::new();
+        let mut b = Material::new();
+        assert_eq!(a.id(), b.id());
+
+        a.reflective = 0.5;
+        assert_ne!(a.id(), b.id());
+        b.reflective = 0.5;
+        b.pattern = Some(Box::new(Stripe::new(color::WHITE, color::BLACK)));
+        assert_ne!(a.id(), b.id());
+        a.pattern = Some(Box::new(Stripe::new(color::WHITE, color::BLACK)));
+        assert_eq!(a.id(), b.id());
+    }
 }
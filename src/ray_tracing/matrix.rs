@@ -1,10 +1,19 @@
-use crate::{float_eq, Point, Vector};
+use super::simd;
+use crate::{float_eq, Float, Normal, Point, Vector};
+use std::error::Error;
+use std::fmt;
 use std::ops::{Index, Mul};
 
+/// Serialised as its rows, the inverse is recomputed when loaded.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "[[Float; 4]; 4]", into = "[[Float; 4]; 4]")
+)]
 pub struct Matrix {
-    data: [[f64; 4]; 4],
-    inverse: [[f64; 4]; 4],
+    data: [[Float; 4]; 4],
+    inverse: [[Float; 4]; 4],
 }
 
 pub const IDENTITY: Matrix = Matrix {
@@ -22,17 +31,114 @@ pub const IDENTITY: Matrix = Matrix {
     ],
 };
 
+/// A determinant this small relative to the product of the row lengths (the
+/// largest it could be) means the rows are nearly dependent and the matrix is
+/// treated as singular. The test does not depend on the scale of the matrix.
+pub(crate) const SINGULAR_TOLERANCE: Float = 1000.0 * Float::EPSILON;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MatrixError {
+    /// The matrix has no inverse, its determinant is zero or close to it.
+    Singular,
+    /// The bottom row is not 0, 0, 0, 1.
+    NotAffine,
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Singular => write!(f, "matrix is not invertible"),
+            MatrixError::NotAffine => write!(f, "matrix is not an affine transform"),
+        }
+    }
+}
+
+impl Error for MatrixError {}
+
 impl Matrix {
-    pub fn new(data: [[f64; 4]; 4]) -> Self {
-        let mut i = [[0.; 4]; 4];
-        let d = Matrix::determinant(data, 4);
-        for row in 0..4 {
-            for col in 0..4 {
-                i[col][row] = Matrix::cofactor(data, row, col, 3) / d;
+    /// Creates the matrix without checking that it can be inverted, the
+    /// inverse of a singular matrix is all NaN. Use `try_new` for matrices
+    /// that may be degenerate.
+    pub fn new(data: [[Float; 4]; 4]) -> Self {
+        let inverse = Matrix::invert(data).unwrap_or([[Float::NAN; 4]; 4]);
+        Self { data, inverse }
+    }
+
+    pub fn try_new(data: [[Float; 4]; 4]) -> Result<Self, MatrixError> {
+        match Matrix::invert(data) {
+            Some(inverse) => Ok(Self { data, inverse }),
+            None => Err(MatrixError::Singular),
+        }
+    }
+
+    pub fn is_singular(&self) -> bool {
+        self.inverse.iter().flatten().any(|v| !v.is_finite())
+    }
+
+    fn invert(data: [[Float; 4]; 4]) -> Option<[[Float; 4]; 4]> {
+        let inverse = if data[3] == [0.0, 0.0, 0.0, 1.0] {
+            Matrix::invert_affine(data)?
+        } else {
+            let d = Matrix::determinant(data, 4);
+            if negligible(d, &data) {
+                return None;
+            }
+            let mut i = [[0.; 4]; 4];
+            for (col, inverse_row) in i.iter_mut().enumerate() {
+                for (row, value) in inverse_row.iter_mut().enumerate() {
+                    *value = Matrix::cofactor(data, row, col, 3) / d;
+                }
             }
+            i
+        };
+
+        if inverse.iter().flatten().all(|v| v.is_finite()) {
+            Some(inverse)
+        } else {
+            None
         }
+    }
+
+    // inverts the 3x3 linear part and translates by its negated image of
+    // the translation, most transforms take this path
+    fn invert_affine(m: [[Float; 4]; 4]) -> Option<[[Float; 4]; 4]> {
+        let c00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+        let c01 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+        let c02 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+        let d = m[0][0] * c00 + m[0][1] * c01 + m[0][2] * c02;
+        if negligible(d, &[&m[0][..3], &m[1][..3], &m[2][..3]]) {
+            return None;
+        }
+
+        let a = [
+            [
+                c00 / d,
+                (m[0][2] * m[2][1] - m[0][1] * m[2][2]) / d,
+                (m[0][1] * m[1][2] - m[0][2] * m[1][1]) / d,
+            ],
+            [
+                c01 / d,
+                (m[0][0] * m[2][2] - m[0][2] * m[2][0]) / d,
+                (m[0][2] * m[1][0] - m[0][0] * m[1][2]) / d,
+            ],
+            [
+                c02 / d,
+                (m[0][1] * m[2][0] - m[0][0] * m[2][1]) / d,
+                (m[0][0] * m[1][1] - m[0][1] * m[1][0]) / d,
+            ],
+        ];
 
-        Self { data, inverse: i }
+        let mut i = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0; 4],
+            [0.0; 4],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        for row in 0..3 {
+            i[row][..3].copy_from_slice(&a[row]);
+            i[row][3] = -(a[row][0] * m[0][3] + a[row][1] * m[1][3] + a[row][2] * m[2][3]);
+        }
+        Some(i)
     }
 
     pub fn transpose(&self) -> Self {
@@ -99,15 +205,11 @@ impl Matrix {
         }
     }
 
-    pub fn is_invertible(m: [[f64; 4]; 4]) -> bool {
-        let mut invertible = true;
-        if Matrix::determinant(m, 4) == 0.0 {
-            invertible = false
-        }
-        invertible
+    pub fn is_invertible(m: [[Float; 4]; 4]) -> bool {
+        Matrix::invert(m).is_some()
     }
 
-    fn determinant(a: [[f64; 4]; 4], s: usize) -> f64 {
+    fn determinant(a: [[Float; 4]; 4], s: usize) -> Float {
         let mut det = 0.;
 
         if s == 2 {
@@ -121,7 +223,7 @@ impl Matrix {
         det
     }
 
-    fn sub_matrix(a: [[f64; 4]; 4], r_row: usize, r_col: usize) -> [[f64; 4]; 4] {
+    fn sub_matrix(a: [[Float; 4]; 4], r_row: usize, r_col: usize) -> [[Float; 4]; 4] {
         let mut m = [[0.; 4]; 4];
 
         for (nri, ri) in [0, 1, 2, 3].iter().filter(|&&x| x != r_row).enumerate() {
@@ -133,11 +235,11 @@ impl Matrix {
         m
     }
 
-    fn minor(a: [[f64; 4]; 4], r_row: usize, r_col: usize, s: usize) -> f64 {
+    fn minor(a: [[Float; 4]; 4], r_row: usize, r_col: usize, s: usize) -> Float {
         Matrix::determinant(Matrix::sub_matrix(a, r_row, r_col), s)
     }
 
-    pub fn cofactor(a: [[f64; 4]; 4], r_row: usize, r_col: usize, s: usize) -> f64 {
+    pub fn cofactor(a: [[Float; 4]; 4], r_row: usize, r_col: usize, s: usize) -> Float {
         let mut minor = Matrix::minor(a, r_row, r_col, s);
         if (r_row + r_col) % 2 == 1 {
             minor *= -1.0
@@ -145,18 +247,8 @@ impl Matrix {
         minor
     }
 
-    pub fn multiple(a: [[f64; 4]; 4], b: [[f64; 4]; 4]) -> [[f64; 4]; 4] {
-        let mut results = [[0.0; 4]; 4];
-
-        for row in 0..4 {
-            for col in 0..4 {
-                results[row][col] = a[row][0] * b[0][col]
-                    + a[row][1] * b[1][col]
-                    + a[row][2] * b[2][col]
-                    + a[row][3] * b[3][col];
-            }
-        }
-        results
+    pub fn multiple(a: [[Float; 4]; 4], b: [[Float; 4]; 4]) -> [[Float; 4]; 4] {
+        simd::multiply(&a, &b)
     }
 }
 
@@ -172,20 +264,8 @@ impl Mul<Point> for Matrix {
     type Output = Point;
 
     fn mul(self, other: Point) -> Point {
-        let c1 = (self[0][0] * other.x)
-            + (self[0][1] * other.y)
-            + (self[0][2] * other.z)
-            + (self[0][3] * 1.0);
-        let c2 = (self[1][0] * other.x)
-            + (self[1][1] * other.y)
-            + (self[1][2] * other.z)
-            + (self[1][3] * 1.0);
-        let c3 = (self[2][0] * other.x)
-            + (self[2][1] * other.y)
-            + (self[2][2] * other.z)
-            + (self[2][3] * 1.0);
-
-        Point::new(c1, c2, c3)
+        let [x, y, z] = simd::transform(&self.data, [other.x, other.y, other.z], 1.0);
+        Point::new(x, y, z)
     }
 }
 
@@ -193,25 +273,36 @@ impl Mul<Vector> for Matrix {
     type Output = Vector;
 
     fn mul(self, other: Vector) -> Vector {
-        let c1 = (self[0][0] * other.x)
-            + (self[0][1] * other.y)
-            + (self[0][2] * other.z)
-            + (self[0][3] * 0.0);
-        let c2 = (self[1][0] * other.x)
-            + (self[1][1] * other.y)
-            + (self[1][2] * other.z)
-            + (self[1][3] * 0.0);
-        let c3 = (self[2][0] * other.x)
-            + (self[2][1] * other.y)
-            + (self[2][2] * other.z)
-            + (self[2][3] * 0.0);
-
-        Vector::new(c1, c2, c3)
+        let [x, y, z] = simd::transform(&self.data, [other.x, other.y, other.z], 0.0);
+        Vector::new(x, y, z)
+    }
+}
+
+impl Mul<Normal> for Matrix {
+    type Output = Normal;
+
+    /// Multiplies by the inverse transpose, the result is not normalized.
+    fn mul(self, other: Normal) -> Normal {
+        let inverse_transpose = self.transpose().inverse;
+        let [x, y, z] = simd::transform(&inverse_transpose, [other.x, other.y, other.z], 0.0);
+        Normal::new(x, y, z)
+    }
+}
+
+impl From<[[Float; 4]; 4]> for Matrix {
+    fn from(data: [[Float; 4]; 4]) -> Self {
+        Matrix::new(data)
+    }
+}
+
+impl From<Matrix> for [[Float; 4]; 4] {
+    fn from(m: Matrix) -> Self {
+        m.data
     }
 }
 
 impl Index<usize> for Matrix {
-    type Output = [f64; 4];
+    type Output = [Float; 4];
 
     fn index(&self, i: usize) -> &Self::Output {
         &self.data[i]
@@ -236,10 +327,20 @@ impl PartialEq for Matrix {
     }
 }
 
+/// Whether the determinant of the matrix with the given rows is too small to
+/// divide by, see `SINGULAR_TOLERANCE`.
+fn negligible<R: AsRef<[Float]>>(determinant: Float, rows: &[R]) -> bool {
+    let bound: Float = rows
+        .iter()
+        .map(|r| r.as_ref().iter().map(|v| v * v).sum::<Float>().sqrt())
+        .product();
+    determinant.is_nan() || determinant.abs() <= SINGULAR_TOLERANCE * bound
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Matrix, IDENTITY};
-    use crate::float_eq;
+    use super::{Matrix, MatrixError, IDENTITY};
+    use crate::{float_eq, Float, Point, Transform};
 
     #[test]
     fn determinant() {
@@ -456,7 +557,7 @@ mod tests {
             [9.0, 1.0, 7.0, -6.0],
         ];
         assert_eq!(-2120.0, Matrix::determinant(a, 4));
-        assert_eq!(true, Matrix::is_invertible(a))
+        assert!(Matrix::is_invertible(a))
     }
 
     #[test]
@@ -468,7 +569,7 @@ mod tests {
             [0.0, 0.0, 0.0, 0.0],
         ];
         assert_eq!(0.0, Matrix::determinant(a, 4));
-        assert_eq!(false, Matrix::is_invertible(a))
+        assert!(!Matrix::is_invertible(a))
     }
 
     #[test]
@@ -492,7 +593,7 @@ mod tests {
         for row in 0..4 {
             for col in 0..4 {
                 let are_equal = float_eq(b.data[row][col], expected.data[row][col]);
-                assert_eq!(true, are_equal);
+                assert!(are_equal);
             }
         }
     }
@@ -515,7 +616,7 @@ mod tests {
         ]);
         for row in 0..4 {
             for col in 0..4 {
-                assert_eq!(true, float_eq(b.data[row][col], expected.data[row][col]));
+                assert!(float_eq(b.data[row][col], expected.data[row][col]));
             }
         }
     }
@@ -539,7 +640,7 @@ mod tests {
         let actual = c * ib;
         for row in 0..4 {
             for col in 0..4 {
-                assert_eq!(true, float_eq(actual.data[row][col], a.data[row][col]));
+                assert!(float_eq(actual.data[row][col], a.data[row][col]));
             }
         }
     }
@@ -573,7 +674,7 @@ mod tests {
 
         for row in 0..4 {
             for col in 0..4 {
-                assert_eq!(true, float_eq(b.data[row][col], expected.data[row][col]));
+                assert!(float_eq(b.data[row][col], expected.data[row][col]));
             }
         }
 
@@ -586,9 +687,64 @@ mod tests {
             [-0.53846, 0.03077, 0.92308, -1.92308],
         ];
 
-        for row in 0..4 {
-            for col in 0..4 {
-                assert_eq!(true, float_eq(t.inverse[row][col], te[row][col]));
+        for (row, te_row) in te.iter().enumerate() {
+            for (col, expected) in te_row.iter().enumerate() {
+                assert!(float_eq(t.inverse[row][col], *expected));
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrices_are_rejected() {
+        let m = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        assert_eq!(Matrix::try_new(m), Err(MatrixError::Singular));
+        assert!(Matrix::new(m).is_singular());
+        assert!(!IDENTITY.is_singular());
+    }
+
+    #[test]
+    fn nearly_singular_matrices_are_rejected() {
+        // the third row is the sum of the first two up to rounding noise
+        let m = [
+            [1.0, 2.0, 3.0, 0.0],
+            [4.0, 5.0, 6.0, 0.0],
+            [5.0, 7.0, 9.0 + Float::EPSILON * 8.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        assert_eq!(Matrix::try_new(m), Err(MatrixError::Singular));
+        let mut general = m;
+        general[3] = [0.0, 0.0, 0.5, 1.0];
+        assert_eq!(Matrix::try_new(general), Err(MatrixError::Singular));
+    }
+
+    #[test]
+    fn small_scales_are_not_singular() {
+        let m = Transform::new().scaling(1e-6, 1e-6, 1e-6).build();
+        assert!(!m.is_singular());
+        assert_eq!(
+            m.inverse() * Point::new(1e-6, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn affine_inverse_matches_the_general_inverse() {
+        let m = [
+            [2.0, 0.5, -1.0, 3.0],
+            [0.0, 1.5, 0.25, -2.0],
+            [1.0, -0.5, 4.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let affine = Matrix::try_new(m).unwrap().inverse();
+        let d = Matrix::determinant(m, 4);
+        for (row, values) in affine.data.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                assert!(float_eq(*value, Matrix::cofactor(m, col, row, 3) / d));
             }
         }
     }
@@ -0,0 +1,105 @@
+use crate::{float_eq, Float, Vector};
+use std::ops::Neg;
+
+/// A surface normal. Unlike a `Vector`, multiplying it by a `Matrix` applies
+/// the inverse transpose, so normals stay perpendicular under non-uniform
+/// scaling and shearing.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Normal {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Normal {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
+        Normal { x, y, z }
+    }
+
+    pub fn normalize(self) -> Self {
+        Normal::from(self.to_vector().normalize())
+    }
+
+    pub fn dot(self, b: Vector) -> Float {
+        self.to_vector().dot(b)
+    }
+
+    pub fn to_vector(self) -> Vector {
+        Vector::new(self.x, self.y, self.z)
+    }
+}
+
+impl From<Vector> for Normal {
+    fn from(v: Vector) -> Self {
+        Normal::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Normal> for Vector {
+    fn from(n: Normal) -> Self {
+        n.to_vector()
+    }
+}
+
+impl Neg for Normal {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Normal::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl PartialEq for Normal {
+    fn eq(&self, other: &Normal) -> bool {
+        float_eq(self.x, other.x) && float_eq(self.y, other.y) && float_eq(self.z, other.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{FRAC_1_SQRT_2, PI};
+    use crate::{Point, Transform};
+
+    #[test]
+    fn matrix_times_normal_applies_the_inverse_transpose() {
+        let m = Transform::new()
+            .rotation_z(PI / 5.0)
+            .scaling(1.0, 0.5, 1.0)
+            .build();
+        let n = Normal::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        let expected = m.inverse().transpose() * n.to_vector();
+        assert_eq!((m * n).to_vector(), expected);
+    }
+
+    #[test]
+    fn normals_ignore_translation() {
+        let m = Transform::new().translation(5.0, -3.0, 2.0).build();
+        let n = Normal::new(0.0, 1.0, 0.0);
+        assert_eq!(m * n, n);
+    }
+
+    #[test]
+    fn a_transformed_normal_stays_perpendicular_to_the_surface() {
+        let m = Transform::new()
+            .shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+            .scaling(2.0, 1.0, 3.0)
+            .build();
+        let tangent = Point::new(1.0, 1.0, 0.0) - Point::new(0.0, 0.0, 0.0);
+        let n = Normal::new(1.0, -1.0, 0.0);
+        assert!(float_eq(n.dot(tangent), 0.0));
+        assert!(float_eq((m * n).dot(m * tangent), 0.0));
+        assert!(!float_eq(
+            Normal::from(m * n.to_vector()).dot(m * tangent),
+            0.0
+        ));
+    }
+
+    #[test]
+    fn converting_between_normals_and_vectors() {
+        let v = Vector::new(1.0, 2.0, 3.0);
+        assert_eq!(Vector::from(Normal::from(v)), v);
+        assert_eq!(-Normal::from(v), Normal::new(-1.0, -2.0, -3.0));
+    }
+}
@@ -0,0 +1,139 @@
+use super::simd;
+use crate::{Float, Matrix, Point, Ray, Vector};
+
+pub const PACKET_SIZE: usize = 4;
+
+/// Up to `PACKET_SIZE` rays stored as separate x, y and z lanes so a whole
+/// packet is transformed into object space with one pass of SIMD kernels.
+/// Unused lanes repeat the last ray and are ignored.
+#[derive(Debug, Copy, Clone)]
+pub struct RayPacket {
+    len: usize,
+    origins: [[Float; PACKET_SIZE]; 3],
+    directions: [[Float; PACKET_SIZE]; 3],
+}
+
+impl RayPacket {
+    /// Panics when there are no rays or more than `PACKET_SIZE`.
+    pub fn new(rays: &[Ray]) -> RayPacket {
+        assert!(!rays.is_empty() && rays.len() <= PACKET_SIZE);
+        let mut packet = RayPacket {
+            len: rays.len(),
+            origins: [[0.0; PACKET_SIZE]; 3],
+            directions: [[0.0; PACKET_SIZE]; 3],
+        };
+        for lane in 0..PACKET_SIZE {
+            let r = rays[lane.min(rays.len() - 1)];
+            for (axis, (o, d)) in [
+                (r.origin.x, r.direction.x),
+                (r.origin.y, r.direction.y),
+                (r.origin.z, r.direction.z),
+            ]
+            .iter()
+            .enumerate()
+            {
+                packet.origins[axis][lane] = *o;
+                packet.directions[axis][lane] = *d;
+            }
+        }
+        packet
+    }
+
+    /// Splits rays into packets, the last one may be partly filled.
+    pub fn packets(rays: &[Ray]) -> Vec<RayPacket> {
+        rays.chunks(PACKET_SIZE).map(RayPacket::new).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn ray(&self, i: usize) -> Ray {
+        Ray::new(
+            Point::new(self.origins[0][i], self.origins[1][i], self.origins[2][i]),
+            Vector::new(
+                self.directions[0][i],
+                self.directions[1][i],
+                self.directions[2][i],
+            ),
+        )
+    }
+
+    pub fn rays(&self) -> impl Iterator<Item = Ray> + '_ {
+        (0..self.len).map(move |i| self.ray(i))
+    }
+
+    pub fn transform(&self, m: &Matrix) -> RayPacket {
+        let data = [m[0], m[1], m[2], m[3]];
+        RayPacket {
+            len: self.len,
+            origins: simd::transform_packet(&data, self.origins, 1.0),
+            directions: simd::transform_packet(&data, self.directions, 0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Intersection, Transform, World};
+
+    fn rays() -> Vec<Ray> {
+        (0..6)
+            .map(|i| {
+                let x = i as Float * 0.3 - 0.75;
+                Ray::new(
+                    Point::new(x, 0.1, -5.0),
+                    Vector::new(0.01 * i as Float, 0.0, 1.0).normalize(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packets_keep_their_rays() {
+        let rays = rays();
+        let packets = RayPacket::packets(&rays);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].len(), 2);
+        let unpacked: Vec<Ray> = packets.iter().flat_map(|p| p.rays()).collect();
+        for (a, b) in rays.iter().zip(&unpacked) {
+            assert_eq!(a.origin, b.origin);
+            assert_eq!(a.direction, b.direction);
+        }
+    }
+
+    #[test]
+    fn transforming_a_packet() {
+        let m = Transform::new()
+            .scaling(2.0, 3.0, 4.0)
+            .translation(1.0, 2.0, 3.0)
+            .build();
+        let rays = rays();
+        let packet = RayPacket::new(&rays[..4]).transform(&m);
+        for (i, r) in rays[..4].iter().enumerate() {
+            let expected = r.transform(&m);
+            assert_eq!(packet.ray(i).origin, expected.origin);
+            assert_eq!(packet.ray(i).direction, expected.direction);
+        }
+    }
+
+    #[test]
+    fn packet_intersections_match_single_rays() {
+        let w = World::default();
+        let rays = rays();
+        for packet in RayPacket::packets(&rays) {
+            let results = w.intersect_packet(&packet);
+            for (ray, xs) in packet.rays().zip(results) {
+                let ts = |xs: Option<Vec<Intersection>>| {
+                    xs.map(|xs| xs.iter().map(|i| i.t).collect::<Vec<Float>>())
+                };
+                assert_eq!(ts(xs), ts(w.intersect(ray)));
+            }
+        }
+    }
+}
@@ -2,6 +2,7 @@ use crate::{shapes::Shape, Color, Matrix, Point};
 use std::any::Any;
 use std::fmt;
 
+#[cfg_attr(feature = "serde", typetag::serde)]
 pub trait Pattern: Send + Any + fmt::Debug {
     fn pattern_eq(&self, other: &dyn Any) -> bool;
 
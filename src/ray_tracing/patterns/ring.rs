@@ -3,6 +3,7 @@ use crate::{ray_tracing::matrix, Color, Matrix, Point};
 use std::any::Any;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ring {
     pub a: Color,
     pub b: Color,
@@ -19,13 +20,14 @@ impl Ring {
     }
 }
 
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Pattern for Ring {
     fn as_any(&self) -> &dyn Any {
         self
     }
 
     fn pattern_eq(&self, other: &dyn Any) -> bool {
-        other.downcast_ref::<Self>().map_or(false, |a| self == a)
+        other.downcast_ref::<Self>() == Some(self)
     }
 
     fn transform(&self) -> Matrix {
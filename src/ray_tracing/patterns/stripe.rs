@@ -4,6 +4,7 @@ use super::Pattern;
 use crate::{ray_tracing::matrix, Color, Matrix, Point};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stripe {
     pub a: Color,
     pub b: Color,
@@ -20,13 +21,14 @@ impl Stripe {
     }
 }
 
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Pattern for Stripe {
     fn as_any(&self) -> &dyn Any {
         self
     }
 
     fn pattern_eq(&self, other: &dyn Any) -> bool {
-        other.downcast_ref::<Self>().map_or(false, |a| self == a)
+        other.downcast_ref::<Self>() == Some(self)
     }
 
     fn transform(&self) -> Matrix {
@@ -1,20 +1,24 @@
-use crate::float_eq;
+use super::tuple::componentwise;
 use crate::Vector;
+use crate::{float_eq, Float};
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 impl Point {
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Point { x, y, z }
     }
 }
 
+componentwise!(Point);
+
 impl Sub for Point {
     type Output = Vector;
 
@@ -63,10 +67,10 @@ impl Add<Vector> for Point {
     }
 }
 
-impl Mul<f64> for Point {
+impl Mul<Float> for Point {
     type Output = Self;
 
-    fn mul(self, other: f64) -> Self {
+    fn mul(self, other: Float) -> Self {
         Self {
             x: self.x * other,
             y: self.y * other,
@@ -75,7 +79,7 @@ impl Mul<f64> for Point {
     }
 }
 
-impl Mul<Point> for f64 {
+impl Mul<Point> for Float {
     type Output = Point;
 
     fn mul(self, other: Point) -> Point {
@@ -87,10 +91,10 @@ impl Mul<Point> for f64 {
     }
 }
 
-impl Div<f64> for Point {
+impl Div<Float> for Point {
     type Output = Point;
 
-    fn div(self, other: f64) -> Point {
+    fn div(self, other: Float) -> Point {
         Point {
             x: self.x / other,
             y: self.y / other,
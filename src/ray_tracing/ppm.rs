@@ -0,0 +1,265 @@
+use super::tone_map::PostProcess;
+use crate::{Canvas, Color, Float};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PpmError {
+    /// The magic number is neither `P3` nor `P6`.
+    UnsupportedFormat(String),
+    /// The header or a pixel value could not be parsed.
+    Malformed(String),
+    /// The data ended before every pixel was read.
+    UnexpectedEof,
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PpmError::UnsupportedFormat(magic) => write!(f, "unsupported PPM format {:?}", magic),
+            PpmError::Malformed(message) => write!(f, "malformed PPM: {}", message),
+            PpmError::UnexpectedEof => write!(f, "unexpected end of PPM data"),
+        }
+    }
+}
+
+impl Error for PpmError {}
+
+/// Parses a plain (`P3`) or binary (`P6`) PPM image. Channel values are
+/// scaled by the maximum value of the header into the 0..1 range.
+pub fn parse(data: &[u8]) -> Result<Canvas, PpmError> {
+    let mut reader = Reader { data, pos: 0 };
+
+    let magic = reader.token()?;
+    let binary = match magic.as_str() {
+        "P3" => false,
+        "P6" => true,
+        _ => return Err(PpmError::UnsupportedFormat(magic)),
+    };
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max_value = reader.number()?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(PpmError::Malformed(format!(
+            "maximum value {} out of range",
+            max_value
+        )));
+    }
+
+    let values = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| PpmError::Malformed(format!("size {}x{} too large", width, height)))?;
+
+    let bytes_per_value = if max_value < 256 { 1 } else { 2 };
+    let raster = if binary {
+        // exactly one whitespace byte separates the header from the raster
+        let start = reader.pos + 1;
+        values
+            .checked_mul(bytes_per_value)
+            .and_then(|needed| start.checked_add(needed))
+            .and_then(|end| data.get(start..end))
+            .ok_or(PpmError::UnexpectedEof)?
+    } else {
+        // every plain value takes at least a separator and one digit
+        if data.len() - reader.pos < values.saturating_mul(2) {
+            return Err(PpmError::UnexpectedEof);
+        }
+        &data[..0]
+    };
+
+    let mut canvas = Canvas::new(width, height);
+    let scale = max_value as Float;
+    let channel = |value: usize| {
+        if value > max_value {
+            Err(PpmError::Malformed(format!(
+                "value {} above the maximum {}",
+                value, max_value
+            )))
+        } else {
+            Ok(value as Float / scale)
+        }
+    };
+
+    if binary {
+        let samples = raster
+            .chunks(bytes_per_value)
+            .map(|b| channel(b.iter().fold(0, |v, &b| v << 8 | b as usize)))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (pixel, v) in canvas.pixels_mut().iter_mut().zip(samples.chunks(3)) {
+            *pixel = Color::new(v[0], v[1], v[2]);
+        }
+    } else {
+        for pixel in canvas.pixels_mut() {
+            let red = channel(reader.number()?)?;
+            let green = channel(reader.number()?)?;
+            let blue = channel(reader.number()?)?;
+            *pixel = Color::new(red, green, blue);
+        }
+    }
+
+    Ok(canvas)
+}
+
+/// Writes the canvas as a binary (`P6`) PPM image with 8 bits per channel,
+/// quantized the same way as `Canvas::canvas_to_ppm`.
+pub fn write_p6<W: Write>(canvas: &Canvas, w: &mut W) -> io::Result<()> {
+    let settings = PostProcess::new();
+    write!(w, "P6\n{} {}\n255\n", canvas.width(), canvas.height())?;
+
+    let mut line = Vec::with_capacity(canvas.width() * 3);
+    for (y, row) in canvas.rows().enumerate() {
+        line.clear();
+        for (x, p) in row.iter().enumerate() {
+            let (r, g, b) = settings.quantize(*p, x, y);
+            line.extend_from_slice(&[r, g, b]);
+        }
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// Returns the next whitespace separated token, skipping `#` comments.
+    fn token(&mut self) -> Result<String, PpmError> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while let Some(&b) = self.data.get(self.pos) {
+                        self.pos += 1;
+                        if b == b'\n' {
+                            break;
+                        }
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(PpmError::UnexpectedEof),
+            }
+        }
+
+        let start = self.pos;
+        while let Some(b) = self.data.get(self.pos) {
+            if b.is_ascii_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self) -> Result<usize, PpmError> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| PpmError::Malformed(format!("expected a number, found {:?}", token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_a_plain_ppm() {
+        let ppm = b"P3\n# a comment\n2 1\n255\n255 0 0  0 51 255\n";
+        let c = parse(ppm).unwrap();
+        assert_eq!(c.width(), 2);
+        assert_eq!(c.height(), 1);
+        assert_eq!(c.pixel_at(0, 0).unwrap(), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(c.pixel_at(1, 0).unwrap(), Color::new(0.0, 0.2, 1.0));
+    }
+
+    #[test]
+    fn the_maximum_value_scales_channels() {
+        let c = parse(b"P3 1 1 100 50 25 100").unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), Color::new(0.5, 0.25, 1.0));
+    }
+
+    #[test]
+    fn canvas_to_ppm_output_parses_back() {
+        let mut c = Canvas::new(10, 2);
+        for x in 0..10 {
+            c.write_pixel(x, 1, Color::new(1.0, 0.8, 0.6)).unwrap();
+        }
+        let parsed = parse(c.canvas_to_ppm().as_bytes()).unwrap();
+        assert_eq!(parsed.pixel_at(0, 0).unwrap(), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(parsed.pixel_at(9, 1).unwrap(), Color::new(1.0, 0.8, 0.6));
+    }
+
+    #[test]
+    fn binary_ppm_round_trip() {
+        let mut c = Canvas::new(3, 2);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0)).unwrap();
+        c.write_pixel(2, 1, Color::new(0.2, 0.4, 0.6)).unwrap();
+
+        let mut buffer = Vec::new();
+        write_p6(&c, &mut buffer).unwrap();
+        assert!(buffer.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(buffer.len(), 11 + 3 * 2 * 3);
+
+        let parsed = parse(&buffer).unwrap();
+        assert_eq!(parsed.pixel_at(0, 0).unwrap(), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(parsed.pixel_at(2, 1).unwrap(), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn sizes_that_overflow_are_rejected() {
+        let ppm = format!("P6 {} {} 255\n", usize::MAX / 2, 3);
+        assert!(matches!(parse(ppm.as_bytes()), Err(PpmError::Malformed(_))));
+    }
+
+    #[test]
+    fn a_short_raster_is_rejected_before_allocating() {
+        assert!(matches!(
+            parse(b"P6 100000 100000 255\n\0\0\0"),
+            Err(PpmError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            parse(b"P3 100000 100000 255 0 0 0"),
+            Err(PpmError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn values_above_the_maximum_are_rejected() {
+        assert!(matches!(
+            parse(b"P3 1 1 100 50 101 0"),
+            Err(PpmError::Malformed(_))
+        ));
+        let mut ppm = b"P6 1 1 100\n".to_vec();
+        ppm.extend_from_slice(&[0, 200, 0]);
+        assert!(matches!(parse(&ppm), Err(PpmError::Malformed(_))));
+    }
+
+    #[test]
+    fn sixteen_bit_binary_ppm() {
+        let mut ppm = b"P6 1 1 65535\n".to_vec();
+        ppm.extend_from_slice(&[0xff, 0xff, 0x00, 0x00, 0x80, 0x00]);
+        let c = parse(&ppm).unwrap();
+        assert_eq!(c.pixel_at(0, 0).unwrap(), Color::new(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn invalid_ppm_data() {
+        assert_eq!(
+            parse(b"P5 1 1 255 0").err(),
+            Some(PpmError::UnsupportedFormat("P5".to_string()))
+        );
+        assert_eq!(
+            parse(b"P3 2 1 255 0 0 0").err(),
+            Some(PpmError::UnexpectedEof)
+        );
+        assert_eq!(
+            parse(b"P6 2 1 255\n\0\0\0").err(),
+            Some(PpmError::UnexpectedEof)
+        );
+        assert!(matches!(parse(b"P3 x 1 255"), Err(PpmError::Malformed(_))));
+    }
+}
@@ -0,0 +1,321 @@
+use super::sampler::{halton, pixel_rng, Filter, SamplePattern, Sampler};
+use super::world::SingularTransform;
+use crate::{Camera, Canvas, Color, Float, World};
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag to stop a running render from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clears the flag so a cancelled session can be resumed.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// The render was cancelled, `canvas` holds the image refined so far.
+#[derive(Debug, Clone)]
+pub struct Cancelled {
+    pub canvas: Canvas,
+    pub progress: Float,
+}
+
+/// Renders an image in passes, every pass adds one sample to each pixel.
+/// Sample positions and weights come from the pattern and filter of the
+/// sampler, by default a per pixel rotated Halton sequence with a box filter
+/// so the image converges evenly. The sample counts of the sampler are
+/// ignored, the number of passes decides them. A session with the same
+/// camera seed always produces the same image.
+pub struct RenderSession<'a> {
+    camera: &'a Camera,
+    world: &'a World,
+    rd: usize,
+    sampler: Sampler,
+    cancel: CancelToken,
+    sums: Vec<Color>,
+    weights: Vec<Float>,
+    plain_sums: Vec<Color>,
+    samples: Vec<usize>,
+    passes: usize,
+}
+
+impl<'a> RenderSession<'a> {
+    /// A shape with a singular transform is an error, like in
+    /// `Camera::try_render`. The session borrows the world, so it can not
+    /// change before the passes are rendered.
+    pub fn new(
+        camera: &'a Camera,
+        world: &'a World,
+        rd: usize,
+    ) -> Result<RenderSession<'a>, SingularTransform> {
+        world.validate()?;
+        let pixels = camera.hsize() * camera.vsize();
+        Ok(RenderSession {
+            camera,
+            world,
+            rd,
+            sampler: Sampler::new(SamplePattern::Halton, Filter::Box),
+            cancel: CancelToken::new(),
+            sums: vec![Color::new(0.0, 0.0, 0.0); pixels],
+            weights: vec![0.0; pixels],
+            plain_sums: vec![Color::new(0.0, 0.0, 0.0); pixels],
+            samples: vec![0; pixels],
+            passes: 0,
+        })
+    }
+
+    /// Only meaningful before the first pass, earlier samples keep the
+    /// positions and weights they were taken with.
+    pub fn with_sampler<S: Into<Sampler>>(mut self, sampler: S) -> RenderSession<'a> {
+        self.sampler = sampler.into();
+        self
+    }
+
+    pub fn with_cancel_token(mut self, token: CancelToken) -> RenderSession<'a> {
+        self.cancel = token;
+        self
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Number of completed passes.
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    /// Adds one sample to every pixel. Returns false when the session was
+    /// cancelled, the pass is then left incomplete.
+    pub fn render_pass(&mut self) -> bool {
+        let (hsize, vsize) = (self.camera.hsize(), self.camera.vsize());
+        let index = self.passes + 1;
+
+        for y in 0..vsize {
+            if self.cancel.is_cancelled() {
+                return false;
+            }
+            for x in 0..hsize {
+                let i = y * hsize + x;
+                if self.samples[i] > self.passes {
+                    // already sampled by an earlier, cancelled attempt
+                    continue;
+                }
+                let (u, v) = self.position(index, x, y);
+                let radius = self.sampler.filter.radius();
+                let dx = (u * 2.0 - 1.0) * radius;
+                let dy = (v * 2.0 - 1.0) * radius;
+                let ray = self.camera.ray_for_pixel(x as Float + dx, y as Float + dy);
+                let color = self.world.color_at(ray, self.rd);
+                let weight = self.sampler.filter.weight(dx, dy);
+                self.sums[i] = self.sums[i] + color * weight;
+                self.weights[i] += weight;
+                self.plain_sums[i] = self.plain_sums[i] + color;
+                self.samples[i] += 1;
+            }
+        }
+
+        self.passes += 1;
+        true
+    }
+
+    /// Position of the `index`th sample of the pixel in the unit square.
+    fn position(&self, index: usize, x: usize, y: usize) -> (Float, Float) {
+        match self.sampler.pattern {
+            SamplePattern::Halton => {
+                let mut rng = pixel_rng(self.camera.seed(), x, y);
+                (
+                    (halton(index, 2) + rng.gen::<Float>()) % 1.0,
+                    (halton(index, 3) + rng.gen::<Float>()) % 1.0,
+                )
+            }
+            // a single sample per pass can not be stratified, every pass
+            // draws a fresh uniform position
+            SamplePattern::Jittered => {
+                let seed = self.camera.seed().wrapping_add(index as u64);
+                let mut rng = pixel_rng(seed, x, y);
+                (rng.gen(), rng.gen())
+            }
+        }
+    }
+
+    /// The current estimate, pixels without samples are black.
+    pub fn canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.camera.hsize(), self.camera.vsize());
+        for (i, pixel) in canvas.pixels_mut().iter_mut().enumerate() {
+            let n = self.samples[i];
+            if self.weights[i] > 0.0 {
+                *pixel = self.sums[i] / self.weights[i];
+            } else if n > 0 {
+                *pixel = self.plain_sums[i] / n as Float;
+            }
+        }
+        canvas
+    }
+
+    /// Renders up to `passes` passes, calling `callback` with the refined
+    /// image and the fraction of the work done after each of them.
+    pub fn run<F>(&mut self, passes: usize, mut callback: F) -> Result<Canvas, Cancelled>
+    where
+        F: FnMut(&Canvas, Float),
+    {
+        let total = passes.max(1) as Float;
+        let start = self.passes;
+        while self.passes - start < passes {
+            if !self.render_pass() {
+                return Err(Cancelled {
+                    canvas: self.canvas(),
+                    progress: (self.passes - start) as Float / total,
+                });
+            }
+            callback(&self.canvas(), (self.passes - start) as Float / total);
+        }
+        Ok(self.canvas())
+    }
+}
+
+impl Camera {
+    pub fn render_session<'a>(
+        &'a self,
+        world: &'a World,
+        rd: usize,
+    ) -> Result<RenderSession<'a>, SingularTransform> {
+        RenderSession::new(self, world, rd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::{Point, Vector};
+
+    fn camera() -> Camera {
+        Camera::builder(11, 11)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build()
+    }
+
+    #[test]
+    fn the_callback_sees_every_pass() {
+        let c = camera();
+        let w = World::default();
+        let mut progress = Vec::new();
+        let canvas = c
+            .render_session(&w, 5)
+            .unwrap()
+            .run(4, |canvas, p| {
+                assert_eq!(canvas.width(), 11);
+                progress.push(p);
+            })
+            .unwrap();
+
+        assert_eq!(progress, vec![0.25, 0.5, 0.75, 1.0]);
+        let center = canvas.pixel_at(5, 5).unwrap();
+        // averaged over the pixel footprint instead of the center ray, f32
+        // builds draw other sample positions from the same seed
+        let tolerance = if cfg!(feature = "f32") { 0.1 } else { 0.05 };
+        assert!((center.red - 0.38066).abs() < tolerance);
+    }
+
+    #[test]
+    fn cancelling_from_the_callback() {
+        let c = camera();
+        let w = World::default();
+        let mut session = c.render_session(&w, 5).unwrap();
+        let token = session.cancel_token();
+
+        let mut calls = 0;
+        let result = session.run(4, |_, _| {
+            calls += 1;
+            if calls == 2 {
+                token.cancel();
+            }
+        });
+
+        let cancelled = result.unwrap_err();
+        assert_eq!(calls, 2);
+        assert_eq!(cancelled.progress, 0.5);
+        assert_eq!(session.passes(), 2);
+        assert_eq!(cancelled.canvas.width(), 11);
+    }
+
+    #[test]
+    fn sessions_are_repeatable() {
+        let c = camera();
+        let w = World::default();
+        let a = c.render_session(&w, 5).unwrap().run(3, |_, _| {}).unwrap();
+        let b = c.render_session(&w, 5).unwrap().run(3, |_, _| {}).unwrap();
+        assert_eq!(a.compare(&b).unwrap().max_error, 0.0);
+    }
+
+    #[test]
+    fn a_reset_token_resumes_the_session() {
+        let c = camera();
+        let w = World::default();
+        let mut session = c.render_session(&w, 5).unwrap();
+        let token = session.cancel_token();
+
+        token.cancel();
+        assert!(session.run(2, |_, _| {}).is_err());
+        token.reset();
+        assert!(session.run(2, |_, _| {}).is_ok());
+        assert_eq!(session.passes(), 2);
+    }
+
+    #[test]
+    fn the_sampler_pattern_and_filter_are_honored() {
+        let c = camera();
+        let w = World::default();
+        let render = |sampler: Sampler| {
+            c.render_session(&w, 5)
+                .unwrap()
+                .with_sampler(sampler)
+                .run(4, |_, _| {})
+                .unwrap()
+        };
+        let default = c.render_session(&w, 5).unwrap().run(4, |_, _| {}).unwrap();
+        let halton_box = render(Sampler::new(SamplePattern::Halton, Filter::Box));
+        let jittered = render(Sampler::new(SamplePattern::Jittered, Filter::Box));
+        let tent = render(Sampler::new(SamplePattern::Halton, Filter::Tent));
+
+        assert_eq!(default.compare(&halton_box).unwrap().max_error, 0.0);
+        assert!(default.compare(&jittered).unwrap().max_error > 0.0);
+        assert!(default.compare(&tent).unwrap().max_error > 0.0);
+    }
+
+    #[test]
+    fn cancelling_from_another_thread() {
+        let c = camera();
+        let w = World::default();
+        let token = CancelToken::new();
+        let mut session = c
+            .render_session(&w, 5)
+            .unwrap()
+            .with_cancel_token(token.clone());
+
+        std::thread::spawn(move || token.cancel()).join().unwrap();
+        assert!(!session.render_pass());
+        assert_eq!(session.passes(), 0);
+    }
+}
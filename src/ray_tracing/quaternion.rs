@@ -0,0 +1,419 @@
+use super::matrix::{MatrixError, SINGULAR_TOLERANCE};
+use crate::{float_eq, Float, Matrix, Transform, Vector};
+use std::ops::Mul;
+
+/// Unit quaternion describing a rotation, free of the gimbal lock of
+/// chained Euler rotations.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation by `angle` radians around `axis`, counter clockwise when
+    /// looking down the axis like `rotation_x/y/z`.
+    pub fn from_axis_angle(axis: Vector, angle: Float) -> Quaternion {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quaternion::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    /// Rotation turning the z axis towards `forward` and the y axis as close
+    /// to `up` as possible. `up` must not be parallel to `forward`.
+    pub fn look_rotation(forward: Vector, up: Vector) -> Quaternion {
+        let forward = forward.normalize();
+        let right = up.cross(forward).normalize();
+        let up = forward.cross(right);
+        Quaternion::from_basis(right, up, forward)
+    }
+
+    /// Extracts the rotation of a matrix without scale or shear, see
+    /// `Matrix::decompose` for other matrices.
+    pub fn from_matrix(m: &Matrix) -> Quaternion {
+        Quaternion::from_basis(
+            Vector::new(m[0][0], m[1][0], m[2][0]),
+            Vector::new(m[0][1], m[1][1], m[2][1]),
+            Vector::new(m[0][2], m[1][2], m[2][2]),
+        )
+    }
+
+    // the columns of a rotation matrix are the rotated axes
+    fn from_basis(x: Vector, y: Vector, z: Vector) -> Quaternion {
+        let trace = x.x + y.y + z.z;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(0.25 * s, (y.z - z.y) / s, (z.x - x.z) / s, (x.y - y.x) / s)
+        } else if x.x > y.y && x.x > z.z {
+            let s = (1.0 + x.x - y.y - z.z).sqrt() * 2.0;
+            Quaternion::new((y.z - z.y) / s, 0.25 * s, (y.x + x.y) / s, (z.x + x.z) / s)
+        } else if y.y > z.z {
+            let s = (1.0 + y.y - x.x - z.z).sqrt() * 2.0;
+            Quaternion::new((z.x - x.z) / s, (y.x + x.y) / s, 0.25 * s, (z.y + y.z) / s)
+        } else {
+            let s = (1.0 + z.z - x.x - y.y).sqrt() * 2.0;
+            Quaternion::new((x.y - y.x) / s, (z.x + x.z) / s, (z.y + y.z) / s, 0.25 * s)
+        };
+        q.normalize()
+    }
+
+    pub fn dot(self, other: Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn magnitude(self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let m = self.magnitude();
+        Quaternion::new(self.w / m, self.x / m, self.y / m, self.z / m)
+    }
+
+    /// The inverse rotation, for unit quaternions.
+    pub fn conjugate(self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Axis and angle in radians, the axis is x for the identity.
+    pub fn to_axis_angle(self) -> (Vector, Float) {
+        let q = if self.w < 0.0 {
+            Quaternion::new(-self.w, -self.x, -self.y, -self.z)
+        } else {
+            self
+        };
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin < 1e-6 {
+            return (Vector::new(1.0, 0.0, 0.0), 0.0);
+        }
+        (
+            Vector::new(q.x / sin, q.y / sin, q.z / sin),
+            2.0 * q.w.min(1.0).acos(),
+        )
+    }
+
+    pub fn rotate(self, v: Vector) -> Vector {
+        let u = Vector::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    pub fn to_matrix(self) -> Matrix {
+        let Quaternion { w, x, y, z } = self;
+        Matrix::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Spherical interpolation along the shortest arc, `t` 0 is `self` and
+    /// 1 is `other`.
+    pub fn slerp(self, other: Quaternion, t: Float) -> Quaternion {
+        let mut d = self.dot(other);
+        let other = if d < 0.0 {
+            d = -d;
+            Quaternion::new(-other.w, -other.x, -other.y, -other.z)
+        } else {
+            other
+        };
+
+        let (a, b) = if d > 0.9995 {
+            // nearly the same rotation, a straight line avoids dividing by
+            // a vanishing sine
+            (1.0 - t, t)
+        } else {
+            let theta = d.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Applies `other` first, then `self`, like matrix multiplication.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+/// `q` and `-q` are the same rotation and compare equal.
+/// Equal when both describe the same rotation, `q` and `-q` included. They
+/// are normalized first so the magnitude does not matter.
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Quaternion) -> bool {
+        float_eq(self.normalize().dot(other.normalize()).abs(), 1.0)
+    }
+}
+
+/// An affine matrix split into scale, then rotation, then translation.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vector,
+    pub rotation: Quaternion,
+    pub scale: Vector,
+}
+
+impl Decomposition {
+    pub fn to_matrix(&self) -> Matrix {
+        Transform::new()
+            .scaling(self.scale.x, self.scale.y, self.scale.z)
+            .rotation(self.rotation)
+            .translation(self.translation.x, self.translation.y, self.translation.z)
+            .build()
+    }
+
+    /// Interpolates translation and scale linearly and the rotation along
+    /// the shortest arc, for animating between two transforms.
+    pub fn interpolate(&self, other: &Decomposition, t: Float) -> Decomposition {
+        Decomposition {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
+
+impl Matrix {
+    /// Splits an affine matrix into translation, rotation and scale. Shear
+    /// can not be represented and is dropped. A mirroring matrix gets a
+    /// negative x scale.
+    pub fn decompose(&self) -> Result<Decomposition, MatrixError> {
+        if !(self[3][0] == 0.0 && self[3][1] == 0.0 && self[3][2] == 0.0 && self[3][3] == 1.0) {
+            return Err(MatrixError::NotAffine);
+        }
+
+        let column = |c: usize| Vector::new(self[0][c], self[1][c], self[2][c]);
+        let (mut x, y, z) = (column(0), column(1), column(2));
+        let mut scale = Vector::new(x.magnitude(), y.magnitude(), z.magnitude());
+        // the volume spanned by the columns, relative to the largest it could
+        // be for their lengths, like `Matrix::invert`
+        let volume = x.dot(y.cross(z));
+        if volume.is_nan() || volume.abs() <= SINGULAR_TOLERANCE * scale.x * scale.y * scale.z {
+            return Err(MatrixError::Singular);
+        }
+        if volume < 0.0 {
+            scale.x = -scale.x;
+            x = -x;
+        }
+
+        Ok(Decomposition {
+            translation: Vector::new(self[0][3], self[1][3], self[2][3]),
+            rotation: Quaternion::from_basis(x / scale.x.abs(), y / scale.y, z / scale.z),
+            scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use crate::Point;
+
+    #[test]
+    fn axis_angle_matches_euler_rotations() {
+        let p = Point::new(1.0, 2.0, 3.0);
+        let axes = [
+            (Vector::new(1.0, 0.0, 0.0), Transform::new().rotation_x(0.7)),
+            (Vector::new(0.0, 1.0, 0.0), Transform::new().rotation_y(0.7)),
+            (Vector::new(0.0, 0.0, 1.0), Transform::new().rotation_z(0.7)),
+        ];
+        for (axis, euler) in axes.iter() {
+            let q = Quaternion::from_axis_angle(*axis, 0.7);
+            assert_eq!(q.to_matrix() * p, euler.build() * p);
+        }
+    }
+
+    #[test]
+    fn rotating_a_vector() {
+        let q = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), FRAC_PI_2);
+        assert_eq!(
+            q.rotate(Vector::new(0.0, 0.0, 1.0)),
+            Vector::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            q.conjugate().rotate(Vector::new(1.0, 0.0, 0.0)),
+            Vector::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn composing_rotations() {
+        let a = Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 0.0), 0.3);
+        let b = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 1.1);
+        assert_eq!((a * b).to_matrix(), a.to_matrix() * b.to_matrix());
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        for &angle in &[0.0, 0.5, FRAC_PI_2, 3.0, PI] {
+            let axis = Vector::new(1.0, -2.0, 0.5);
+            let q = Quaternion::from_axis_angle(axis, angle);
+            assert_eq!(Quaternion::from_matrix(&q.to_matrix()), q);
+        }
+    }
+
+    #[test]
+    fn equality_ignores_the_magnitude() {
+        let q = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 0.6);
+        let scaled = Quaternion::new(q.w * 3.0, q.x * 3.0, q.y * 3.0, q.z * 3.0);
+        assert_eq!(scaled, q);
+        assert_eq!(Quaternion::new(-2.0, 0.0, 0.0, 0.0), Quaternion::identity());
+        assert_ne!(Quaternion::new(0.0, 2.0, 0.0, 0.0), Quaternion::identity());
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let q = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 2.0), 1.25);
+        let (axis, angle) = q.to_axis_angle();
+        assert_eq!(axis, Vector::new(0.0, 0.0, 1.0));
+        assert!(float_eq(angle, 1.25));
+    }
+
+    #[test]
+    fn look_rotation_turns_z_to_forward() {
+        let forward = Vector::new(1.0, 0.0, 1.0);
+        let q = Quaternion::look_rotation(forward, Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(q.rotate(Vector::new(0.0, 0.0, 1.0)), forward.normalize());
+        assert_eq!(
+            q.rotate(Vector::new(0.0, 1.0, 0.0)),
+            Vector::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn slerp_halfway() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), FRAC_PI_2);
+        let half = a.slerp(b, 0.5);
+        assert_eq!(
+            half,
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), FRAC_PI_4)
+        );
+        assert_eq!(a.slerp(b, 0.0), a);
+        assert_eq!(a.slerp(b, 1.0), b);
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way() {
+        let a = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), 0.1);
+        let b = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), 2.0 * PI - 0.1);
+        let (_, angle) = a.slerp(b, 0.5).to_axis_angle();
+        assert!(float_eq(angle, 0.0));
+    }
+
+    #[test]
+    fn decomposing_a_transform() {
+        let q = Quaternion::from_axis_angle(Vector::new(1.0, 1.0, 0.0), 0.8);
+        let m = Transform::new()
+            .scaling(2.0, 3.0, 0.5)
+            .rotation(q)
+            .translation(1.0, -2.0, 3.0)
+            .build();
+
+        let d = m.decompose().unwrap();
+        assert_eq!(d.translation, Vector::new(1.0, -2.0, 3.0));
+        assert_eq!(d.scale, Vector::new(2.0, 3.0, 0.5));
+        assert_eq!(d.rotation, q);
+        assert_eq!(d.to_matrix(), m);
+    }
+
+    #[test]
+    fn decomposing_a_mirror() {
+        let m = Transform::new().scaling(-1.0, 1.0, 1.0).build();
+        let d = m.decompose().unwrap();
+        assert_eq!(d.scale, Vector::new(-1.0, 1.0, 1.0));
+        assert_eq!(d.to_matrix(), m);
+    }
+
+    #[test]
+    fn decomposing_degenerate_matrices() {
+        let flat = Transform::new().scaling(1.0, 0.0, 1.0).build();
+        assert_eq!(flat.decompose(), Err(MatrixError::Singular));
+        let nearly_flat = Matrix::new([
+            [1.0, 1.0, 0.0, 0.0],
+            [0.0, 1e-14, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(nearly_flat.decompose(), Err(MatrixError::Singular));
+        let tiny = Transform::new().scaling(1e-6, 1e-6, 1e-6).build();
+        assert!(tiny.decompose().is_ok());
+        let projective = Matrix::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+        assert_eq!(projective.decompose(), Err(MatrixError::NotAffine));
+    }
+
+    #[test]
+    fn interpolating_transforms() {
+        let a = Transform::new().build().decompose().unwrap();
+        let b = Transform::new()
+            .scaling(3.0, 3.0, 3.0)
+            .rotation_y(FRAC_PI_2)
+            .translation(4.0, 0.0, 0.0)
+            .build()
+            .decompose()
+            .unwrap();
+
+        let mid = a.interpolate(&b, 0.5);
+        assert_eq!(mid.translation, Vector::new(2.0, 0.0, 0.0));
+        assert_eq!(mid.scale, Vector::new(2.0, 2.0, 2.0));
+        assert_eq!(
+            mid.rotation,
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), FRAC_PI_4)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Matrix, Point, Vector};
+use crate::{Float, Matrix, Point, Vector};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
@@ -11,7 +11,7 @@ impl Ray {
         Ray { origin, direction }
     }
 
-    pub fn position(&self, t: f64) -> Point {
+    pub fn position(&self, t: Float) -> Point {
         self.origin + self.direction * t
     }
 
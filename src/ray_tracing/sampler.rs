@@ -0,0 +1,452 @@
+use crate::{Color, Float};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+/// Random number generator used for stochastic sampling. It is portable, a
+/// given seed produces the same sequence on every platform.
+pub type SampleRng = Pcg32;
+
+/// Creates the generator for a single pixel. It only depends on the seed and
+/// the pixel coordinates so pixels can be rendered in any order and still
+/// produce identical images.
+pub fn pixel_rng(seed: u64, x: usize, y: usize) -> SampleRng {
+    let h = splitmix64(seed);
+    let h = splitmix64(h ^ x as u64);
+    let h = splitmix64(h ^ y as u64);
+    SampleRng::seed_from_u64(h)
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Where sub-pixel samples are placed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SamplePattern {
+    /// Stratified grid with a random offset inside every cell.
+    Jittered,
+    /// Halton (2, 3) low-discrepancy sequence, randomly rotated per pixel.
+    Halton,
+}
+
+/// Reconstruction filter used to weight samples around the pixel center.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+impl Filter {
+    /// Half width of the filter footprint in pixels.
+    pub fn radius(&self) -> Float {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    pub fn weight(&self, dx: Float, dy: Float) -> Float {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: Float) -> Float {
+        let x = x.abs();
+        let r = self.radius();
+        if x > r {
+            return 0.0;
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - x / r,
+            Filter::Gaussian => {
+                let alpha = 2.0;
+                (-alpha * x * x).exp() - (-alpha * r * r).exp()
+            }
+            Filter::Mitchell => {
+                // B = C = 1/3 as recommended by Mitchell and Netravali,
+                // the kernel is defined on [-2, 2]
+                let b = 1.0 / 3.0;
+                let c = 1.0 / 3.0;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                } else {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+/// Decides how many samples a pixel receives and how they are combined.
+///
+/// Samples are taken in batches of `min_samples`. After every batch the
+/// standard error of the mean luminance is compared with
+/// `variance_threshold` and sampling stops once it drops below it or
+/// `max_samples` is reached.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sampler {
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+    pub min_samples: usize,
+    pub max_samples: usize,
+    pub variance_threshold: Float,
+}
+
+impl Sampler {
+    pub fn new(pattern: SamplePattern, filter: Filter) -> Sampler {
+        Sampler {
+            pattern,
+            filter,
+            min_samples: 1,
+            max_samples: 1,
+            variance_threshold: 0.0,
+        }
+    }
+
+    /// One ray through the center of every pixel.
+    pub fn center() -> Sampler {
+        Sampler::new(SamplePattern::Jittered, Filter::Box)
+    }
+
+    /// Exactly `samples` stratified samples per pixel.
+    pub fn fixed(samples: usize) -> Sampler {
+        Sampler::new(SamplePattern::Jittered, Filter::Box).with_samples(samples, samples)
+    }
+
+    pub fn adaptive(min_samples: usize, max_samples: usize, variance_threshold: Float) -> Sampler {
+        Sampler::new(SamplePattern::Halton, Filter::Tent)
+            .with_samples(min_samples, max_samples)
+            .with_threshold(variance_threshold)
+    }
+
+    pub fn with_samples(mut self, min_samples: usize, max_samples: usize) -> Sampler {
+        self.min_samples = min_samples.max(1);
+        self.max_samples = max_samples.max(self.min_samples);
+        self
+    }
+
+    pub fn with_threshold(mut self, variance_threshold: Float) -> Sampler {
+        self.variance_threshold = variance_threshold;
+        self
+    }
+
+    pub fn with_pattern(mut self, pattern: SamplePattern) -> Sampler {
+        self.pattern = pattern;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Sampler {
+        self.filter = filter;
+        self
+    }
+
+    /// Estimates the color of one pixel. `radiance` receives the sample
+    /// offset from the pixel center in pixels and returns the color seen
+    /// through that point.
+    pub fn sample_pixel<R, F>(&self, rng: &mut R, mut radiance: F) -> Color
+    where
+        R: Rng + ?Sized,
+        F: FnMut(Float, Float) -> Color,
+    {
+        let [color] = self.sample_pixel_passes(rng, |dx, dy| [radiance(dx, dy)]);
+        color
+    }
+
+    /// Like `sample_pixel` but filters several colors per sample with the
+    /// same weights, e.g. the parts a color is made of. The first color
+    /// decides when adaptive sampling stops.
+    pub fn sample_pixel_passes<R, F, const N: usize>(
+        &self,
+        rng: &mut R,
+        mut radiance: F,
+    ) -> [Color; N]
+    where
+        R: Rng + ?Sized,
+        F: FnMut(Float, Float) -> [Color; N],
+    {
+        if self.max_samples <= 1 {
+            return radiance(0.0, 0.0);
+        }
+
+        let rotation = (rng.gen::<Float>(), rng.gen::<Float>());
+        let radius = self.filter.radius();
+
+        let mut sum = [Color::new(0.0, 0.0, 0.0); N];
+        let mut weight_sum = 0.0;
+        let mut plain_sum = [Color::new(0.0, 0.0, 0.0); N];
+
+        // running mean and variance of the luminance (Welford)
+        let mut count = 0;
+        let mut mean = 0.0;
+        let mut m2 = 0.0;
+
+        while count < self.max_samples {
+            let batch = self.min_samples.min(self.max_samples - count);
+            let positions = match self.pattern {
+                SamplePattern::Jittered => jittered(batch, rng),
+                SamplePattern::Halton => (count..count + batch)
+                    .map(|k| {
+                        (
+                            (halton(k + 1, 2) + rotation.0) % 1.0,
+                            (halton(k + 1, 3) + rotation.1) % 1.0,
+                        )
+                    })
+                    .collect(),
+            };
+            for (u, v) in positions {
+                let dx = (u * 2.0 - 1.0) * radius;
+                let dy = (v * 2.0 - 1.0) * radius;
+
+                let colors = radiance(dx, dy);
+                let weight = self.filter.weight(dx, dy);
+                for i in 0..N {
+                    sum[i] = sum[i] + colors[i] * weight;
+                    plain_sum[i] = plain_sum[i] + colors[i];
+                }
+                weight_sum += weight;
+
+                count += 1;
+                let luminance = colors.first().map_or(0.0, |c| c.luminance());
+                let delta = luminance - mean;
+                mean += delta / count as Float;
+                m2 += delta * (luminance - mean);
+            }
+
+            if count >= self.max_samples || count < 2 {
+                continue;
+            }
+            let variance = m2 / (count - 1) as Float;
+            if (variance / count as Float).sqrt() <= self.variance_threshold {
+                break;
+            }
+        }
+
+        if weight_sum > 0.0 {
+            sum.map(|c| c / weight_sum)
+        } else {
+            plain_sum.map(|c| c / count as Float)
+        }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::center()
+    }
+}
+
+// `n` stratified points in the unit square. A square `n` fills a grid, any
+// other `n` places one point in every row and every column of an n by n
+// grid (n-rooks) with the rows shuffled, so no part of the pixel is skipped.
+fn jittered<R: Rng + ?Sized>(n: usize, rng: &mut R) -> Vec<(Float, Float)> {
+    let cells = (n as Float).sqrt().round() as usize;
+    if cells * cells == n {
+        return (0..n)
+            .map(|i| {
+                (
+                    ((i % cells) as Float + rng.gen::<Float>()) / cells as Float,
+                    ((i / cells) as Float + rng.gen::<Float>()) / cells as Float,
+                )
+            })
+            .collect();
+    }
+
+    let mut rows: Vec<usize> = (0..n).collect();
+    rows.shuffle(rng);
+    rows.into_iter()
+        .enumerate()
+        .map(|(column, row)| {
+            (
+                (column as Float + rng.gen::<Float>()) / n as Float,
+                (row as Float + rng.gen::<Float>()) / n as Float,
+            )
+        })
+        .collect()
+}
+
+/// Radical inverse of `index` in the given base.
+pub fn halton(mut index: usize, base: usize) -> Float {
+    let mut result = 0.0;
+    let mut f = 1.0 / base as Float;
+    while index > 0 {
+        result += f * (index % base) as Float;
+        index /= base;
+        f /= base as Float;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{float_eq, ray_tracing::color};
+
+    #[test]
+    fn halton_sequence_base_2_and_3() {
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(2, 2), 0.25);
+        assert_eq!(halton(3, 2), 0.75);
+        assert!(float_eq(halton(1, 3), 1.0 / 3.0));
+        assert!(float_eq(halton(2, 3), 2.0 / 3.0));
+        assert!(float_eq(halton(3, 3), 1.0 / 9.0));
+    }
+
+    #[test]
+    fn filters_peak_at_the_pixel_center() {
+        for filter in &[
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+        ] {
+            let center = filter.weight(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.weight(0.25, 0.25) <= center);
+            assert_eq!(filter.weight(filter.radius() + 0.1, 0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn mitchell_filter_has_negative_lobes() {
+        assert!(Filter::Mitchell.weight(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn a_single_sample_goes_through_the_pixel_center() {
+        let mut offsets = Vec::new();
+        let c = Sampler::center().sample_pixel(&mut pixel_rng(1, 2, 3), |dx, dy| {
+            offsets.push((dx, dy));
+            color::WHITE
+        });
+
+        assert_eq!(c, color::WHITE);
+        assert_eq!(offsets, vec![(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn jittered_samples_cover_every_stratum() {
+        let mut offsets = Vec::new();
+        Sampler::fixed(16).sample_pixel(&mut pixel_rng(1, 2, 3), |dx, dy| {
+            offsets.push((dx, dy));
+            color::BLACK
+        });
+
+        assert_eq!(offsets.len(), 16);
+        for cy in 0..4 {
+            for cx in 0..4 {
+                let lo_x = -0.5 + cx as Float * 0.25;
+                let lo_y = -0.5 + cy as Float * 0.25;
+                assert!(offsets.iter().any(|&(dx, dy)| dx >= lo_x
+                    && dx < lo_x + 0.25
+                    && dy >= lo_y
+                    && dy < lo_y + 0.25));
+            }
+        }
+    }
+
+    fn offsets(samples: usize) -> Vec<(Float, Float)> {
+        let mut offsets = Vec::new();
+        Sampler::fixed(samples).sample_pixel(&mut pixel_rng(4, 5, 6), |dx, dy| {
+            offsets.push((dx, dy));
+            color::BLACK
+        });
+        offsets
+    }
+
+    fn covers_every_band(offsets: &[(Float, Float)]) -> bool {
+        let n = offsets.len();
+        let band = |v: Float| ((v + 0.5) * n as Float) as usize;
+        (0..n).all(|b| offsets.iter().any(|&(dx, _)| band(dx) == b))
+            && (0..n).all(|b| offsets.iter().any(|&(_, dy)| band(dy) == b))
+    }
+
+    #[test]
+    fn two_samples_cover_both_halves_of_the_pixel() {
+        let offsets = offsets(2);
+        assert_eq!(offsets.len(), 2);
+        assert!(offsets.iter().any(|&(_, dy)| dy < 0.0));
+        assert!(offsets.iter().any(|&(_, dy)| dy >= 0.0));
+        assert!(covers_every_band(&offsets));
+    }
+
+    #[test]
+    fn non_square_sample_counts_cover_every_row_and_column() {
+        for n in &[2, 3, 5, 7, 12] {
+            assert!(covers_every_band(&offsets(*n)), "{} samples", n);
+        }
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_early_on_flat_pixels() {
+        let sampler = Sampler::adaptive(4, 64, 0.01);
+        let mut count = 0;
+        let c = sampler.sample_pixel(&mut pixel_rng(1, 2, 3), |_, _| {
+            count += 1;
+            Color::new(0.5, 0.5, 0.5)
+        });
+
+        assert_eq!(count, 4);
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn adaptive_sampling_spends_the_budget_on_edges() {
+        let sampler = Sampler::adaptive(4, 64, 0.01);
+        let mut count = 0;
+        sampler.sample_pixel(&mut pixel_rng(1, 2, 3), |dx, _| {
+            count += 1;
+            if dx < 0.0 {
+                color::WHITE
+            } else {
+                color::BLACK
+            }
+        });
+
+        assert_eq!(count, 64);
+    }
+
+    #[test]
+    fn box_filtered_edge_averages_both_sides() {
+        let sampler = Sampler::fixed(64).with_pattern(SamplePattern::Halton);
+        let c = sampler.sample_pixel(&mut pixel_rng(1, 2, 3), |dx, _| {
+            if dx < 0.0 {
+                color::WHITE
+            } else {
+                color::BLACK
+            }
+        });
+
+        assert!((c.red - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn pixel_rng_is_repeatable() {
+        let mut a = pixel_rng(7, 3, 5);
+        let mut b = pixel_rng(7, 3, 5);
+        for _ in 0..4 {
+            assert_eq!(a.gen::<u32>(), b.gen::<u32>());
+        }
+    }
+
+    #[test]
+    fn pixel_rng_differs_between_pixels_and_seeds() {
+        let first = pixel_rng(7, 3, 5).gen::<u64>();
+        assert_ne!(first, pixel_rng(7, 5, 3).gen::<u64>());
+        assert_ne!(first, pixel_rng(8, 3, 5).gen::<u64>());
+    }
+}
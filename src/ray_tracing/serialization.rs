@@ -0,0 +1,344 @@
+//! Scene files in JSON and a compact binary format (bincode).
+//!
+//! `World`, `Camera`, `Material`, `PointLight`, `Matrix`, every shape and
+//! every pattern can be saved and loaded. Boxed shapes and patterns are
+//! written with the name of their type, and `typetag` keeps a registry of
+//! those names. A custom shape or pattern joins the registry by deriving
+//! serde's traits and marking its impl, with `typetag` as a dependency:
+//!
+//! ```ignore
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Torus { /* ... */ }
+//!
+//! #[typetag::serde]
+//! impl Shape for Torus { /* ... */ }
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SerializationError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Json(e) => write!(f, "invalid JSON scene: {}", e),
+            SerializationError::Binary(e) => write!(f, "invalid binary scene: {}", e),
+        }
+    }
+}
+
+impl Error for SerializationError {}
+
+impl From<serde_json::Error> for SerializationError {
+    fn from(e: serde_json::Error) -> Self {
+        SerializationError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SerializationError {
+    fn from(e: bincode::Error) -> Self {
+        SerializationError::Binary(e)
+    }
+}
+
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String, SerializationError> {
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, SerializationError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+pub fn to_binary<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerializationError> {
+    Ok(bincode::serialize(value)?)
+}
+
+pub fn from_binary<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SerializationError> {
+    Ok(bincode::deserialize(bytes)?)
+}
+
+/// For `#[serde(with = ...)]` on floats that may be infinite, like the
+/// bounds of cones and cylinders. JSON has no infinity, so non-finite values
+/// are written as the strings `"inf"`, `"-inf"` and `"NaN"` there.
+pub(crate) mod non_finite {
+    use crate::Float;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        Number(Float),
+        Name(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Float, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() || !serializer.is_human_readable() {
+            value.serialize(serializer)
+        } else {
+            value.to_string().serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Float, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Float::deserialize(deserializer);
+        }
+        match Text::deserialize(deserializer)? {
+            Text::Number(value) => Ok(value),
+            Text::Name(name) => name
+                .parse()
+                .map_err(|_| D::Error::custom(format!("invalid number {:?}", name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::patterns::{Checkers, Pattern};
+    use crate::ray_tracing::color::{BLACK, RED, WHITE};
+    use crate::shapes::{Cone, Cube, Cylinder, Group, Metadata, ParentKey, Plane, Shape, Sphere};
+    use crate::{
+        Camera, Color, Float, Intersection, Material, Matrix, Normal, Point, PointLight, Ray,
+        Transform, Vector, World,
+    };
+    use serde::Deserialize;
+    use std::any::Any;
+    use uuid::Uuid;
+
+    fn scene() -> World {
+        let mut world = World::default();
+        let mut group = Group::new();
+        group.transform = Transform::new().translation(0.0, 0.5, 2.0).build();
+        let mut sphere = Sphere::new();
+        sphere.material.pattern = Some(Box::new(Checkers::new(RED, WHITE)));
+        sphere.material.reflective = 0.3;
+        group.add_shape(Box::new(sphere)).unwrap();
+        let mut cylinder = Cylinder::new();
+        cylinder.closed = true;
+        cylinder.maximum = 1.0;
+        group.add_shape(Box::new(cylinder)).unwrap();
+        world.add_shape(Box::new(group)).unwrap();
+        world.add_shape(Box::new(Cone::new())).unwrap();
+        world.add_shape(Box::new(Cube::new())).unwrap();
+        let mut floor = Plane::new();
+        floor.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
+        world.add_shape(Box::new(floor)).unwrap();
+        world
+    }
+
+    fn assert_same_render(a: &World, b: &World) {
+        let camera = Camera::builder(8, 6)
+            .look_at(
+                Point::new(0.0, 1.5, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 3.0)
+            .build();
+        for y in 0..camera.vsize() {
+            for x in 0..camera.hsize() {
+                let r = camera.ray_for_pixel(x as Float, y as Float);
+                assert_eq!(a.color_at(r, 5), b.color_at(r, 5));
+            }
+        }
+    }
+
+    #[test]
+    fn a_world_round_trips_through_json() {
+        let world = scene();
+        let json = to_json(&world).unwrap();
+        assert!(json.contains("\"Checkers\""));
+        let loaded: World = from_json(&json).unwrap();
+        assert_same_render(&world, &loaded);
+    }
+
+    #[test]
+    fn a_world_round_trips_through_binary() {
+        let world = scene();
+        let bytes = to_binary(&world).unwrap();
+        let loaded: World = from_binary(&bytes).unwrap();
+        assert_same_render(&world, &loaded);
+    }
+
+    #[test]
+    fn a_camera_round_trips() {
+        let camera = Camera::builder(201, 101)
+            .look_at(
+                Point::new(1.0, 2.0, -4.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .seed(7)
+            .build();
+        let loaded: Camera = from_json(&to_json(&camera).unwrap()).unwrap();
+        assert_eq!(loaded.seed(), 7);
+        assert_eq!(loaded.projection(), camera.projection());
+        let binary: Camera = from_binary(&to_binary(&camera).unwrap()).unwrap();
+        let (a, b) = (
+            binary.ray_for_pixel(100.0, 50.0),
+            camera.ray_for_pixel(100.0, 50.0),
+        );
+        assert_eq!(a.origin, b.origin);
+        assert_eq!(a.direction, b.direction);
+    }
+
+    #[test]
+    fn camera_sizes_are_recomputed_when_loaded() {
+        let camera = Camera::new(200, 125, PI / 2.0);
+        let json = to_json(&camera).unwrap();
+        assert!(!json.contains("pixel_size"));
+        assert!(!json.contains("half_width"));
+
+        let edited = json.replace("200", "400");
+        let loaded: Camera = from_json(&edited).unwrap();
+        assert_eq!(loaded.hsize(), 400);
+        assert!(crate::float_eq(loaded.pixel_size(), 0.005));
+    }
+
+    #[test]
+    fn materials_lights_and_matrices_round_trip() {
+        let mut material = Material::new();
+        material.pattern = Some(Box::new(Checkers::new(WHITE, BLACK)));
+        material.transparency = 0.5;
+        let loaded: Material = from_json(&to_json(&material).unwrap()).unwrap();
+        assert_eq!(loaded, material);
+
+        let light = PointLight::new(Point::new(-10.0, 10.0, -10.0), WHITE);
+        let loaded: PointLight = from_binary(&to_binary(&light).unwrap()).unwrap();
+        assert_eq!(loaded, light);
+
+        let m = Transform::new()
+            .rotation_y(PI / 3.0)
+            .scaling(2.0, 1.0, 1.0)
+            .build();
+        let loaded: Matrix = from_json(&to_json(&m).unwrap()).unwrap();
+        assert_eq!(loaded, m);
+        assert_eq!(loaded.inverse(), m.inverse());
+    }
+
+    #[test]
+    fn infinite_bounds_are_written_as_strings_in_json() {
+        let cylinder: Box<dyn Shape> = Box::new(Cylinder::new());
+        let json = to_json(&cylinder).unwrap();
+        assert!(json.contains("\"-inf\""));
+        let loaded: Box<dyn Shape> = from_json(&json).unwrap();
+        let loaded = loaded.as_any().downcast_ref::<Cylinder>().unwrap();
+        assert_eq!(loaded.minimum, Float::NEG_INFINITY);
+        assert_eq!(loaded.maximum, Float::INFINITY);
+    }
+
+    #[test]
+    fn an_unknown_shape_type_is_an_error() {
+        let json = r#"{ "lights": [], "shapes": { "shapes": [{ "Teapot": {} }] } }"#;
+        match from_json::<World>(json) {
+            Err(SerializationError::Json(e)) => assert!(e.to_string().contains("Teapot")),
+            other => panic!("expected a JSON error, got {:?}", other),
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Marker {
+        id: Uuid,
+        parent_id: Option<Uuid>,
+        transform: Matrix,
+        material: Material,
+        metadata: Metadata,
+    }
+
+    #[typetag::serde]
+    impl Shape for Marker {
+        fn id(&self) -> Uuid {
+            self.id
+        }
+
+        fn parent_id(&self) -> Option<Uuid> {
+            self.parent_id
+        }
+
+        fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+            self.parent_id = id;
+        }
+
+        fn metadata(&self) -> &Metadata {
+            &self.metadata
+        }
+
+        fn metadata_mut(&mut self) -> &mut Metadata {
+            &mut self.metadata
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn shape_eq(&self, other: &dyn Shape) -> bool {
+            self.id == other.id()
+        }
+
+        fn transform(&self) -> Matrix {
+            self.transform
+        }
+
+        fn set_transform(&mut self, transform: Matrix) {
+            self.transform = transform;
+        }
+
+        fn material(&self) -> &Material {
+            &self.material
+        }
+
+        fn material_mut(&mut self) -> &mut Material {
+            &mut self.material
+        }
+
+        fn set_material(&mut self, material: Material) {
+            self.material = material;
+        }
+
+        fn local_intersect(&self, _ray: Ray) -> Option<Vec<Intersection<'_>>> {
+            None
+        }
+
+        fn local_normal_at(&self, point: Point) -> Normal {
+            Normal::from(point - Point::new(0.0, 0.0, 0.0))
+        }
+    }
+
+    #[test]
+    fn custom_shapes_join_the_registry() {
+        let id = Uuid::new_v4();
+        let mut marker: Box<dyn Shape> = Box::new(Marker {
+            id,
+            parent_id: None,
+            transform: Transform::new().translation(1.0, 2.0, 3.0).build(),
+            material: Material::new(),
+            metadata: Metadata::new(),
+        });
+        marker.set_name("marker").unwrap();
+        let json = to_json(&marker).unwrap();
+        assert!(json.contains("\"Marker\""));
+        let loaded: Box<dyn Shape> = from_json(&json).unwrap();
+        assert_eq!(loaded.id(), id);
+        assert_eq!(loaded.name(), Some("marker"));
+        assert!(loaded.as_any().downcast_ref::<Marker>().is_some());
+        let loaded: Box<dyn Shape> = from_binary(&to_binary(&marker).unwrap()).unwrap();
+        assert_eq!(loaded.transform(), marker.transform());
+    }
+
+    #[test]
+    fn boxed_patterns_round_trip() {
+        let pattern: Box<dyn Pattern> = Box::new(Checkers::new(WHITE, Color::new(0.0, 0.0, 1.0)));
+        let loaded: Box<dyn Pattern> = from_binary(&to_binary(&pattern).unwrap()).unwrap();
+        assert!(loaded == pattern);
+    }
+}
@@ -1,7 +1,12 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::iter;
+use std::mem;
 use uuid::Uuid;
 
-use crate::shapes::Shape;
+use crate::ray_tracing::matrix::IDENTITY;
+use crate::shapes::{ParentKey, Shape};
+use crate::Matrix;
 
 #[derive(Debug)]
 pub struct ShapeRelationship {
@@ -10,6 +15,7 @@ pub struct ShapeRelationship {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShapeContainer {
     pub shapes: Vec<Box<dyn Shape>>,
 }
@@ -39,6 +45,10 @@ impl ShapeContainer {
         self.shapes.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
     pub fn get_shape(&self, id: Uuid) -> Option<&dyn Shape> {
         let mut shape = None;
         for s in &self.shapes {
@@ -46,26 +56,324 @@ impl ShapeContainer {
                 shape = Some(s.as_ref());
                 break;
             }
-            match s.get_child(id) {
-                Some(c) => {
-                    shape = Some(c);
-                    break;
-                }
-                None => (),
+            if let Some(c) = s.get_child(id) {
+                shape = Some(c);
+                break;
             }
         }
 
         shape
     }
+
+    /// Finds a shape by id, searching inside groups.
+    pub fn get_shape_mut(&mut self, id: Uuid) -> Option<&mut dyn Shape> {
+        find_slot(&mut self.shapes, id).map(|s| s.as_mut())
+    }
+
+    /// Takes the shape, and everything in it, out of the hierarchy.
+    pub fn remove_shape(&mut self, id: Uuid) -> Option<Box<dyn Shape>> {
+        let mut shape = remove(&mut self.shapes, id)?;
+        shape.set_parent_id(None, ParentKey(()));
+        Some(shape)
+    }
+
+    /// Puts `shape` in the place of the shape with `id`, under the same
+    /// parent, and returns the shape it replaced. Refused when `shape`, or
+    /// a shape inside it, has the id of a shape that stays in the world.
+    pub fn replace_shape(
+        &mut self,
+        id: Uuid,
+        mut shape: Box<dyn Shape>,
+    ) -> Result<Box<dyn Shape>, HierarchyError> {
+        self.get_shape(id).ok_or(HierarchyError::NotFound(id))?;
+        if let Some(new) = self.duplicate_id(shape.as_ref(), Some(id)) {
+            return Err(HierarchyError::DuplicateId(new));
+        }
+
+        let slot = find_slot(&mut self.shapes, id).ok_or(HierarchyError::NotFound(id))?;
+        shape.set_parent_id(slot.parent_id(), ParentKey(()));
+        Ok(mem::replace(slot, shape))
+    }
+
+    /// The first id in `shape`, or in a shape inside it, that is used twice
+    /// in it or is already in the container. Shapes inside `replacing` do
+    /// not count, they leave when `shape` comes in.
+    pub(crate) fn duplicate_id(&self, shape: &dyn Shape, replacing: Option<Uuid>) -> Option<Uuid> {
+        let mut replaced = Vec::new();
+        if let Some(old) = replacing.and_then(|id| self.get_shape(id)) {
+            subtree_ids(old, &mut replaced);
+        }
+        let mut added = Vec::new();
+        subtree_ids(shape, &mut added);
+        added.iter().enumerate().find_map(|(i, new)| {
+            let taken = !replaced.contains(new) && self.get_shape(*new).is_some();
+            if taken || added[..i].contains(new) {
+                Some(*new)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Moves a shape into the group `parent`, or to the top level when
+    /// `parent` is `None`. The shape keeps its transform, so it moves with
+    /// its new parent. Refused when more than one shape has the id, as it
+    /// is not clear which one to move.
+    pub fn reparent(&mut self, id: Uuid, parent: Option<Uuid>) -> Result<(), HierarchyError> {
+        let shape = self.get_shape(id).ok_or(HierarchyError::NotFound(id))?;
+        let mut ids = Vec::new();
+        for s in &self.shapes {
+            subtree_ids(s.as_ref(), &mut ids);
+        }
+        if ids.iter().filter(|i| **i == id).count() > 1 {
+            return Err(HierarchyError::DuplicateId(id));
+        }
+        if let Some(parent) = parent {
+            if parent == id || shape.get_child(parent).is_some() {
+                return Err(HierarchyError::Cycle(parent));
+            }
+            let target = self
+                .get_shape_mut(parent)
+                .ok_or(HierarchyError::NotFound(parent))?;
+            if target.as_group_mut().is_none() {
+                return Err(HierarchyError::NotAGroup(parent));
+            }
+        }
+
+        let mut shape = remove(&mut self.shapes, id).ok_or(HierarchyError::NotFound(id))?;
+        shape.set_parent_id(None, ParentKey(()));
+        match parent.and_then(|p| self.get_shape_mut(p)) {
+            Some(p) => p
+                .as_group_mut()
+                .expect("parent is a group")
+                .add_shape(shape),
+            None => {
+                self.shapes.push(shape);
+                Ok(())
+            }
+        }
+    }
+
+    /// First shape with this name, depth first.
+    pub fn get_shape_by_name(&self, name: &str) -> Option<&dyn Shape> {
+        self.walk()
+            .into_iter()
+            .find(|(_, s)| s.name() == Some(name))
+            .map(|(_, s)| s)
+    }
+
+    /// Follows names from the top level down, e.g. `"room/table/leg1"`.
+    /// Unnamed shapes are addressed by their id. Siblings may share a name,
+    /// the path then leads to the first of them, `find_shapes` finds all.
+    pub fn get_shape_by_path(&self, path: &str) -> Option<&dyn Shape> {
+        let mut shapes = self.shapes.as_slice();
+        let mut found = None;
+        for name in path.split('/') {
+            let shape = shapes.iter().find(|s| segment(s.as_ref()) == name)?;
+            shapes = shape.children();
+            found = Some(shape.as_ref());
+        }
+        found
+    }
+
+    pub fn path_of(&self, id: Uuid) -> Option<String> {
+        self.walk()
+            .into_iter()
+            .find(|(_, s)| s.id() == id)
+            .map(|(path, _)| path.join("/"))
+    }
+
+    /// Shapes whose path matches a glob pattern. `*` matches any part of a
+    /// name, `?` one character and `**` any number of levels, so
+    /// `"room/**/leg*"` finds every leg in the room.
+    pub fn find_shapes(&self, pattern: &str) -> Vec<&dyn Shape> {
+        let pattern: Vec<&str> = pattern.split('/').collect();
+        self.walk()
+            .into_iter()
+            .filter(|(path, _)| glob_match(&pattern, path))
+            .map(|(_, s)| s)
+            .collect()
+    }
+
+    pub fn shapes_tagged(&self, tag: &str) -> Vec<&dyn Shape> {
+        self.walk()
+            .into_iter()
+            .filter(|(_, s)| s.has_tag(tag))
+            .map(|(_, s)| s)
+            .collect()
+    }
+
+    fn walk(&self) -> Vec<(Vec<String>, &dyn Shape)> {
+        let mut all = Vec::new();
+        walk(&self.shapes, &[], &mut all);
+        all
+    }
+
+    /// Every shape, depth first, with the transform from its object space
+    /// to world space.
+    pub fn all_with_transforms(&self) -> impl Iterator<Item = (&dyn Shape, Matrix)> + '_ {
+        let mut stack: Vec<(&[Box<dyn Shape>], Matrix)> = vec![(&self.shapes, IDENTITY)];
+        iter::from_fn(move || loop {
+            let (shapes, parent) = stack.last_mut()?;
+            match shapes.split_first() {
+                Some((s, rest)) => {
+                    let transform = *parent * s.transform();
+                    *shapes = rest;
+                    stack.push((s.children(), transform));
+                    return Some((s.as_ref(), transform));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        })
+    }
+}
+
+fn find_slot(shapes: &mut [Box<dyn Shape>], id: Uuid) -> Option<&mut Box<dyn Shape>> {
+    for s in shapes.iter_mut() {
+        if s.id() == id {
+            return Some(s);
+        }
+        if let Some(found) = find_slot(s.children_mut(), id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn remove(shapes: &mut Vec<Box<dyn Shape>>, id: Uuid) -> Option<Box<dyn Shape>> {
+    if let Some(i) = shapes.iter().position(|s| s.id() == id) {
+        return Some(shapes.remove(i));
+    }
+    shapes.iter_mut().find_map(|s| remove_child(s.as_mut(), id))
+}
+
+fn remove_child(shape: &mut dyn Shape, id: Uuid) -> Option<Box<dyn Shape>> {
+    let group = shape.as_group_mut()?;
+    group.remove_shape(id).or_else(|| {
+        group
+            .children_mut()
+            .iter_mut()
+            .find_map(|c| remove_child(c.as_mut(), id))
+    })
+}
+
+fn subtree_ids(shape: &dyn Shape, ids: &mut Vec<Uuid>) {
+    ids.push(shape.id());
+    for c in shape.children() {
+        subtree_ids(c.as_ref(), ids);
+    }
+}
+
+fn segment(shape: &dyn Shape) -> String {
+    match shape.name() {
+        Some(name) => name.to_string(),
+        None => shape.id().to_string(),
+    }
+}
+
+fn walk<'a>(
+    shapes: &'a [Box<dyn Shape>],
+    parent: &[String],
+    all: &mut Vec<(Vec<String>, &'a dyn Shape)>,
+) {
+    for s in shapes {
+        let mut path = parent.to_vec();
+        path.push(segment(s.as_ref()));
+        all.push((path.clone(), s.as_ref()));
+        walk(s.children(), &path, all);
+    }
+}
+
+fn glob_match(pattern: &[&str], path: &[String]) -> bool {
+    wildcard_match(pattern, path, |p| *p == "**", |p, name| name_match(p, name))
+}
+
+fn name_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    wildcard_match(&pattern, &name, |c| *c == '*', |c, n| *c == '?' || c == n)
+}
+
+/// Matches `text` against a pattern where `is_star` items match any run of
+/// items and every other item matches one. Iterative with two pointers: on a
+/// mismatch the last star takes one more item and matching resumes after
+/// it, so long inputs need no stack and no backtracking over earlier stars.
+fn wildcard_match<P, T>(
+    pattern: &[P],
+    text: &[T],
+    is_star: impl Fn(&P) -> bool,
+    matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // pattern index of the last star and the text index it matched up to
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && is_star(&pattern[p]) {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && matches(&pattern[p], &text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(is_star)
+}
+
+/// Why a change to the shape hierarchy was refused.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HierarchyError {
+    /// No shape has this id.
+    NotFound(Uuid),
+    /// The shape can not have children.
+    NotAGroup(Uuid),
+    /// The new parent is the shape itself or inside it.
+    Cycle(Uuid),
+    /// The shape already belongs to another group, or is already a child.
+    AlreadyParented(Uuid),
+    /// Another shape in the world has this id.
+    DuplicateId(Uuid),
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HierarchyError::NotFound(id) => write!(f, "no shape with id {}", id),
+            HierarchyError::NotAGroup(id) => write!(f, "shape {} is not a group", id),
+            HierarchyError::Cycle(id) => {
+                write!(f, "shape {} is inside the shape being moved", id)
+            }
+            HierarchyError::AlreadyParented(id) => {
+                write!(f, "shape {} already has a parent", id)
+            }
+            HierarchyError::DuplicateId(id) => write!(f, "more than one shape has id {}", id),
+        }
+    }
+}
+
+impl Error for HierarchyError {}
+
+impl Default for ShapeContainer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::f64::consts::PI;
+    use crate::consts::PI;
+    use crate::Float;
 
     use crate::{
         shapes::{Group, Sphere},
-        Point, Transform, Vector,
+        Normal, Point, Transform,
     };
 
     use super::*;
@@ -81,15 +389,13 @@ mod tests {
 
         let mut g2 = Group::new();
         g2.transform = Transform::new().scaling(2.0, 2.0, 2.0).build();
-        g2.parent_id = Some(g1.id);
 
         let mut s = Sphere::new();
         s.transform = Transform::new().translation(5.0, 0.0, 0.0).build();
-        s.parent_id = Some(g2.id);
         let s_id = s.id;
 
-        g2.add_shape(Box::new(s));
-        g1.add_shape(Box::new(g2));
+        g2.add_shape(Box::new(s)).unwrap();
+        g1.add_shape(Box::new(g2)).unwrap();
         go.add_shape(Box::new(g1));
 
         let s = go.get_shape(s_id).unwrap();
@@ -106,23 +412,67 @@ mod tests {
 
         let mut g2 = Group::new();
         g2.transform = Transform::new().scaling(1.0, 2.0, 3.0).build();
-        g2.parent_id = Some(g1.id);
 
         let mut s = Sphere::new();
         s.transform = Transform::new().translation(5.0, 0.0, 0.0).build();
-        s.parent_id = Some(g2.id);
         let s_id = s.id;
 
         let mut go = ShapeContainer::new();
 
-        g2.add_shape(Box::new(s));
-        g1.add_shape(Box::new(g2));
+        g2.add_shape(Box::new(s)).unwrap();
+        g1.add_shape(Box::new(g2)).unwrap();
         go.add_shape(Box::new(g1));
 
         let p = go.get_shape(s_id).unwrap().normal_to_world(
-            Vector::new(3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0),
+            Normal::new(
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+            ),
             &go,
         );
-        assert_eq!(p, Vector::new(0.2857, 0.4286, -0.8571));
+        assert_eq!(p, Normal::new(0.2857, 0.4286, -0.8571));
+    }
+
+    #[test]
+    fn a_missing_parent_is_treated_as_the_world() {
+        let mut s = Sphere::new();
+        s.transform = Transform::new().scaling(1.0, 2.0, 3.0).build();
+        s.parent_id = Some(Uuid::new_v4());
+        let sc = ShapeContainer::new();
+        let n = Normal::new(0.0, 1.0, 0.0);
+        assert_eq!(s.normal_to_world(n, &sc), (s.transform * n).normalize());
+        let p = Point::new(1.0, 2.0, 3.0);
+        assert_eq!(s.world_to_object(p, &sc), s.transform.inverse() * p);
+    }
+
+    #[test]
+    fn wildcards_match_names() {
+        assert!(name_match("leg?", "leg1"));
+        assert!(name_match("*", ""));
+        assert!(name_match("l*g*", "leg1"));
+        assert!(name_match("*a*b", "xaxxab"));
+        assert!(!name_match("leg?", "leg"));
+        assert!(!name_match("*a*b", "xaxxa"));
+    }
+
+    #[test]
+    fn globs_match_paths() {
+        let path: Vec<String> = vec!["room".into(), "table".into(), "leg1".into()];
+        assert!(glob_match(&["**"], &path));
+        assert!(glob_match(&["room", "**", "leg?"], &path));
+        assert!(glob_match(&["**", "table", "**"], &path));
+        assert!(!glob_match(&["room", "*"], &path));
+        assert!(!glob_match(&["**", "lamp"], &path));
+    }
+
+    #[test]
+    fn long_patterns_do_not_blow_up() {
+        let name = "a".repeat(10_000);
+        assert!(!name_match(&format!("{}b", "*a".repeat(100)), &name));
+        let path: Vec<String> = vec!["a".into(); 1_000];
+        let mut pattern = vec!["**"; 100];
+        pattern.push("b");
+        assert!(!glob_match(&pattern, &path));
     }
 }
@@ -2,6 +2,7 @@ pub mod cone;
 pub mod cube;
 pub mod cylinder;
 pub mod group;
+pub mod metadata;
 pub mod plane;
 pub mod shape;
 pub mod sphere;
@@ -11,7 +12,8 @@ pub use cone::Cone;
 pub use cube::Cube;
 pub use cylinder::Cylinder;
 pub use group::Group;
+pub use metadata::{InvalidName, Metadata};
 pub use plane::Plane;
-pub use shape::Shape;
+pub use shape::{ParentKey, Shape};
 pub use sphere::Sphere;
 pub use test_shape::TestShape;
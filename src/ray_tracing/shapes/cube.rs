@@ -2,17 +2,21 @@ use std::any::Any;
 
 use uuid::Uuid;
 
-use super::Shape;
+use super::{Metadata, ParentKey, Shape};
 use crate::{
-    float_cmp, ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector,
+    float_cmp, ray_tracing::matrix::IDENTITY, Float, Intersection, Material, Matrix, Normal, Point,
+    Ray,
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cube {
     id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
 }
 
 impl Cube {
@@ -22,10 +26,11 @@ impl Cube {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
+            metadata: Metadata::new(),
         }
     }
 
-    fn check_axis(&self, origin: f64, direction: f64) -> (f64, f64) {
+    fn check_axis(&self, origin: Float, direction: Float) -> (Float, Float) {
         let tmin_numerator = -1.0 - origin;
         let tmax_numerator = 1.0 - origin;
 
@@ -40,6 +45,13 @@ impl Cube {
     }
 }
 
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for Cube {
     fn id(&self) -> Uuid {
         self.id
@@ -49,8 +61,16 @@ impl Shape for Cube {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -81,7 +101,7 @@ impl Shape for Cube {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         let (xtmin, xtmax) = self.check_axis(ray.origin.x, ray.direction.x);
         let (ytmin, ytmax) = self.check_axis(ray.origin.y, ray.direction.y);
         let (ztmin, ztmax) = self.check_axis(ray.origin.z, ray.direction.z);
@@ -104,18 +124,18 @@ impl Shape for Cube {
         }
     }
 
-    fn local_normal_at(&self, point: Point) -> Vector {
+    fn local_normal_at(&self, point: Point) -> Normal {
         let max_values = [point.x.abs(), point.y.abs(), point.z.abs()];
         let maxc = max_values.iter().max_by(|x, y| float_cmp(**x, **y));
 
         let maxc = *maxc.unwrap();
 
         if maxc == point.x.abs() {
-            Vector::new(point.x, 0.0, 0.0)
+            Normal::new(point.x, 0.0, 0.0)
         } else if maxc == point.y.abs() {
-            Vector::new(0.0, point.y, 0.0)
+            Normal::new(0.0, point.y, 0.0)
         } else {
-            Vector::new(0.0, 0.0, point.z)
+            Normal::new(0.0, 0.0, point.z)
         }
     }
 }
@@ -129,6 +149,7 @@ impl PartialEq for Cube {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Vector;
 
     // Chapter 12 Cubes
     // Page 168
@@ -223,14 +244,14 @@ mod tests {
     fn the_normal_on_the_surface_of_a_cube() {
         let c = Cube::new();
         let data = vec![
-            (Point::new(1.0, 0.5, -0.8), Vector::new(1.0, 0.0, 0.0)),
-            (Point::new(-1.0, -0.2, 0.9), Vector::new(-1.0, 0.0, 0.0)),
-            (Point::new(-0.4, 1.0, -0.1), Vector::new(0.0, 1.0, 0.0)),
-            (Point::new(0.3, -1.0, -0.7), Vector::new(0.0, -1.0, 0.0)),
-            (Point::new(-0.6, 0.3, 1.0), Vector::new(0.0, 0.0, 1.0)),
-            (Point::new(0.4, 0.4, -1.0), Vector::new(0.0, 0.0, -1.0)),
-            (Point::new(1.0, 1.0, 1.0), Vector::new(1.0, 0.0, 0.0)),
-            (Point::new(-1.0, -1.0, -1.0), Vector::new(-1.0, 0.0, 0.0)),
+            (Point::new(1.0, 0.5, -0.8), Normal::new(1.0, 0.0, 0.0)),
+            (Point::new(-1.0, -0.2, 0.9), Normal::new(-1.0, 0.0, 0.0)),
+            (Point::new(-0.4, 1.0, -0.1), Normal::new(0.0, 1.0, 0.0)),
+            (Point::new(0.3, -1.0, -0.7), Normal::new(0.0, -1.0, 0.0)),
+            (Point::new(-0.6, 0.3, 1.0), Normal::new(0.0, 0.0, 1.0)),
+            (Point::new(0.4, 0.4, -1.0), Normal::new(0.0, 0.0, -1.0)),
+            (Point::new(1.0, 1.0, 1.0), Normal::new(1.0, 0.0, 0.0)),
+            (Point::new(-1.0, -1.0, -1.0), Normal::new(-1.0, 0.0, 0.0)),
         ];
         for rec in data {
             let p = rec.0;
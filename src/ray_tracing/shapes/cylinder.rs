@@ -1,23 +1,31 @@
 use uuid::Uuid;
 
-use super::Shape;
+use super::{Metadata, ParentKey, Shape};
 use crate::{
-    float_eq, ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector,
-    EPSILON,
-};
-use std::{
-    any::Any,
-    f64::{INFINITY, NEG_INFINITY},
+    float_eq, ray_tracing::matrix::IDENTITY, Float, Intersection, Material, Matrix, Normal, Point,
+    Ray, EPSILON,
 };
+use std::any::Any;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cylinder {
     pub id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
-    pub maximum: f64,
-    pub minimum: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::ray_tracing::serialization::non_finite")
+    )]
+    pub maximum: Float,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::ray_tracing::serialization::non_finite")
+    )]
+    pub minimum: Float,
     pub closed: bool,
 }
 
@@ -28,20 +36,27 @@ impl Cylinder {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
-            minimum: NEG_INFINITY,
-            maximum: INFINITY,
+            metadata: Metadata::new(),
+            minimum: Float::NEG_INFINITY,
+            maximum: Float::INFINITY,
             closed: false,
         }
     }
 
-    fn check_cap(&self, ray: Ray, t: f64) -> bool {
+    fn check_cap(&self, ray: Ray, t: Float) -> bool {
         let x = ray.origin.x + t * ray.direction.x;
         let z = ray.origin.z + t * ray.direction.z;
 
-        x.powi(2) + z.powi(2) <= 1.0
+        // with f32 tolerate rounding for rays through the rim
+        let rim = if cfg!(feature = "f32") {
+            4.0 * Float::EPSILON
+        } else {
+            0.0
+        };
+        x.powi(2) + z.powi(2) <= 1.0 + rim
     }
 
-    fn intersect_caps(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn intersect_caps(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         let mut xs: Vec<Intersection> = Vec::new();
 
         if !self.closed || float_eq(ray.direction.y, 0.0) {
@@ -66,6 +81,13 @@ impl Cylinder {
     }
 }
 
+impl Default for Cylinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for Cylinder {
     fn id(&self) -> Uuid {
         self.id
@@ -75,8 +97,16 @@ impl Shape for Cylinder {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -107,7 +137,7 @@ impl Shape for Cylinder {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         let a = ray.direction.x.powi(2) + ray.direction.z.powi(2);
 
         if float_eq(a, 0.0) {
@@ -144,13 +174,10 @@ impl Shape for Cylinder {
             xs.push(Intersection::new(t.1, self))
         }
 
-        match self.intersect_caps(ray) {
-            Some(cxs) => {
-                for i in cxs {
-                    xs.push(i)
-                }
+        if let Some(cxs) = self.intersect_caps(ray) {
+            for i in cxs {
+                xs.push(i)
             }
-            _ => (),
         }
 
         if xs.is_empty() {
@@ -160,15 +187,15 @@ impl Shape for Cylinder {
         }
     }
 
-    fn local_normal_at(&self, point: Point) -> Vector {
+    fn local_normal_at(&self, point: Point) -> Normal {
         let dist = point.x.powi(2) + point.z.powi(2);
 
         if dist < 1.0 && point.y >= self.maximum - EPSILON {
-            Vector::new(0.0, 1.0, 0.0)
+            Normal::new(0.0, 1.0, 0.0)
         } else if dist < 1.0 && point.y <= self.minimum + EPSILON {
-            Vector::new(0.0, -1.0, 0.0)
+            Normal::new(0.0, -1.0, 0.0)
         } else {
-            Vector::new(point.x, 0.0, point.z)
+            Normal::new(point.x, 0.0, point.z)
         }
     }
 }
@@ -181,8 +208,6 @@ impl PartialEq for Cylinder {
 
 #[cfg(test)]
 mod tests {
-    use std::f64::{INFINITY, NEG_INFINITY};
-
     use super::*;
     use crate::{shapes::Shape, Point, Ray, Vector};
 
@@ -245,10 +270,10 @@ mod tests {
     fn normal_vector_on_a_cylinder() {
         let c = Cylinder::new();
         let data = vec![
-            (Point::new(1.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
-            (Point::new(0.0, 5.0, -1.0), Vector::new(0.0, 0.0, -1.0)),
-            (Point::new(0.0, -2.0, 1.0), Vector::new(0.0, 0.0, 1.0)),
-            (Point::new(-1.0, 1.0, 0.0), Vector::new(-1.0, 0.0, 0.0)),
+            (Point::new(1.0, 0.0, 0.0), Normal::new(1.0, 0.0, 0.0)),
+            (Point::new(0.0, 5.0, -1.0), Normal::new(0.0, 0.0, -1.0)),
+            (Point::new(0.0, -2.0, 1.0), Normal::new(0.0, 0.0, 1.0)),
+            (Point::new(-1.0, 1.0, 0.0), Normal::new(-1.0, 0.0, 0.0)),
         ];
         for rec in data {
             let p = rec.0;
@@ -262,8 +287,8 @@ mod tests {
     #[test]
     pub fn the_default_minimum_and_maximum_for_a_cylinder() {
         let cyl = Cylinder::new();
-        assert_eq!(cyl.minimum, NEG_INFINITY);
-        assert_eq!(cyl.maximum, INFINITY);
+        assert_eq!(cyl.minimum, Float::NEG_INFINITY);
+        assert_eq!(cyl.maximum, Float::INFINITY);
     }
 
     // Chapter 13 Cylinders
@@ -296,7 +321,7 @@ mod tests {
     #[test]
     pub fn the_default_closed_value_for_a_cylinder() {
         let cyl = Cylinder::new();
-        assert_eq!(false, cyl.closed)
+        assert!(!cyl.closed)
     }
 
     // Chapter 13 Cylinders
@@ -331,12 +356,12 @@ mod tests {
         c.maximum = 2.0;
         c.closed = true;
         let data = vec![
-            (Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
-            (Point::new(0.5, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
-            (Point::new(0.0, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0)),
-            (Point::new(0.0, 2.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
-            (Point::new(0.5, 2.0, 0.0), Vector::new(0.0, 1.0, 0.0)),
-            (Point::new(0.0, 2.0, 0.5), Vector::new(0.0, 1.0, 0.0)),
+            (Point::new(0.0, 1.0, 0.0), Normal::new(0.0, -1.0, 0.0)),
+            (Point::new(0.5, 1.0, 0.0), Normal::new(0.0, -1.0, 0.0)),
+            (Point::new(0.0, 1.0, 0.5), Normal::new(0.0, -1.0, 0.0)),
+            (Point::new(0.0, 2.0, 0.0), Normal::new(0.0, 1.0, 0.0)),
+            (Point::new(0.5, 2.0, 0.0), Normal::new(0.0, 1.0, 0.0)),
+            (Point::new(0.0, 2.0, 0.5), Normal::new(0.0, 1.0, 0.0)),
         ];
         for rec in data {
             let n = c.local_normal_at(rec.0);
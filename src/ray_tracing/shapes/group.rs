@@ -2,17 +2,21 @@ use std::any::Any;
 
 use uuid::Uuid;
 
-use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector};
+use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Normal, Point, Ray};
 
-use super::Shape;
+use super::{Metadata, ParentKey, Shape};
+use crate::ray_tracing::shape_container::HierarchyError;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Group {
     pub id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
-    pub shapes: Vec<Box<dyn Shape>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
+    shapes: Vec<Box<dyn Shape>>,
 }
 
 impl Group {
@@ -22,15 +26,67 @@ impl Group {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
+            metadata: Metadata::new(),
             shapes: Vec::new(),
         }
     }
 
-    pub fn add_shape(&mut self, shape: Box<dyn Shape>) {
+    /// Adds a child and makes this group its parent. Refused when the shape
+    /// already has another parent, is already in the group, or is this
+    /// group or contains it.
+    pub fn add_shape(&mut self, mut shape: Box<dyn Shape>) -> Result<(), HierarchyError> {
+        match shape.parent_id() {
+            Some(parent) if parent != self.id => {
+                return Err(HierarchyError::AlreadyParented(shape.id()))
+            }
+            _ => self.check_child(shape.as_ref())?,
+        }
+        shape.set_parent_id(Some(self.id), ParentKey(()));
         self.shapes.push(shape);
+        Ok(())
+    }
+
+    /// Takes a direct child out of the group, it no longer has a parent.
+    pub fn remove_shape(&mut self, id: Uuid) -> Option<Box<dyn Shape>> {
+        let i = self.shapes.iter().position(|s| s.id() == id)?;
+        let mut shape = self.shapes.remove(i);
+        shape.set_parent_id(None, ParentKey(()));
+        Some(shape)
+    }
+
+    /// Moves a direct child into another group, the child stays here when
+    /// it can not be moved.
+    pub fn move_shape(&mut self, id: Uuid, to: &mut Group) -> Result<(), HierarchyError> {
+        let i = self
+            .shapes
+            .iter()
+            .position(|s| s.id() == id)
+            .ok_or(HierarchyError::NotFound(id))?;
+        to.check_child(self.shapes[i].as_ref())?;
+        let mut shape = self.shapes.remove(i);
+        shape.set_parent_id(Some(to.id), ParentKey(()));
+        to.shapes.push(shape);
+        Ok(())
+    }
+
+    fn check_child(&self, shape: &dyn Shape) -> Result<(), HierarchyError> {
+        if shape.id() == self.id || shape.get_child(self.id).is_some() {
+            return Err(HierarchyError::Cycle(self.id));
+        }
+        if self.get_child(shape.id()).is_some() {
+            return Err(HierarchyError::AlreadyParented(shape.id()));
+        }
+        Ok(())
     }
 }
 
+impl Default for Group {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for Group {
     fn id(&self) -> Uuid {
         self.id
@@ -40,8 +96,16 @@ impl Shape for Group {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -72,6 +136,18 @@ impl Shape for Group {
         self.material = material;
     }
 
+    fn children(&self) -> &[Box<dyn Shape>] {
+        &self.shapes
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Shape>] {
+        &mut self.shapes
+    }
+
+    fn as_group_mut(&mut self) -> Option<&mut Group> {
+        Some(self)
+    }
+
     fn get_child(&self, id: Uuid) -> Option<&dyn Shape> {
         let mut shape = None;
         for s in &self.shapes {
@@ -79,12 +155,9 @@ impl Shape for Group {
                 shape = Some(s.as_ref());
                 break;
             }
-            match s.get_child(id) {
-                Some(c) => {
-                    shape = Some(c);
-                    break;
-                }
-                None => (),
+            if let Some(c) = s.get_child(id) {
+                shape = Some(c);
+                break;
             }
         }
 
@@ -110,7 +183,7 @@ impl Shape for Group {
         }
     }
 
-    fn local_normal_at(&self, _point: Point) -> Vector {
+    fn local_normal_at(&self, _point: Point) -> Normal {
         panic!("Should not be called!")
     }
 }
@@ -119,7 +192,7 @@ impl Shape for Group {
 mod tests {
     use crate::{
         shapes::{Sphere, TestShape},
-        Transform,
+        Transform, Vector,
     };
 
     use super::*;
@@ -129,14 +202,14 @@ mod tests {
     #[test]
     fn adding_a_child_to_a_group() {
         let mut g = Group::new();
-        let mut s = TestShape::new();
-        s.parent_id = Some(g.id);
+        let s = TestShape::new();
         let s_id = s.id;
 
-        g.add_shape(Box::new(s));
+        g.add_shape(Box::new(s)).unwrap();
 
         assert!(!g.shapes.is_empty());
         assert_eq!(g.shapes[0].id(), s_id);
+        assert_eq!(g.shapes[0].parent_id(), Some(g.id));
     }
 
     // Chapter 14 Groups
@@ -165,9 +238,9 @@ mod tests {
         let mut s3 = Sphere::new();
         s3.transform = Transform::new().translation(5.0, 0.0, 0.0).build();
 
-        g.add_shape(Box::new(s1));
-        g.add_shape(Box::new(s2));
-        g.add_shape(Box::new(s3));
+        g.add_shape(Box::new(s1)).unwrap();
+        g.add_shape(Box::new(s2)).unwrap();
+        g.add_shape(Box::new(s3)).unwrap();
 
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let xs = g.intersect(r).unwrap();
@@ -189,11 +262,101 @@ mod tests {
         let mut s = Sphere::new();
         s.transform = Transform::new().translation(5.0, 0.0, 0.0).build();
 
-        g.add_shape(Box::new(s));
+        g.add_shape(Box::new(s)).unwrap();
 
         let r = Ray::new(Point::new(10.0, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0));
 
         let xs = g.intersect(r).unwrap();
         assert_eq!(xs.len(), 2);
     }
+
+    #[test]
+    fn a_shape_can_not_have_two_parents() {
+        let mut g1 = Group::new();
+        let mut g2 = Group::new();
+        let mut s = Sphere::new();
+        s.parent_id = Some(g1.id);
+        let s_id = s.id;
+        assert_eq!(
+            g2.add_shape(Box::new(s)),
+            Err(HierarchyError::AlreadyParented(s_id))
+        );
+
+        let s = Sphere::new();
+        let mut twin = Sphere::new();
+        twin.id = s.id;
+        let twin_id = twin.id;
+        g1.add_shape(Box::new(s)).unwrap();
+        assert_eq!(
+            g1.add_shape(Box::new(twin)),
+            Err(HierarchyError::AlreadyParented(twin_id))
+        );
+        assert!(g2.shapes.is_empty());
+    }
+
+    #[test]
+    fn a_group_can_not_contain_itself() {
+        let mut outer = Group::new();
+        let mut inner = Group::new();
+        inner.id = outer.id;
+        assert_eq!(
+            outer.add_shape(Box::new(inner)),
+            Err(HierarchyError::Cycle(outer.id))
+        );
+
+        let mut g = Group::new();
+        let mut holder = Group::new();
+        let mut same_as_g = Group::new();
+        same_as_g.id = g.id;
+        holder.add_shape(Box::new(same_as_g)).unwrap();
+        assert_eq!(
+            g.add_shape(Box::new(holder)),
+            Err(HierarchyError::Cycle(g.id))
+        );
+    }
+
+    #[test]
+    fn removing_a_child() {
+        let mut g = Group::new();
+        let s = Sphere::new();
+        let s_id = s.id;
+        g.add_shape(Box::new(s)).unwrap();
+
+        let s = g.remove_shape(s_id).unwrap();
+        assert_eq!(s.parent_id(), None);
+        assert!(g.shapes.is_empty());
+        assert!(g.remove_shape(s_id).is_none());
+
+        g.add_shape(s).unwrap();
+        assert_eq!(g.shapes[0].parent_id(), Some(g.id));
+    }
+
+    #[test]
+    fn moving_a_child_between_groups() {
+        let mut from = Group::new();
+        let mut to = Group::new();
+        let s = Sphere::new();
+        let s_id = s.id;
+        from.add_shape(Box::new(s)).unwrap();
+
+        from.move_shape(s_id, &mut to).unwrap();
+        assert!(from.shapes.is_empty());
+        assert_eq!(to.shapes[0].id(), s_id);
+        assert_eq!(to.shapes[0].parent_id(), Some(to.id));
+
+        assert_eq!(
+            from.move_shape(s_id, &mut to),
+            Err(HierarchyError::NotFound(s_id))
+        );
+
+        let mut same_as_from = Group::new();
+        same_as_from.id = from.id;
+        let moved_id = same_as_from.id;
+        to.add_shape(Box::new(same_as_from)).unwrap();
+        assert_eq!(
+            to.move_shape(moved_id, &mut from),
+            Err(HierarchyError::Cycle(from.id))
+        );
+        assert_eq!(to.shapes.len(), 2);
+    }
 }
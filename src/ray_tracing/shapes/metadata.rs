@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fmt;
+
+/// Name and tags of a shape, used to look shapes up by path, glob or tag.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: Vec<String>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Metadata {
+            name: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Names are path segments, so they can not be empty or contain a
+    /// `'/'`, or the wildcards `'*'` and `'?'` of glob queries. Names of
+    /// siblings are not checked, a path leads to the first sibling with the
+    /// name.
+    pub fn set_name(&mut self, name: &str) -> Result<(), InvalidName> {
+        if name.is_empty() || name.contains(&['/', '*', '?'][..]) {
+            return Err(InvalidName {
+                name: name.to_string(),
+            });
+        }
+        self.name = Some(name.to_string());
+        Ok(())
+    }
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A name that can not be used as a path segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidName {
+    pub name: String,
+}
+
+impl fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not a shape name, names can not be empty or contain '/', '*' or '?'",
+            self.name
+        )
+    }
+}
+
+impl Error for InvalidName {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_path_segments() {
+        let mut m = Metadata::new();
+        m.set_name("leg1").unwrap();
+        assert_eq!(m.name(), Some("leg1"));
+        assert_eq!(
+            m.set_name("table/leg1"),
+            Err(InvalidName {
+                name: "table/leg1".to_string()
+            })
+        );
+        assert!(m.set_name("").is_err());
+        assert!(m.set_name("leg*").is_err());
+        assert!(m.set_name("leg?").is_err());
+        assert_eq!(m.name(), Some("leg1"));
+    }
+}
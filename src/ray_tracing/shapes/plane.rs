@@ -2,17 +2,20 @@ use std::any::Any;
 
 use uuid::Uuid;
 
-use super::Shape;
+use super::{Metadata, ParentKey, Shape};
 use crate::{
-    ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector, EPSILON,
+    ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Normal, Point, Ray, EPSILON,
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Plane {
     id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
     pub cast_shadow: bool,
 }
 
@@ -23,11 +26,19 @@ impl Plane {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
+            metadata: Metadata::new(),
             cast_shadow: true,
         }
     }
 }
 
+impl Default for Plane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for Plane {
     fn id(&self) -> Uuid {
         self.id
@@ -37,8 +48,16 @@ impl Shape for Plane {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -69,7 +88,7 @@ impl Shape for Plane {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         if ray.direction.y.abs() < EPSILON {
             None
         } else {
@@ -80,8 +99,8 @@ impl Shape for Plane {
         }
     }
 
-    fn local_normal_at(&self, _point: Point) -> Vector {
-        Vector::new(0.0, 1.0, 0.0)
+    fn local_normal_at(&self, _point: Point) -> Normal {
+        Normal::new(0.0, 1.0, 0.0)
     }
 }
 
@@ -94,7 +113,7 @@ impl PartialEq for Plane {
 #[cfg(test)]
 mod tests {
     use super::Plane;
-    use crate::{shapes::Shape, Point, Ray, Vector};
+    use crate::{shapes::Shape, Normal, Point, Ray, Vector};
 
     #[test]
     fn normal_plane_constant_everywhere() {
@@ -102,9 +121,9 @@ mod tests {
         let n1 = p.local_normal_at(Point::new(0.0, 0.0, 0.0));
         let n2 = p.local_normal_at(Point::new(0.0, 0.0, 0.0));
         let n3 = p.local_normal_at(Point::new(0.0, 0.0, 0.0));
-        assert_eq!(n1, Vector::new(0.0, 1.0, 0.0));
-        assert_eq!(n2, Vector::new(0.0, 1.0, 0.0));
-        assert_eq!(n3, Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(n1, Normal::new(0.0, 1.0, 0.0));
+        assert_eq!(n2, Normal::new(0.0, 1.0, 0.0));
+        assert_eq!(n3, Normal::new(0.0, 1.0, 0.0));
     }
 
     #[test]
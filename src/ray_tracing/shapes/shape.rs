@@ -1,9 +1,20 @@
 use uuid::Uuid;
 
-use crate::{Intersection, Material, Matrix, Point, Ray, ShapeContainer, Vector};
+use super::{Group, InvalidName, Metadata};
+use crate::ray_tracing::matrix::MatrixError;
+use crate::{
+    Float, Intersection, Material, Matrix, Normal, Point, Quaternion, Ray, ShapeContainer,
+    Transform,
+};
 use std::any::Any;
 use std::fmt;
 
+/// Handed to `Shape::set_parent_id`. Only this crate can make one, so only
+/// groups and the shape container decide which group a shape is in.
+#[derive(Debug)]
+pub struct ParentKey(pub(crate) ());
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 pub trait Shape: Any + fmt::Debug {
     fn id(&self) -> Uuid;
 
@@ -11,7 +22,38 @@ pub trait Shape: Any + fmt::Debug {
         None
     }
 
-    fn set_parent_id(&mut self, id: Uuid);
+    fn set_parent_id(&mut self, id: Option<Uuid>, key: ParentKey);
+
+    fn metadata(&self) -> &Metadata;
+
+    fn metadata_mut(&mut self) -> &mut Metadata;
+
+    /// Human readable name, used in paths like `"room/table/leg1"`.
+    fn name(&self) -> Option<&str> {
+        self.metadata().name()
+    }
+
+    fn set_name(&mut self, name: &str) -> Result<(), InvalidName> {
+        self.metadata_mut().set_name(name)
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.metadata().tags
+    }
+
+    fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.metadata_mut().tags
+    }
+
+    fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags_mut().push(tag.to_string());
+        }
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().iter().any(|t| t == tag)
+    }
 
     fn as_any(&self) -> &dyn Any;
 
@@ -21,64 +63,108 @@ pub trait Shape: Any + fmt::Debug {
 
     fn set_transform(&mut self, transform: Matrix);
 
+    /// Sets the transform unless it cannot be inverted, a shape with a
+    /// singular transform can not be intersected.
+    fn try_set_transform(&mut self, transform: Matrix) -> Result<(), MatrixError> {
+        if transform.is_singular() {
+            return Err(MatrixError::Singular);
+        }
+        self.set_transform(transform);
+        Ok(())
+    }
+
+    /// Applies `t` after the current transform, moving the shape relative
+    /// to where it is.
+    fn transform_by(&mut self, t: &Transform) {
+        let current = Transform::from(self.transform());
+        self.set_transform(current.then(t).build());
+    }
+
+    fn translate(&mut self, x: Float, y: Float, z: Float) {
+        self.transform_by(&Transform::new().translation(x, y, z));
+    }
+
+    fn rotate(&mut self, q: Quaternion) {
+        self.transform_by(&Transform::new().rotation(q));
+    }
+
+    fn rotate_about(&mut self, q: Quaternion, pivot: Point) {
+        self.transform_by(&Transform::new().rotation_about(q, pivot));
+    }
+
+    fn scale(&mut self, x: Float, y: Float, z: Float) {
+        self.transform_by(&Transform::new().scaling(x, y, z));
+    }
+
+    fn scale_about(&mut self, x: Float, y: Float, z: Float, pivot: Point) {
+        self.transform_by(&Transform::new().scaling_about(x, y, z, pivot));
+    }
+
     fn material(&self) -> &Material;
 
     fn material_mut(&mut self) -> &mut Material;
 
     fn set_material(&mut self, material: Material);
 
-    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection>>;
+    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>>;
 
-    fn local_normal_at(&self, point: Point) -> Vector;
+    fn local_normal_at(&self, point: Point) -> Normal;
 
     fn cast_shadow(&self) -> bool {
         true
     }
 
+    /// Shapes directly inside this one, only groups have children.
+    fn children(&self) -> &[Box<dyn Shape>] {
+        &[]
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Shape>] {
+        &mut []
+    }
+
+    /// Children are added and removed through the group.
+    fn as_group_mut(&mut self) -> Option<&mut Group> {
+        None
+    }
+
     fn get_child(&self, _id: Uuid) -> Option<&dyn Shape> {
         None
     }
 
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         let local_ray = ray.transform(&self.transform().inverse());
         self.local_intersect(local_ray)
     }
 
-    fn normal_at(&self, point: Point, sc: Option<&ShapeContainer>) -> Vector {
+    fn normal_at(&self, point: Point, sc: Option<&ShapeContainer>) -> Normal {
         match sc {
             Some(sc) => {
                 let local_point = self.world_to_object(point, sc);
-                let local_normal = self.local_normal_at(local_point);
-                self.normal_to_world(local_normal, sc)
+                self.normal_to_world(self.local_normal_at(local_point), sc)
             }
             None => {
                 let local_point = self.transform().inverse() * point;
-                let local_normal = self.local_normal_at(local_point);
-                (self.transform().inverse().transpose() * local_normal).normalize()
+                (self.transform() * self.local_normal_at(local_point)).normalize()
             }
         }
     }
 
+    /// A parent that is not in `sc` is treated as the world.
     fn world_to_object(&self, point: Point, sc: &ShapeContainer) -> Point {
-        let object_point = match self.parent_id() {
-            Some(id) => {
-                let parent = sc.get_shape(id).expect("Shape not found!");
-                parent.world_to_object(point, sc)
-            }
+        let object_point = match self.parent_id().and_then(|id| sc.get_shape(id)) {
+            Some(parent) => parent.world_to_object(point, sc),
             None => point,
         };
 
         self.transform().inverse() * object_point
     }
 
-    fn normal_to_world(&self, normal: Vector, w: &ShapeContainer) -> Vector {
-        let world_normal = (self.transform().inverse().transpose() * normal).normalize();
+    fn normal_to_world(&self, normal: Normal, w: &ShapeContainer) -> Normal {
+        let world_normal = (self.transform() * normal).normalize();
 
-        match self.parent_id() {
-            Some(id) => {
-                let parent = w.get_shape(id).expect("Shape not found!");
-                parent.normal_to_world(world_normal, w)
-            }
+        match self.parent_id().and_then(|id| w.get_shape(id)) {
+            Some(parent) => parent.normal_to_world(world_normal, w),
             None => world_normal,
         }
     }
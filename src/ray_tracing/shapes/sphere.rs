@@ -2,15 +2,18 @@ use std::any::Any;
 
 use uuid::Uuid;
 
-use super::Shape;
-use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector};
+use super::{Metadata, ParentKey, Shape};
+use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Normal, Point, Ray};
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sphere {
     pub id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
     pub cast_shadow: bool,
 }
 
@@ -21,6 +24,7 @@ impl Sphere {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
+            metadata: Metadata::new(),
             cast_shadow: true,
         }
     }
@@ -34,11 +38,19 @@ impl Sphere {
             parent_id: None,
             transform: IDENTITY,
             material: m,
+            metadata: Metadata::new(),
             cast_shadow: true,
         }
     }
 }
 
+impl Default for Sphere {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for Sphere {
     fn id(&self) -> Uuid {
         self.id
@@ -48,8 +60,16 @@ impl Shape for Sphere {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -84,7 +104,7 @@ impl Shape for Sphere {
         self.cast_shadow
     }
 
-    fn local_intersect(&self, r2: Ray) -> Option<Vec<Intersection>> {
+    fn local_intersect(&self, r2: Ray) -> Option<Vec<Intersection<'_>>> {
         let mut xs: Vec<Intersection> = Vec::new();
 
         let sphere_to_ray = r2.origin - Point::new(0.0, 0.0, 0.0);
@@ -109,19 +129,20 @@ impl Shape for Sphere {
         }
     }
 
-    fn local_normal_at(&self, object_point: Point) -> Vector {
+    fn local_normal_at(&self, object_point: Point) -> Normal {
         //object_normal = object_point - Point::new(0.0, 0.0, 0.0);
         //let word_normal = self.transform.inverse().transpose() * object_normal;
         //word_normal.normalize()
-        object_point - Point::new(0.0, 0.0, 0.0)
+        Normal::from(object_point - Point::new(0.0, 0.0, 0.0))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::consts::{FRAC_1_SQRT_2, PI};
+    use crate::Float;
     use crate::{ray_tracing::matrix, Material, Point, Ray, Transform, Vector};
-    use std::f64::consts::PI;
 
     #[test]
     fn a_ray_intersects_a_sphere_at_two_points() {
@@ -147,7 +168,7 @@ mod tests {
     fn a_ray_misses_a_sphere() {
         let r = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let s = Sphere::new();
-        assert_eq!(s.intersect(r).is_none(), true);
+        assert!(s.intersect(r).is_none());
     }
 
     #[test]
@@ -185,32 +206,40 @@ mod tests {
     fn the_normal_on_sphere_at_point_x_axis() {
         let s = Sphere::new();
         let n = s.normal_at(Point::new(1.0, 0.0, 0.0), None);
-        assert_eq!(Vector::new(1.0, 0.0, 0.0), n);
+        assert_eq!(Normal::new(1.0, 0.0, 0.0), n);
     }
 
     #[test]
     fn the_normal_on_sphere_at_point_y_axis() {
         let s = Sphere::new();
         let n = s.normal_at(Point::new(0.0, 1.0, 0.0), None);
-        assert_eq!(Vector::new(0.0, 1.0, 0.0), n);
+        assert_eq!(Normal::new(0.0, 1.0, 0.0), n);
     }
 
     #[test]
     fn the_normal_on_sphere_at_point_z_axis() {
         let s = Sphere::new();
         let n = s.normal_at(Point::new(0.0, 0.0, 1.0), None);
-        assert_eq!(Vector::new(0.0, 0.0, 1.0), n);
+        assert_eq!(Normal::new(0.0, 0.0, 1.0), n);
     }
 
     #[test]
     fn the_normal_on_sphere_at_point_non_axial() {
         let s = Sphere::new();
         let n = s.normal_at(
-            Point::new(3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0),
+            Point::new(
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+            ),
             None,
         );
         assert_eq!(
-            Vector::new(3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0),
+            Normal::new(
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0
+            ),
             n
         );
     }
@@ -219,7 +248,11 @@ mod tests {
     fn the_normal_is_a_normalized_vector() {
         let s = Sphere::new();
         let n = s.normal_at(
-            Point::new(3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0, 3_f64.sqrt() / 3.0),
+            Point::new(
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+                Float::sqrt(3.0) / 3.0,
+            ),
             None,
         );
         assert_eq!(n, n.normalize());
@@ -229,8 +262,8 @@ mod tests {
     fn computing_normal_on_translated_sphere() {
         let mut s = Sphere::new();
         s.transform = Transform::new().translation(0.0, 1.0, 0.0).build();
-        let n = s.normal_at(Point::new(0.0, 1.70711, -0.70711), None);
-        assert_eq!(Vector::new(0.0, 0.70711, -0.70711), n);
+        let n = s.normal_at(Point::new(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2), None);
+        assert_eq!(Normal::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2), n);
     }
 
     #[test]
@@ -241,10 +274,10 @@ mod tests {
             .scaling(1.0, 0.5, 1.0)
             .build();
         let n = s.normal_at(
-            Point::new(0.0, 2_f64.sqrt() / 2.0, -2_f64.sqrt() / 2.0),
+            Point::new(0.0, Float::sqrt(2.0) / 2.0, -Float::sqrt(2.0) / 2.0),
             None,
         );
-        assert_eq!(Vector::new(0.0, 0.97014, -0.24254), n);
+        assert_eq!(Normal::new(0.0, 0.97014, -0.24254), n);
     }
 
     #[test]
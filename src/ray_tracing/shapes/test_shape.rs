@@ -2,15 +2,18 @@ use std::any::Any;
 
 use uuid::Uuid;
 
-use super::Shape;
-use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Point, Ray, Vector};
+use super::{Metadata, ParentKey, Shape};
+use crate::{ray_tracing::matrix::IDENTITY, Intersection, Material, Matrix, Normal, Point, Ray};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TestShape {
     pub id: Uuid,
     pub parent_id: Option<Uuid>,
     pub transform: Matrix,
     pub material: Material,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: Metadata,
 }
 
 impl TestShape {
@@ -20,10 +23,18 @@ impl TestShape {
             parent_id: None,
             transform: IDENTITY,
             material: Material::new(),
+            metadata: Metadata::new(),
         }
     }
 }
 
+impl Default for TestShape {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(feature = "serde", typetag::serde)]
 impl Shape for TestShape {
     fn id(&self) -> Uuid {
         self.id
@@ -33,8 +44,16 @@ impl Shape for TestShape {
         self.parent_id
     }
 
-    fn set_parent_id(&mut self, id: Uuid) {
-        self.parent_id = Some(id);
+    fn set_parent_id(&mut self, id: Option<Uuid>, _: ParentKey) {
+        self.parent_id = id;
+    }
+
+    fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -65,7 +84,7 @@ impl Shape for TestShape {
         self.material = material;
     }
 
-    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+    fn local_intersect(&self, ray: Ray) -> Option<Vec<Intersection<'_>>> {
         Some(vec![
             Intersection::new(ray.origin.x, self),
             Intersection::new(ray.origin.y, self),
@@ -76,8 +95,8 @@ impl Shape for TestShape {
         ])
     }
 
-    fn local_normal_at(&self, point: Point) -> Vector {
-        Vector::new(point.x, point.y, point.z)
+    fn local_normal_at(&self, point: Point) -> Normal {
+        Normal::new(point.x, point.y, point.z)
     }
 }
 
@@ -90,7 +109,8 @@ impl PartialEq for TestShape {
 #[cfg(test)]
 mod tests {
     use super::TestShape;
-    use crate::{shapes::Shape, Point, Ray, Transform, Vector};
+    use crate::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+    use crate::{shapes::Shape, Normal, Point, Quaternion, Ray, Transform, Vector};
 
     #[test]
     fn name() {
@@ -126,7 +146,42 @@ mod tests {
     fn computing_normal_on_translated_shape() {
         let mut s = TestShape::new();
         s.set_transform(Transform::new().translation(0.0, 1.0, 0.0).build());
-        let n = s.normal_at(Point::new(0.0, 1.7071, -0.70711), None);
-        assert_eq!(n, Vector::new(0.0, 0.70711, -0.70711));
+        let n = s.normal_at(Point::new(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2), None);
+        assert_eq!(n, Normal::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn moving_shapes_relative_to_their_transform() {
+        let mut s = TestShape::new();
+        s.set_transform(Transform::new().scaling(2.0, 2.0, 2.0).build());
+        s.translate(1.0, 0.0, 0.0);
+        assert_eq!(
+            s.transform() * Point::new(1.0, 1.0, 1.0),
+            Point::new(3.0, 2.0, 2.0)
+        );
+
+        s.scale_about(0.5, 0.5, 0.5, Point::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            s.transform() * Point::new(1.0, 1.0, 1.0),
+            Point::new(2.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn rotating_shapes_in_place() {
+        let quarter = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        let mut s = TestShape::new();
+        s.translate(2.0, 0.0, 0.0);
+        s.rotate_about(quarter, Point::new(2.0, 0.0, 0.0));
+        assert_eq!(
+            s.transform() * Point::new(1.0, 0.0, 0.0),
+            Point::new(2.0, 1.0, 0.0)
+        );
+
+        s.rotate(quarter);
+        assert_eq!(
+            s.transform() * Point::new(1.0, 0.0, 0.0),
+            Point::new(-1.0, 2.0, 0.0)
+        );
     }
 }
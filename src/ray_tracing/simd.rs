@@ -0,0 +1,220 @@
+//! Vector and matrix kernels. With the `simd` feature on x86_64 they use
+//! SSE2, which every x86_64 cpu supports, so no runtime detection is needed.
+//! Other targets, `f32` builds and builds without the feature use the scalar
+//! versions.
+//!
+//! Both versions perform the same operations in the same order, renders are
+//! bit for bit identical with and without the feature.
+
+use crate::Float;
+
+pub type Matrix4 = [[Float; 4]; 4];
+
+#[cfg(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64"))]
+pub use self::sse2::{cross, div, dot, multiply, transform, transform_packet};
+
+#[cfg(not(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64")))]
+pub use self::scalar::{cross, div, dot, multiply, transform, transform_packet};
+
+pub mod scalar {
+    use super::Matrix4;
+    use crate::Float;
+
+    #[inline]
+    pub fn dot(a: [Float; 3], b: [Float; 3]) -> Float {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    #[inline]
+    pub fn cross(a: [Float; 3], b: [Float; 3]) -> [Float; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    #[inline]
+    pub fn div(a: [Float; 3], s: Float) -> [Float; 3] {
+        [a[0] / s, a[1] / s, a[2] / s]
+    }
+
+    pub fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+        let mut results = [[0.0; 4]; 4];
+        for row in 0..4 {
+            for col in 0..4 {
+                results[row][col] = a[row][0] * b[0][col]
+                    + a[row][1] * b[1][col]
+                    + a[row][2] * b[2][col]
+                    + a[row][3] * b[3][col];
+            }
+        }
+        results
+    }
+
+    /// Multiplies `m` with the column (x, y, z, w) and drops the fourth row.
+    #[inline]
+    pub fn transform(m: &Matrix4, v: [Float; 3], w: Float) -> [Float; 3] {
+        let mut results = [0.0; 3];
+        for (row, result) in results.iter_mut().enumerate() {
+            *result = m[row][0] * v[0] + m[row][1] * v[1] + m[row][2] * v[2] + m[row][3] * w;
+        }
+        results
+    }
+
+    /// `transform` for four columns stored as separate x, y and z lanes.
+    pub fn transform_packet(m: &Matrix4, v: [[Float; 4]; 3], w: Float) -> [[Float; 4]; 3] {
+        let mut results = [[0.0; 4]; 3];
+        for lane in 0..4 {
+            let t = transform(m, [v[0][lane], v[1][lane], v[2][lane]], w);
+            for axis in 0..3 {
+                results[axis][lane] = t[axis];
+            }
+        }
+        results
+    }
+}
+
+#[cfg(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64"))]
+mod sse2 {
+    use super::Matrix4;
+    use std::arch::x86_64::*;
+
+    // SSE2 is part of the x86_64 baseline, the intrinsics below are always
+    // available and only touch the memory they are given.
+
+    #[inline]
+    pub fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+        unsafe {
+            let xy = _mm_mul_pd(_mm_loadu_pd(a.as_ptr()), _mm_loadu_pd(b.as_ptr()));
+            let sum = _mm_add_sd(xy, _mm_unpackhi_pd(xy, xy));
+            _mm_cvtsd_f64(sum) + a[2] * b[2]
+        }
+    }
+
+    #[inline]
+    pub fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+        unsafe {
+            let a_yz = _mm_loadu_pd(a[1..].as_ptr());
+            let b_yz = _mm_loadu_pd(b[1..].as_ptr());
+            let a_zx = _mm_set_pd(a[0], a[2]);
+            let b_zx = _mm_set_pd(b[0], b[2]);
+            let xy = _mm_sub_pd(_mm_mul_pd(a_yz, b_zx), _mm_mul_pd(a_zx, b_yz));
+            let mut results = [0.0; 3];
+            _mm_storeu_pd(results.as_mut_ptr(), xy);
+            results[2] = a[0] * b[1] - a[1] * b[0];
+            results
+        }
+    }
+
+    #[inline]
+    pub fn div(a: [f64; 3], s: f64) -> [f64; 3] {
+        unsafe {
+            let xy = _mm_div_pd(_mm_loadu_pd(a.as_ptr()), _mm_set1_pd(s));
+            let mut results = [0.0; 3];
+            _mm_storeu_pd(results.as_mut_ptr(), xy);
+            results[2] = a[2] / s;
+            results
+        }
+    }
+
+    pub fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+        let mut results = [[0.0; 4]; 4];
+        unsafe {
+            for (row, result) in a.iter().zip(results.iter_mut()) {
+                let mut lo = _mm_mul_pd(_mm_set1_pd(row[0]), _mm_loadu_pd(b[0].as_ptr()));
+                let mut hi = _mm_mul_pd(_mm_set1_pd(row[0]), _mm_loadu_pd(b[0][2..].as_ptr()));
+                for k in 1..4 {
+                    let s = _mm_set1_pd(row[k]);
+                    lo = _mm_add_pd(lo, _mm_mul_pd(s, _mm_loadu_pd(b[k].as_ptr())));
+                    hi = _mm_add_pd(hi, _mm_mul_pd(s, _mm_loadu_pd(b[k][2..].as_ptr())));
+                }
+                _mm_storeu_pd(result.as_mut_ptr(), lo);
+                _mm_storeu_pd(result[2..].as_mut_ptr(), hi);
+            }
+        }
+        results
+    }
+
+    #[inline]
+    pub fn transform(m: &Matrix4, v: [f64; 3], w: f64) -> [f64; 3] {
+        unsafe {
+            // rows 0 and 1 in the two lanes
+            let column = |k: usize| _mm_set_pd(m[1][k], m[0][k]);
+            let mut xy = _mm_mul_pd(column(0), _mm_set1_pd(v[0]));
+            xy = _mm_add_pd(xy, _mm_mul_pd(column(1), _mm_set1_pd(v[1])));
+            xy = _mm_add_pd(xy, _mm_mul_pd(column(2), _mm_set1_pd(v[2])));
+            xy = _mm_add_pd(xy, _mm_mul_pd(column(3), _mm_set1_pd(w)));
+            let mut results = [0.0; 3];
+            _mm_storeu_pd(results.as_mut_ptr(), xy);
+            results[2] = m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2] + m[2][3] * w;
+            results
+        }
+    }
+
+    pub fn transform_packet(m: &Matrix4, v: [[f64; 4]; 3], w: f64) -> [[f64; 4]; 3] {
+        let mut results = [[0.0; 4]; 3];
+        unsafe {
+            for half in 0..2 {
+                let lanes = [
+                    _mm_loadu_pd(v[0][half * 2..].as_ptr()),
+                    _mm_loadu_pd(v[1][half * 2..].as_ptr()),
+                    _mm_loadu_pd(v[2][half * 2..].as_ptr()),
+                ];
+                for (row, result) in m.iter().zip(results.iter_mut()) {
+                    let mut acc = _mm_mul_pd(_mm_set1_pd(row[0]), lanes[0]);
+                    acc = _mm_add_pd(acc, _mm_mul_pd(_mm_set1_pd(row[1]), lanes[1]));
+                    acc = _mm_add_pd(acc, _mm_mul_pd(_mm_set1_pd(row[2]), lanes[2]));
+                    acc = _mm_add_pd(acc, _mm_mul_pd(_mm_set1_pd(row[3]), _mm_set1_pd(w)));
+                    _mm_storeu_pd(result[half * 2..].as_mut_ptr(), acc);
+                }
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Matrix4 = [
+        [1.0, 2.0, 3.0, 4.0],
+        [5.5, 6.5, 7.5, 8.5],
+        [9.0, 8.0, 7.0, 6.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+    const B: Matrix4 = [
+        [-2.0, 1.0, 2.0, 3.0],
+        [3.0, 2.0, 1.0, -1.0],
+        [4.0, 3.0, 6.0, 5.0],
+        [1.0, 2.0, 7.0, 8.0],
+    ];
+
+    #[test]
+    fn kernels_match_the_scalar_versions() {
+        let a = [0.1, -2.7, 3.3];
+        let b = [4.25, 0.5, -1.125];
+        assert_eq!(dot(a, b), scalar::dot(a, b));
+        assert_eq!(cross(a, b), scalar::cross(a, b));
+        assert_eq!(div(a, 3.0), scalar::div(a, 3.0));
+        assert_eq!(multiply(&A, &B), scalar::multiply(&A, &B));
+        assert_eq!(transform(&A, a, 1.0), scalar::transform(&A, a, 1.0));
+        assert_eq!(transform(&A, a, 0.0), scalar::transform(&A, a, 0.0));
+
+        let lanes = [
+            [1.0, 2.0, 3.0, 4.0],
+            [0.5, -0.5, 1.5, -1.5],
+            [9.0, 8.0, 7.0, 6.0],
+        ];
+        assert_eq!(
+            transform_packet(&B, lanes, 1.0),
+            scalar::transform_packet(&B, lanes, 1.0)
+        );
+    }
+
+    #[test]
+    fn cross_product() {
+        assert_eq!(cross([1.0, 2.0, 3.0], [2.0, 3.0, 4.0]), [-1.0, 2.0, -1.0]);
+    }
+}
@@ -0,0 +1,94 @@
+//! FNV-1a hashing with results that do not change between runs, builds or
+//! platforms, unlike `DefaultHasher` which makes no such promise.
+
+use std::fmt;
+use std::hash::Hasher;
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> StableHasher {
+        StableHasher(OFFSET_BASIS)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(PRIME);
+        }
+    }
+
+    // fixed width, little endian integers so 32 and 64 bit targets agree
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Formatting into the hasher hashes `Debug` or `Display` output without
+/// building a string.
+impl fmt::Write for StableHasher {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+    use std::hash::Hash;
+
+    #[test]
+    fn matches_the_published_fnv_1a_values() {
+        let mut h = StableHasher::new();
+        assert_eq!(h.finish(), 0xcbf2_9ce4_8422_2325);
+        h.write(b"a");
+        assert_eq!(h.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn formatting_hashes_the_text() {
+        let mut a = StableHasher::new();
+        write!(a, "{:?}", (1, "x")).unwrap();
+        let mut b = StableHasher::new();
+        b.write(b"(1, \"x\")");
+        assert_eq!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn integers_hash_the_same_at_every_width_of_usize() {
+        let mut a = StableHasher::new();
+        7usize.hash(&mut a);
+        let mut b = StableHasher::new();
+        7u64.hash(&mut b);
+        assert_eq!(a.finish(), b.finish());
+    }
+}
@@ -0,0 +1,514 @@
+use super::progressive::CancelToken;
+use super::sampler::Sampler;
+use super::stable_hash::StableHasher;
+use super::world::SingularTransform;
+use crate::{Camera, Canvas, Color, Float, World};
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Rectangle of the camera image in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Splits a `width` by `height` image into tiles row by row, tiles on
+    /// the right and bottom edges are cut to fit.
+    pub fn tiles(
+        width: usize,
+        height: usize,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> Vec<Region> {
+        let tile_width = tile_width.max(1);
+        let tile_height = tile_height.max(1);
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(tile_height) {
+            for x in (0..width).step_by(tile_width) {
+                tiles.push(Region::new(
+                    x,
+                    y,
+                    tile_width.min(width - x),
+                    tile_height.min(height - y),
+                ));
+            }
+        }
+        tiles
+    }
+}
+
+impl Camera {
+    /// Renders only the given part of the image. Pixels are sampled exactly
+    /// like `render` does, so regions pasted together match a full render.
+    /// A shape with a singular transform is an error, like in `try_render`.
+    pub fn render_region<S: Into<Sampler>>(
+        &self,
+        world: &World,
+        sampler: S,
+        rd: usize,
+        region: Region,
+    ) -> Result<Canvas, SingularTransform> {
+        world.validate()?;
+        Ok(self.sample_region(world, &sampler.into(), rd, region))
+    }
+
+    fn sample_region(&self, world: &World, sampler: &Sampler, rd: usize, region: Region) -> Canvas {
+        let right = (region.x + region.width).min(self.hsize());
+        let bottom = (region.y + region.height).min(self.vsize());
+        let mut canvas = Canvas::new(
+            right.saturating_sub(region.x),
+            bottom.saturating_sub(region.y),
+        );
+
+        for (dy, row) in canvas.rows_mut().enumerate() {
+            for (dx, pixel) in row.iter_mut().enumerate() {
+                let (x, y) = (region.x + dx, region.y + dy);
+                *pixel = self.sample_pixel(world, sampler, x, y, rd);
+            }
+        }
+
+        canvas
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    /// The file is not a checkpoint or it is truncated.
+    Malformed,
+    /// The checkpoint was written for a different camera, tiling, sampler,
+    /// recursion depth or scene id.
+    Mismatch,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "checkpoint I/O failed: {}", e),
+            CheckpointError::Malformed => write!(f, "malformed checkpoint"),
+            CheckpointError::Mismatch => {
+                write!(f, "checkpoint belongs to a render with other settings")
+            }
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+const MAGIC: &[u8; 8] = b"RRTILES1";
+
+/// Renders the camera image tile by tile and keeps track of the finished
+/// tiles so the state can be written to a checkpoint file and resumed later.
+pub struct TiledRender<'a> {
+    camera: &'a Camera,
+    world: &'a World,
+    sampler: Sampler,
+    rd: usize,
+    scene_id: u64,
+    checkpoint_interval: usize,
+    tiles: Vec<Region>,
+    done: Vec<bool>,
+    canvas: Canvas,
+}
+
+impl<'a> TiledRender<'a> {
+    /// A shape with a singular transform is an error, like in
+    /// `Camera::try_render`. The render borrows the world, so it can not
+    /// change before the tiles are rendered.
+    pub fn new<S: Into<Sampler>>(
+        camera: &'a Camera,
+        world: &'a World,
+        sampler: S,
+        rd: usize,
+        tile_width: usize,
+        tile_height: usize,
+    ) -> Result<TiledRender<'a>, SingularTransform> {
+        world.validate()?;
+        let tiles = Region::tiles(camera.hsize(), camera.vsize(), tile_width, tile_height);
+        Ok(TiledRender {
+            camera,
+            world,
+            sampler: sampler.into(),
+            rd,
+            scene_id: 0,
+            checkpoint_interval: 16,
+            done: vec![false; tiles.len()],
+            tiles,
+            canvas: Canvas::new(camera.hsize(), camera.vsize()),
+        })
+    }
+
+    /// Identifies the world in checkpoints, e.g. a hash or version of the
+    /// scene file. Checkpoints can not detect changes to the world by
+    /// themselves, only a different scene id.
+    pub fn with_scene_id(mut self, scene_id: u64) -> TiledRender<'a> {
+        self.scene_id = scene_id;
+        self
+    }
+
+    /// Number of tiles `run` renders between two checkpoints, 16 by
+    /// default. Unsaved tiles are also written when the render is cancelled
+    /// or complete.
+    pub fn with_checkpoint_interval(mut self, tiles: usize) -> TiledRender<'a> {
+        self.checkpoint_interval = tiles.max(1);
+        self
+    }
+
+    pub fn tiles(&self) -> &[Region] {
+        &self.tiles
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.done.iter().filter(|&&d| !d).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// The image so far, unfinished tiles are black.
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    /// Renders the next unfinished tile and returns it, `None` once every
+    /// tile is done.
+    pub fn render_next_tile(&mut self) -> Option<Region> {
+        let i = self.done.iter().position(|&d| !d)?;
+        let region = self.tiles[i];
+        let tile = self
+            .camera
+            .sample_region(self.world, &self.sampler, self.rd, region);
+        self.canvas.paste(&tile, region.x, region.y).unwrap();
+        self.done[i] = true;
+        Some(region)
+    }
+
+    /// Renders the remaining tiles, saving a checkpoint every
+    /// `checkpoint_interval` tiles and before returning when a path is
+    /// given. Returns false when cancelled before the image was complete.
+    pub fn run(
+        &mut self,
+        checkpoint: Option<&Path>,
+        cancel: &CancelToken,
+    ) -> Result<bool, CheckpointError> {
+        let mut unsaved = 0;
+        while !self.is_complete() && !cancel.is_cancelled() {
+            self.render_next_tile();
+            unsaved += 1;
+            if let Some(path) = checkpoint {
+                if unsaved >= self.checkpoint_interval {
+                    self.save_checkpoint(path)?;
+                    unsaved = 0;
+                }
+            }
+        }
+        if let Some(path) = checkpoint {
+            if unsaved > 0 {
+                self.save_checkpoint(path)?;
+            }
+        }
+        Ok(self.is_complete())
+    }
+
+    /// Restores the finished tiles from a checkpoint when the file exists,
+    /// then renders the rest. The checkpoint is removed once the image is
+    /// complete.
+    pub fn resume(
+        &mut self,
+        checkpoint: &Path,
+        cancel: &CancelToken,
+    ) -> Result<bool, CheckpointError> {
+        if checkpoint.exists() {
+            self.load_checkpoint(checkpoint)?;
+        }
+        let complete = self.run(Some(checkpoint), cancel)?;
+        if complete && checkpoint.exists() {
+            fs::remove_file(checkpoint)?;
+        }
+        Ok(complete)
+    }
+
+    /// Writes the finished tiles. The file is synced and then replaced
+    /// atomically, so a crash while saving keeps the previous checkpoint.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        {
+            let mut w = BufWriter::new(File::create(&temporary)?);
+            w.write_all(MAGIC)?;
+            w.write_all(&self.fingerprint().to_le_bytes())?;
+            for &d in &self.done {
+                w.write_all(&[d as u8])?;
+            }
+            for (region, _) in self.tiles.iter().zip(&self.done).filter(|(_, &d)| d) {
+                let tile = self
+                    .canvas
+                    .view(region.x, region.y, region.width, region.height)
+                    .unwrap();
+                for row in tile.rows() {
+                    for c in row {
+                        for v in &[c.red, c.green, c.blue] {
+                            #[allow(clippy::unnecessary_cast)]
+                            w.write_all(&(*v as f64).to_le_bytes())?;
+                        }
+                    }
+                }
+            }
+            w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load_checkpoint(&mut self, path: &Path) -> Result<(), CheckpointError> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        let header = MAGIC.len() + 8;
+        if data.len() < header + self.tiles.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(CheckpointError::Malformed);
+        }
+        let fingerprint = u64::from_le_bytes(data[MAGIC.len()..header].try_into().unwrap());
+        if fingerprint != self.fingerprint() {
+            return Err(CheckpointError::Mismatch);
+        }
+        let done: Vec<bool> = data[header..header + self.tiles.len()]
+            .iter()
+            .map(|&b| b != 0)
+            .collect();
+
+        let mut values = data[header + self.tiles.len()..]
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as Float);
+        let mut canvas = Canvas::new(self.canvas.width(), self.canvas.height());
+        for (region, _) in self.tiles.iter().zip(&done).filter(|(_, &d)| d) {
+            let mut tile = Canvas::new(region.width, region.height);
+            for pixel in tile.pixels_mut() {
+                let mut next = || values.next().ok_or(CheckpointError::Malformed);
+                *pixel = Color::new(next()?, next()?, next()?);
+            }
+            canvas.paste(&tile, region.x, region.y).unwrap();
+        }
+        if values.next().is_some() {
+            return Err(CheckpointError::Malformed);
+        }
+
+        self.done = done;
+        self.canvas = canvas;
+        Ok(())
+    }
+
+    /// Identifies everything that influences the pixels, a checkpoint is
+    /// only valid for a render with the same fingerprint. The hash is
+    /// stable so checkpoints survive a rebuild.
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        (self.camera.hsize(), self.camera.vsize(), self.camera.seed()).hash(&mut hasher);
+        for row in 0..4 {
+            for value in self.camera.transform[row].iter() {
+                hasher.write(&value.to_le_bytes());
+            }
+        }
+        write!(hasher, "{:?}", self.camera.projection()).unwrap();
+        self.rd.hash(&mut hasher);
+        self.scene_id.hash(&mut hasher);
+        self.tiles.len().hash(&mut hasher);
+        for t in &self.tiles {
+            (t.x, t.y, t.width, t.height).hash(&mut hasher);
+        }
+        write!(hasher, "{:?}", self.sampler).unwrap();
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PI;
+    use crate::ray_tracing::camera::AntiAlias;
+    use crate::{Point, Transform, Vector};
+    use std::path::PathBuf;
+
+    fn camera() -> Camera {
+        Camera::builder(11, 7)
+            .look_at(
+                Point::new(0.0, 0.0, -5.0),
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            )
+            .field_of_view(PI / 2.0)
+            .build()
+    }
+
+    fn checkpoint_path(name: &str) -> PathBuf {
+        crate::temp_path(&format!("{}.ckpt", name))
+    }
+
+    #[test]
+    fn splitting_an_image_into_tiles() {
+        let tiles = Region::tiles(11, 7, 4, 4);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[2], Region::new(8, 0, 3, 4));
+        assert_eq!(tiles[5], Region::new(8, 4, 3, 3));
+    }
+
+    #[test]
+    fn a_rendered_region_matches_the_full_render() {
+        let c = camera();
+        let w = World::default();
+        let full = c.render(&w, AntiAlias::Random(4), 5);
+        let region = c
+            .render_region(&w, AntiAlias::Random(4), 5, Region::new(3, 2, 5, 4))
+            .unwrap();
+
+        assert_eq!(region, full.crop(3, 2, 5, 4).unwrap());
+    }
+
+    #[test]
+    fn regions_are_clipped_to_the_image() {
+        let c = camera();
+        let region = c
+            .render_region(
+                &World::default(),
+                AntiAlias::None,
+                5,
+                Region::new(9, 5, 4, 4),
+            )
+            .unwrap();
+        assert_eq!((region.width(), region.height()), (2, 2));
+    }
+
+    #[test]
+    fn tiled_render_matches_the_full_render() {
+        let c = camera();
+        let w = World::default();
+        let mut tiled = TiledRender::new(&c, &w, AntiAlias::Random(4), 5, 4, 4).unwrap();
+        assert!(tiled.run(None, &CancelToken::new()).unwrap());
+        assert_eq!(tiled.canvas(), &c.render(&w, AntiAlias::Random(4), 5));
+    }
+
+    #[test]
+    fn resuming_from_a_checkpoint() {
+        let c = camera();
+        let w = World::default();
+        let path = checkpoint_path("resume");
+
+        // render part of the image, then drop the state as if the process died
+        let mut first = TiledRender::new(&c, &w, AntiAlias::Random(4), 5, 4, 4).unwrap();
+        first.render_next_tile();
+        first.render_next_tile();
+        first.save_checkpoint(&path).unwrap();
+
+        let mut second = TiledRender::new(&c, &w, AntiAlias::Random(4), 5, 4, 4).unwrap();
+        second.load_checkpoint(&path).unwrap();
+        assert_eq!(second.remaining(), 4);
+        assert_eq!(second.canvas(), first.canvas());
+
+        assert!(second.resume(&path, &CancelToken::new()).unwrap());
+        assert!(!path.exists());
+        assert_eq!(second.canvas(), &c.render(&w, AntiAlias::Random(4), 5));
+    }
+
+    #[test]
+    fn checkpoints_of_other_renders_are_rejected() {
+        let c = camera();
+        let w = World::default();
+        let path = checkpoint_path("mismatch");
+
+        let mut render = TiledRender::new(&c, &w, AntiAlias::Random(4), 5, 4, 4).unwrap();
+        render.render_next_tile();
+        render.save_checkpoint(&path).unwrap();
+
+        let mut other = TiledRender::new(&c, &w, AntiAlias::Random(8), 5, 4, 4).unwrap();
+        let mismatch = other.load_checkpoint(&path);
+        let mut moved = camera();
+        moved.transform = Transform::new().translation(0.0, 0.0, 1.0).build() * moved.transform;
+        let mut other = TiledRender::new(&moved, &w, AntiAlias::Random(4), 5, 4, 4).unwrap();
+        let moved_camera = other.load_checkpoint(&path);
+        let mut other = TiledRender::new(&c, &w, AntiAlias::Random(4), 5, 4, 4)
+            .unwrap()
+            .with_scene_id(1);
+        let other_scene = other.load_checkpoint(&path);
+        std::fs::write(&path, b"RRTILES1").unwrap();
+        let malformed = render.load_checkpoint(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(mismatch, Err(CheckpointError::Mismatch)));
+        assert!(matches!(moved_camera, Err(CheckpointError::Mismatch)));
+        assert!(matches!(other_scene, Err(CheckpointError::Mismatch)));
+        assert!(matches!(malformed, Err(CheckpointError::Malformed)));
+    }
+
+    #[test]
+    fn the_temporary_file_keeps_the_whole_file_name() {
+        let path = checkpoint_path("temporary");
+        let unrelated = path.with_extension("tmp");
+        std::fs::write(&unrelated, b"keep").unwrap();
+
+        let c = camera();
+        let w = World::default();
+        let mut render = TiledRender::new(&c, &w, AntiAlias::None, 5, 4, 4).unwrap();
+        render.render_next_tile();
+        render.save_checkpoint(&path).unwrap();
+        let kept = std::fs::read(&unrelated).unwrap();
+        std::fs::remove_file(&unrelated).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(kept, b"keep");
+    }
+
+    #[test]
+    fn cancelled_renders_stop_between_tiles() {
+        let c = camera();
+        let w = World::default();
+        let token = CancelToken::new();
+        token.cancel();
+
+        let mut render = TiledRender::new(&c, &w, AntiAlias::None, 5, 4, 4).unwrap();
+        assert!(!render.run(None, &token).unwrap());
+        assert_eq!(render.remaining(), 6);
+    }
+
+    #[test]
+    fn tiles_since_the_last_checkpoint_are_saved_at_the_end() {
+        let path = checkpoint_path("interval");
+        let c = camera();
+        let w = World::default();
+        let mut render = TiledRender::new(&c, &w, AntiAlias::None, 5, 4, 4)
+            .unwrap()
+            .with_checkpoint_interval(4);
+        assert!(render.run(Some(&path), &CancelToken::new()).unwrap());
+
+        let mut loaded = TiledRender::new(&c, &w, AntiAlias::None, 5, 4, 4).unwrap();
+        loaded.load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_complete());
+        assert_eq!(loaded.canvas(), render.canvas());
+    }
+}
@@ -0,0 +1,238 @@
+use super::sampler::pixel_rng;
+use crate::{Color, Float};
+use rand::Rng;
+
+/// Operator compressing linear radiance into the displayable 0..1 range.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    /// Hard clip at 1.0, the behaviour of `Color::to_rgb`.
+    Clamp,
+    /// Reinhard `L / (1 + L)` on the luminance, keeps the hue.
+    Reinhard,
+    /// Reinhard with a white point, luminance `white` maps to 1.0. Prefer
+    /// `ToneMap::reinhard_extended`, a white point below `Float::EPSILON`
+    /// is raised to it.
+    ReinhardExtended { white: Float },
+    /// Narkowicz's fit of the ACES filmic curve.
+    AcesFilmic,
+}
+
+impl ToneMap {
+    /// Extended Reinhard, `None` unless `white` is finite and positive.
+    pub fn reinhard_extended(white: Float) -> Option<ToneMap> {
+        if white.is_finite() && white > 0.0 {
+            Some(ToneMap::ReinhardExtended { white })
+        } else {
+            None
+        }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        match self {
+            ToneMap::Clamp => Color::new(
+                color.red.clamp(0.0, 1.0),
+                color.green.clamp(0.0, 1.0),
+                color.blue.clamp(0.0, 1.0),
+            ),
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ReinhardExtended { white } => {
+                let white = white.max(Float::EPSILON);
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMap::AcesFilmic => Color::new(aces(color.red), aces(color.green), aces(color.blue)),
+        }
+    }
+}
+
+fn scale_luminance<F: Fn(Float) -> Float>(color: Color, f: F) -> Color {
+    let l = color.luminance();
+    if l <= 0.0 {
+        Color::new(0.0, 0.0, 0.0)
+    } else {
+        color * (f(l) / l)
+    }
+}
+
+fn aces(x: Float) -> Float {
+    let x = x.max(0.0);
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+/// sRGB opto-electronic transfer function for a linear value in 0..1.
+pub fn srgb_encode(c: Float) -> Float {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_encode`.
+pub fn srgb_decode(c: Float) -> Float {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Settings for turning a linear canvas into display referred 8 bit values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PostProcess {
+    /// Exposure adjustment in stops, colors are scaled by `2^exposure`.
+    pub exposure: Float,
+    pub tone_map: ToneMap,
+    /// Apply the sRGB transfer curve, otherwise values are written linearly.
+    pub srgb: bool,
+    /// Add triangular noise of one quantization step before rounding to
+    /// break up banding in smooth gradients.
+    pub dither: bool,
+    pub seed: u64,
+}
+
+impl PostProcess {
+    pub fn new() -> PostProcess {
+        PostProcess {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            srgb: false,
+            dither: false,
+            seed: 0,
+        }
+    }
+
+    /// Filmic tone mapping, sRGB encoding and dithering.
+    pub fn display() -> PostProcess {
+        PostProcess {
+            tone_map: ToneMap::AcesFilmic,
+            srgb: true,
+            dither: true,
+            ..PostProcess::new()
+        }
+    }
+
+    /// Applies exposure, the tone mapping operator and the transfer curve.
+    pub fn apply(&self, color: Color) -> Color {
+        let c = self.tone_map.apply(color * Float::powf(2.0, self.exposure));
+        if self.srgb {
+            Color::new(
+                srgb_encode(c.red.clamp(0.0, 1.0)),
+                srgb_encode(c.green.clamp(0.0, 1.0)),
+                srgb_encode(c.blue.clamp(0.0, 1.0)),
+            )
+        } else {
+            c
+        }
+    }
+
+    /// Processes and quantizes the color of the pixel at x, y.
+    pub fn quantize(&self, color: Color, x: usize, y: usize) -> (u8, u8, u8) {
+        let c = self.apply(color);
+        let mut noise = [0.0; 3];
+        if self.dither {
+            let mut rng = pixel_rng(self.seed, x, y);
+            for n in noise.iter_mut() {
+                *n = rng.gen::<Float>() - rng.gen::<Float>();
+            }
+        }
+        let q = |v: Float, n: Float| (v * 255.0 + 0.5 + n).floor().clamp(0.0, 255.0) as u8;
+        (
+            q(c.red, noise[0]),
+            q(c.green, noise[1]),
+            q(c.blue, noise[2]),
+        )
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float_eq;
+
+    #[test]
+    fn reinhard_compresses_highlights() {
+        let c = ToneMap::Reinhard.apply(Color::new(1.0, 1.0, 1.0));
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+        let c = ToneMap::Reinhard.apply(Color::new(1000.0, 1000.0, 1000.0));
+        assert!(c.red < 1.0 && c.red > 0.99);
+    }
+
+    #[test]
+    fn reinhard_keeps_the_hue() {
+        let c = ToneMap::Reinhard.apply(Color::new(4.0, 2.0, 0.0));
+        assert!(float_eq(c.red / c.green, 2.0));
+        assert_eq!(c.blue, 0.0);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let c = ToneMap::ReinhardExtended { white: 4.0 }.apply(Color::new(4.0, 4.0, 4.0));
+        assert_eq!(c, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn extended_reinhard_needs_a_positive_white_point() {
+        assert_eq!(
+            ToneMap::reinhard_extended(4.0),
+            Some(ToneMap::ReinhardExtended { white: 4.0 })
+        );
+        assert_eq!(ToneMap::reinhard_extended(0.0), None);
+        assert_eq!(ToneMap::reinhard_extended(-1.0), None);
+        assert_eq!(ToneMap::reinhard_extended(Float::NAN), None);
+        assert_eq!(ToneMap::reinhard_extended(Float::INFINITY), None);
+
+        for white in [0.0, -2.0, Float::NAN].iter() {
+            let c = ToneMap::ReinhardExtended { white: *white }.apply(Color::new(0.5, 0.5, 0.5));
+            assert!(c.red.is_finite() && c.green.is_finite() && c.blue.is_finite());
+        }
+    }
+
+    #[test]
+    fn aces_filmic_curve() {
+        let c = ToneMap::AcesFilmic.apply(Color::new(0.0, 0.18, 100.0));
+        assert_eq!(c.red, 0.0);
+        assert!(float_eq(c.green, 0.26698));
+        assert_eq!(c.blue, 1.0);
+    }
+
+    #[test]
+    fn srgb_transfer_round_trips() {
+        assert!(float_eq(srgb_encode(0.5), 0.73536));
+        assert!(float_eq(srgb_encode(0.001), 0.01292));
+        for &v in &[0.0, 0.002, 0.2, 0.5, 1.0] {
+            assert!(float_eq(srgb_decode(srgb_encode(v)), v));
+        }
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let mut p = PostProcess::new();
+        p.exposure = 1.0;
+        assert_eq!(
+            p.apply(Color::new(0.25, 0.1, 0.4)),
+            Color::new(0.5, 0.2, 0.8)
+        );
+    }
+
+    #[test]
+    fn quantizing_without_dither_rounds() {
+        let p = PostProcess::new();
+        assert_eq!(p.quantize(Color::new(0.5, 1.5, -1.0), 0, 0), (128, 255, 0));
+    }
+
+    #[test]
+    fn dither_stays_within_one_step() {
+        let mut p = PostProcess::new();
+        p.dither = true;
+        for x in 0..64 {
+            let (r, _, _) = p.quantize(Color::new(0.5, 0.5, 0.5), x, 0);
+            assert!((127..=129).contains(&r));
+        }
+    }
+}
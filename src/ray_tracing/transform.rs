@@ -1,8 +1,10 @@
-use crate::{Matrix, Point, Vector};
+use super::matrix::MatrixError;
+use super::quaternion::Quaternion;
+use crate::{Float, Matrix, Point, Vector};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Transform {
-    data: [[f64; 4]; 4],
+    data: [[Float; 4]; 4],
 }
 
 impl Transform {
@@ -17,7 +19,7 @@ impl Transform {
         }
     }
 
-    pub fn translation(&self, x: f64, y: f64, z: f64) -> Transform {
+    pub fn translation(&self, x: Float, y: Float, z: Float) -> Transform {
         let m = [
             [1.0, 0.0, 0.0, x],
             [0.0, 1.0, 0.0, y],
@@ -29,7 +31,7 @@ impl Transform {
         }
     }
 
-    pub fn scaling(&self, x: f64, y: f64, z: f64) -> Transform {
+    pub fn scaling(&self, x: Float, y: Float, z: Float) -> Transform {
         let m = [
             [x, 0.0, 0.0, 0.0],
             [0.0, y, 0.0, 0.0],
@@ -41,7 +43,7 @@ impl Transform {
         }
     }
 
-    pub fn rotation_x(&self, r: f64) -> Transform {
+    pub fn rotation_x(&self, r: Float) -> Transform {
         let m = [
             [1.0, 0.0, 0.0, 0.0],
             [0.0, r.cos(), -r.sin(), 0.0],
@@ -53,7 +55,7 @@ impl Transform {
         }
     }
 
-    pub fn rotation_y(&self, r: f64) -> Transform {
+    pub fn rotation_y(&self, r: Float) -> Transform {
         let m = [
             [r.cos(), 0.0, r.sin(), 0.0],
             [0.0, 1.0, 0.0, 0.0],
@@ -65,7 +67,7 @@ impl Transform {
         }
     }
 
-    pub fn rotation_z(&self, r: f64) -> Transform {
+    pub fn rotation_z(&self, r: Float) -> Transform {
         let m = [
             [r.cos(), -(r.sin()), 0.0, 0.0],
             [r.sin(), r.cos(), 0.0, 0.0],
@@ -77,7 +79,22 @@ impl Transform {
         }
     }
 
-    pub fn shearing(self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Transform {
+    pub fn rotation(&self, q: Quaternion) -> Transform {
+        let r = q.to_matrix();
+        Transform {
+            data: Matrix::multiple([r[0], r[1], r[2], r[3]], self.data),
+        }
+    }
+
+    pub fn shearing(
+        self,
+        xy: Float,
+        xz: Float,
+        yx: Float,
+        yz: Float,
+        zx: Float,
+        zy: Float,
+    ) -> Transform {
         let m = [
             [1.0, xy, xz, 0.0],
             [yx, 1.0, yz, 0.0],
@@ -89,6 +106,32 @@ impl Transform {
         }
     }
 
+    /// Rotates around `pivot` instead of the origin.
+    pub fn rotation_about(&self, q: Quaternion, pivot: Point) -> Transform {
+        self.translation(-pivot.x, -pivot.y, -pivot.z)
+            .rotation(q)
+            .translation(pivot.x, pivot.y, pivot.z)
+    }
+
+    /// Scales away from `pivot` instead of the origin, `pivot` stays put.
+    pub fn scaling_about(&self, x: Float, y: Float, z: Float, pivot: Point) -> Transform {
+        self.translation(-pivot.x, -pivot.y, -pivot.z)
+            .scaling(x, y, z)
+            .translation(pivot.x, pivot.y, pivot.z)
+    }
+
+    /// Applies the operations of `other` after the ones of `self`.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            data: Matrix::multiple(other.data, self.data),
+        }
+    }
+
+    /// Applies the operations of `other` before the ones of `self`.
+    pub fn before(&self, other: &Transform) -> Transform {
+        other.then(self)
+    }
+
     pub fn view_transformation(from: Point, to: Point, up: Vector) -> Matrix {
         let forward = (to - from).normalize();
         let upn = up.normalize();
@@ -107,13 +150,33 @@ impl Transform {
     pub fn build(&self) -> Matrix {
         Matrix::new(self.data)
     }
+
+    /// Like `build`, but fails for degenerate transforms such as a zero
+    /// scaling instead of producing a matrix with a NaN inverse.
+    pub fn try_build(&self) -> Result<Matrix, MatrixError> {
+        Matrix::try_new(self.data)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Matrix> for Transform {
+    fn from(m: Matrix) -> Self {
+        Transform {
+            data: [m[0], m[1], m[2], m[3]],
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Transform;
-    use crate::{Matrix, Point, Vector};
-    use std::f64::consts::PI;
+    use super::{MatrixError, Transform};
+    use crate::consts::PI;
+    use crate::{Float, Matrix, Point, Vector};
 
     #[test]
     fn translation_matrix() {
@@ -182,7 +245,7 @@ mod tests {
 
     #[test]
     fn rotating_point_around_x() {
-        let t: f64 = 2.0;
+        let t: Float = 2.0;
         let p = Point::new(0.0, 1.0, 0.0);
         let half_quarter = Transform::new().rotation_x(PI / 4.0).build();
         let full_quarter = Transform::new().rotation_x(PI / 2.0).build();
@@ -196,7 +259,7 @@ mod tests {
 
     #[test]
     fn rotating_inverse_point_around_x() {
-        let t: f64 = 2.0;
+        let t: Float = 2.0;
         let p = Point::new(0.0, 1.0, 0.0);
         let half_quarter = Transform::new().rotation_x(PI / 4.0).build();
         let inv = half_quarter.inverse();
@@ -207,7 +270,7 @@ mod tests {
 
     #[test]
     fn rotating_point_around_y() {
-        let t: f64 = 2.0;
+        let t: Float = 2.0;
         let p = Point::new(0.0, 0.0, 1.0);
         let half_quarter = Transform::new().rotation_y(PI / 4.0).build();
         let full_quarter = Transform::new().rotation_y(PI / 2.0).build();
@@ -221,7 +284,7 @@ mod tests {
 
     #[test]
     fn rotating_point_around_z() {
-        let t: f64 = 2.0;
+        let t: Float = 2.0;
         let p = Point::new(0.0, 1.0, 0.0);
         let half_quarter = Transform::new().rotation_z(PI / 4.0).build();
         let full_quarter = Transform::new().rotation_z(PI / 2.0).build();
@@ -374,4 +437,57 @@ mod tests {
 
         assert_eq!(t, e);
     }
+
+    #[test]
+    fn building_a_degenerate_transform() {
+        let t = Transform::new().scaling(0.0, 1.0, 1.0);
+        assert_eq!(t.try_build(), Err(MatrixError::Singular));
+        assert!(t.build().is_singular());
+        assert!(Transform::new()
+            .translation(1.0, 2.0, 3.0)
+            .try_build()
+            .is_ok());
+    }
+
+    #[test]
+    fn composing_transforms() {
+        let rotate = Transform::new().rotation_x(PI / 2.0);
+        let scale = Transform::new().scaling(5.0, 5.0, 5.0);
+        let translate = Transform::new().translation(10.0, 5.0, 7.0);
+        let p = Point::new(1.0, 0.0, 1.0);
+
+        let expected = Point::new(15.0, 0.0, 7.0);
+        assert_eq!(rotate.then(&scale).then(&translate).build() * p, expected);
+        assert_eq!(
+            translate.before(&scale).before(&rotate).build() * p,
+            expected
+        );
+    }
+
+    #[test]
+    fn transforms_from_matrices() {
+        let m = Transform::new().scaling(2.0, 2.0, 2.0).build();
+        let t = Transform::from(m).translation(1.0, 0.0, 0.0);
+        assert_eq!(
+            t.build() * Point::new(1.0, 1.0, 1.0),
+            Point::new(3.0, 2.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn rotating_about_a_pivot() {
+        let q = crate::Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), PI / 2.0);
+        let pivot = Point::new(1.0, 0.0, 1.0);
+        let t = Transform::new().rotation_about(q, pivot).build();
+        assert_eq!(t * pivot, pivot);
+        assert_eq!(t * Point::new(1.0, 0.0, 2.0), Point::new(2.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn scaling_about_a_pivot() {
+        let pivot = Point::new(1.0, 2.0, 3.0);
+        let t = Transform::new().scaling_about(2.0, 2.0, 2.0, pivot).build();
+        assert_eq!(t * pivot, pivot);
+        assert_eq!(t * Point::new(2.0, 2.0, 3.0), Point::new(3.0, 2.0, 3.0));
+    }
 }
@@ -0,0 +1,66 @@
+/// Componentwise helpers shared by the `x`, `y`, `z` tuple types.
+macro_rules! componentwise {
+    ($t:ty) => {
+        impl $t {
+            /// Linear interpolation, `t` of 0.0 gives `self` and 1.0 gives `other`.
+            pub fn lerp(self, other: Self, t: $crate::Float) -> Self {
+                Self {
+                    x: self.x + (other.x - self.x) * t,
+                    y: self.y + (other.y - self.y) * t,
+                    z: self.z + (other.z - self.z) * t,
+                }
+            }
+
+            /// Componentwise minimum.
+            pub fn min(self, other: Self) -> Self {
+                Self {
+                    x: self.x.min(other.x),
+                    y: self.y.min(other.y),
+                    z: self.z.min(other.z),
+                }
+            }
+
+            /// Componentwise maximum.
+            pub fn max(self, other: Self) -> Self {
+                Self {
+                    x: self.x.max(other.x),
+                    y: self.y.max(other.y),
+                    z: self.z.max(other.z),
+                }
+            }
+
+            pub fn abs(self) -> Self {
+                Self {
+                    x: self.x.abs(),
+                    y: self.y.abs(),
+                    z: self.z.abs(),
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use componentwise;
+
+#[cfg(test)]
+mod tests {
+    use crate::{Point, Vector};
+
+    #[test]
+    fn lerp_between_two_points() {
+        let a = Point::new(0.0, 2.0, -4.0);
+        let b = Point::new(1.0, 4.0, 4.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.25), Point::new(0.25, 2.5, -2.0));
+    }
+
+    #[test]
+    fn componentwise_min_max_and_abs() {
+        let a = Vector::new(1.0, -5.0, 3.0);
+        let b = Vector::new(-2.0, 4.0, 3.0);
+        assert_eq!(a.min(b), Vector::new(-2.0, -5.0, 3.0));
+        assert_eq!(a.max(b), Vector::new(1.0, 4.0, 3.0));
+        assert_eq!(a.abs(), Vector::new(1.0, 5.0, 3.0));
+    }
+}
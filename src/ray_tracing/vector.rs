@@ -1,42 +1,37 @@
-use crate::float_eq;
+use super::simd;
+use super::tuple::componentwise;
+use crate::{float_eq, Float};
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 impl Vector {
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Vector { x, y, z }
     }
 
-    pub fn magnitude(self) -> f64 {
-        (self.x.powf(2.0) + self.y.powf(2.0) + self.z.powf(2.0)).sqrt()
+    pub fn magnitude(self) -> Float {
+        self.dot(self).sqrt()
     }
 
     pub fn normalize(self) -> Self {
-        let mag = self.magnitude();
-        Self {
-            x: self.x / mag,
-            y: self.y / mag,
-            z: self.z / mag,
-        }
+        let [x, y, z] = simd::div([self.x, self.y, self.z], self.magnitude());
+        Self { x, y, z }
     }
 
-    pub fn dot(self, b: Vector) -> f64 {
-        self.x * b.x + self.y * b.y + self.z * b.z
+    pub fn dot(self, b: Vector) -> Float {
+        simd::dot([self.x, self.y, self.z], [b.x, b.y, b.z])
     }
 
-    // a: Vector,
     pub fn cross(self, b: Vector) -> Vector {
-        Vector {
-            x: self.y * b.z - self.z * b.y,
-            y: self.z * b.x - self.x * b.z,
-            z: self.x * b.y - self.y * b.x,
-        }
+        let [x, y, z] = simd::cross([self.x, self.y, self.z], [b.x, b.y, b.z]);
+        Vector { x, y, z }
     }
 
     pub fn reflect(self, normal: Vector) -> Vector {
@@ -44,6 +39,8 @@ impl Vector {
     }
 }
 
+componentwise!(Vector);
+
 impl Sub for Vector {
     type Output = Self;
 
@@ -68,10 +65,10 @@ impl Add for Vector {
     }
 }
 
-impl Mul<f64> for Vector {
+impl Mul<Float> for Vector {
     type Output = Self;
 
-    fn mul(self, other: f64) -> Self {
+    fn mul(self, other: Float) -> Self {
         Self {
             x: self.x * other,
             y: self.y * other,
@@ -80,7 +77,7 @@ impl Mul<f64> for Vector {
     }
 }
 
-impl Mul<Vector> for f64 {
+impl Mul<Vector> for Float {
     type Output = Vector;
 
     fn mul(self, other: Vector) -> Vector {
@@ -92,10 +89,10 @@ impl Mul<Vector> for f64 {
     }
 }
 
-impl Div<f64> for Vector {
+impl Div<Float> for Vector {
     type Output = Self;
 
-    fn div(self, other: f64) -> Self {
+    fn div(self, other: Float) -> Self {
         Self {
             x: self.x / other,
             y: self.y / other,
@@ -230,7 +227,7 @@ mod tests {
     fn compute_magnitude_for_vector_1_2_3() {
         let v = Vector::new(1.0, 2.0, 3.0);
         let results = v.magnitude();
-        let expected: f64 = 14.0;
+        let expected: Float = 14.0;
         assert_eq!(expected.sqrt(), results);
     }
 
@@ -238,7 +235,7 @@ mod tests {
     fn compute_magnitude_for_vector_neg_1_2_3() {
         let v = Vector::new(-1.0, -2.0, -3.0);
         let results = v.magnitude();
-        let expected: f64 = 14.0;
+        let expected: Float = 14.0;
         assert_eq!(expected.sqrt(), results);
     }
 
@@ -263,7 +260,7 @@ mod tests {
         let v = Vector::new(1.0, 2.0, 3.0);
         let norm = v.normalize();
         let results = norm.magnitude();
-        assert_eq!(1.0, results);
+        assert!(float_eq(1.0, results));
     }
 
     #[test]
@@ -297,7 +294,7 @@ mod tests {
     #[test]
     fn reflecting_a_vector_slant() {
         let v = Vector::new(0.0, -1.0, 0.0);
-        let n = Vector::new(2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0, 0.0);
+        let n = Vector::new(Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0, 0.0);
         let r = v.reflect(n);
         assert_eq!(Vector::new(1.0, 0.0, 0.0), r);
     }
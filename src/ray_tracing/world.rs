@@ -1,11 +1,17 @@
+use super::aov::AovSample;
 use super::color;
+use super::packet::RayPacket;
+use super::shape_container::HierarchyError;
 use crate::{
     shapes::{Shape, Sphere},
-    Color, Computations, Intersection, Point, PointLight, Ray, ShapeContainer, Transform,
+    Color, Computations, Intersection, Matrix, Point, PointLight, Ray, ShapeContainer, Transform,
 };
+use std::error::Error;
+use std::fmt;
 use uuid::Uuid;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct World {
     pub lights: Vec<PointLight>,
     shapes: ShapeContainer,
@@ -19,8 +25,51 @@ impl World {
         }
     }
 
-    pub fn add_shape(&mut self, shape: Box<dyn Shape>) {
-        self.shapes.add_shape(shape);
+    /// Adds the shape unless it, or a shape in it, has a transform that
+    /// cannot be inverted or an id that is already taken, or it already
+    /// belongs to a group.
+    pub fn add_shape(&mut self, shape: Box<dyn Shape>) -> Result<(), AddShapeError> {
+        self.try_add_shape(shape).map_err(|(e, _)| e)
+    }
+
+    /// Like `add_shape`, but a rejected shape is handed back with the error.
+    pub fn try_add_shape(
+        &mut self,
+        shape: Box<dyn Shape>,
+    ) -> Result<(), (AddShapeError, Box<dyn Shape>)> {
+        match self.check_new_shape(shape.as_ref(), None) {
+            Ok(()) => {
+                self.shapes.add_shape(shape);
+                Ok(())
+            }
+            Err(e) => Err((e, shape)),
+        }
+    }
+
+    fn check_new_shape(
+        &self,
+        shape: &dyn Shape,
+        replacing: Option<Uuid>,
+    ) -> Result<(), AddShapeError> {
+        if shape.parent_id().is_some() {
+            return Err(HierarchyError::AlreadyParented(shape.id()).into());
+        }
+        if let Some(id) = self.shapes.duplicate_id(shape, replacing) {
+            return Err(HierarchyError::DuplicateId(id).into());
+        }
+        if let Some(id) = singular_shape(shape) {
+            return Err(SingularTransform { shape: id }.into());
+        }
+        Ok(())
+    }
+
+    /// Checks every transform again, they can be changed after the shapes
+    /// were added.
+    pub fn validate(&self) -> Result<(), SingularTransform> {
+        match self.shapes.all().into_iter().find_map(singular_shape) {
+            Some(id) => Err(SingularTransform { shape: id }),
+            None => Ok(()),
+        }
     }
 
     pub fn get_shape(&self, id: Uuid) -> Option<&dyn Shape> {
@@ -30,12 +79,9 @@ impl World {
                 shape = Some(s);
                 break;
             }
-            match s.get_child(id) {
-                Some(c) => {
-                    shape = Some(c);
-                    break;
-                }
-                None => (),
+            if let Some(c) = s.get_child(id) {
+                shape = Some(c);
+                break;
             }
         }
 
@@ -46,7 +92,63 @@ impl World {
         self.shapes.shape_at(i)
     }
 
-    pub fn intersect(&self, r: Ray) -> Option<Vec<Intersection>> {
+    /// Finds a shape by id, searching inside groups.
+    pub fn get_shape_mut(&mut self, id: Uuid) -> Option<&mut dyn Shape> {
+        self.shapes.get_shape_mut(id)
+    }
+
+    pub fn remove_shape(&mut self, id: Uuid) -> Option<Box<dyn Shape>> {
+        self.shapes.remove_shape(id)
+    }
+
+    /// Swaps in `shape` where the shape with `id` was and returns the old
+    /// one. `shape` is checked like in `add_shape`.
+    pub fn replace_shape(
+        &mut self,
+        id: Uuid,
+        shape: Box<dyn Shape>,
+    ) -> Result<Box<dyn Shape>, AddShapeError> {
+        if self.get_shape(id).is_none() {
+            return Err(HierarchyError::NotFound(id).into());
+        }
+        self.check_new_shape(shape.as_ref(), Some(id))?;
+        Ok(self.shapes.replace_shape(id, shape)?)
+    }
+
+    /// Moves a shape into a group, or to the top level with `None`.
+    pub fn reparent(&mut self, id: Uuid, parent: Option<Uuid>) -> Result<(), HierarchyError> {
+        self.shapes.reparent(id, parent)
+    }
+
+    pub fn get_shape_by_name(&self, name: &str) -> Option<&dyn Shape> {
+        self.shapes.get_shape_by_name(name)
+    }
+
+    /// Looks a shape up by the names of its groups and itself,
+    /// e.g. `"room/table/leg1"`.
+    pub fn get_shape_by_path(&self, path: &str) -> Option<&dyn Shape> {
+        self.shapes.get_shape_by_path(path)
+    }
+
+    pub fn path_of(&self, id: Uuid) -> Option<String> {
+        self.shapes.path_of(id)
+    }
+
+    /// Shapes whose path matches a glob like `"room/**/leg*"`.
+    pub fn find_shapes(&self, pattern: &str) -> Vec<&dyn Shape> {
+        self.shapes.find_shapes(pattern)
+    }
+
+    pub fn shapes_tagged(&self, tag: &str) -> Vec<&dyn Shape> {
+        self.shapes.shapes_tagged(tag)
+    }
+
+    /// Every shape, groups included, with its object to world transform.
+    pub fn all_with_transforms(&self) -> impl Iterator<Item = (&dyn Shape, Matrix)> + '_ {
+        self.shapes.all_with_transforms()
+    }
+
+    pub fn intersect(&self, r: Ray) -> Option<Vec<Intersection<'_>>> {
         let mut xs: Vec<Intersection> = Vec::new();
         for o in self.shapes.all() {
             if let Some(o_xs) = o.intersect(r) {
@@ -63,7 +165,39 @@ impl World {
         }
     }
 
+    /// Intersects every ray of the packet with the world, the results are
+    /// the same as calling `intersect` for each ray. The packet is moved
+    /// into the space of each shape once instead of ray by ray.
+    pub fn intersect_packet(&self, packet: &RayPacket) -> Vec<Option<Vec<Intersection<'_>>>> {
+        let mut xs: Vec<Vec<Intersection>> = vec![Vec::new(); packet.len()];
+        for o in self.shapes.all() {
+            let local = packet.transform(&o.transform().inverse());
+            for (i, ray) in local.rays().enumerate() {
+                if let Some(o_xs) = o.local_intersect(ray) {
+                    xs[i].extend(o_xs);
+                }
+            }
+        }
+
+        xs.into_iter()
+            .map(|mut xs| {
+                if xs.is_empty() {
+                    None
+                } else {
+                    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    Some(xs)
+                }
+            })
+            .collect()
+    }
+
     pub fn shade_hit(&self, comps: &Computations, remaining: usize) -> Color {
+        self.shade_hit_components(comps, remaining).color()
+    }
+
+    /// Shades a hit like `shade_hit` but keeps the direct lighting and the
+    /// reflected and refracted light apart.
+    pub fn shade_hit_components(&self, comps: &Computations, remaining: usize) -> Shading {
         let in_shadow = self.is_shadow(comps.over_point);
 
         let material = comps.object.material();
@@ -80,11 +214,18 @@ impl World {
         let reflected = self.reflected_color(comps, remaining);
         let refracted = self.refracted_color(comps, remaining);
 
-        if material.reflective > 0.0 && material.transparency > 0.0 {
+        let (reflected, refracted) = if material.reflective > 0.0 && material.transparency > 0.0 {
             let reflectance = comps.schlick();
-            surface + reflected * reflectance + refracted * (1.0 - reflectance)
+            (reflected * reflectance, refracted * (1.0 - reflectance))
         } else {
-            surface + reflected + refracted
+            (reflected, refracted)
+        };
+
+        Shading {
+            surface,
+            reflected,
+            refracted,
+            in_shadow,
         }
     }
 
@@ -101,6 +242,29 @@ impl World {
         }
     }
 
+    /// Auxiliary values of the first surface hit by the ray, `None` when the
+    /// ray escapes the world.
+    pub fn aov_at(&self, r: Ray, remaining: usize) -> Option<AovSample> {
+        let xs = self.intersect(r)?;
+        let hit = Intersection::hit(&xs)?;
+        let comps = Intersection::prepare_computations(&hit, r, &xs, Some(&self.shapes));
+        let shading = self.shade_hit_components(&comps, remaining);
+        let material = comps.object.material();
+
+        Some(AovSample {
+            color: shading.color(),
+            direct: shading.surface,
+            depth: comps.t,
+            normal: comps.normalv,
+            albedo: material.color_at(comps.object, comps.point),
+            object_id: comps.object.id(),
+            material_id: material.id(),
+            in_shadow: shading.in_shadow,
+            reflection: shading.reflected,
+            refraction: shading.refracted,
+        })
+    }
+
     pub fn is_shadow(&self, point: Point) -> bool {
         let v = self.lights[0].position - point;
         let distance = v.magnitude();
@@ -148,6 +312,80 @@ impl World {
     }
 }
 
+/// Light arriving at the eye from a single hit, see
+/// `World::shade_hit_components`. Reflected and refracted light is already
+/// weighted by the Fresnel term.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shading {
+    pub surface: Color,
+    pub reflected: Color,
+    pub refracted: Color,
+    pub in_shadow: bool,
+}
+
+impl Shading {
+    pub fn color(&self) -> Color {
+        self.surface + self.reflected + self.refracted
+    }
+}
+
+/// A shape's transform has no inverse, rays can not be brought into its
+/// object space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SingularTransform {
+    pub shape: Uuid,
+}
+
+impl fmt::Display for SingularTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "shape {} has a non-invertible transform", self.shape)
+    }
+}
+
+impl Error for SingularTransform {}
+
+/// Why `World::add_shape` refused a shape.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AddShapeError {
+    /// The shape, or a shape in it, can not be inverted.
+    Singular(SingularTransform),
+    /// Shapes in a group are added with the group.
+    Hierarchy(HierarchyError),
+}
+
+impl fmt::Display for AddShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddShapeError::Singular(e) => write!(f, "{}", e),
+            AddShapeError::Hierarchy(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for AddShapeError {}
+
+impl From<SingularTransform> for AddShapeError {
+    fn from(e: SingularTransform) -> Self {
+        AddShapeError::Singular(e)
+    }
+}
+
+impl From<HierarchyError> for AddShapeError {
+    fn from(e: HierarchyError) -> Self {
+        AddShapeError::Hierarchy(e)
+    }
+}
+
+fn singular_shape(shape: &dyn Shape) -> Option<Uuid> {
+    if shape.transform().is_singular() {
+        return Some(shape.id());
+    }
+    shape
+        .children()
+        .iter()
+        .find_map(|s| singular_shape(s.as_ref()))
+}
+
 impl Default for World {
     fn default() -> Self {
         let mut w = World::new();
@@ -159,11 +397,11 @@ impl Default for World {
         s1.material.color = Color::new(0.8, 1.0, 0.6);
         s1.material.diffuse = 0.7;
         s1.material.specular = 0.2;
-        w.add_shape(Box::new(s1));
+        w.add_shape(Box::new(s1)).unwrap();
 
         let mut s2 = Sphere::new();
         s2.transform = Transform::new().scaling(0.5, 0.5, 0.5).build();
-        w.add_shape(Box::new(s2));
+        w.add_shape(Box::new(s2)).unwrap();
 
         w
     }
@@ -172,9 +410,10 @@ impl Default for World {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Float;
     use crate::{
-        patterns::TestPattern, shapes::Plane, shapes::Sphere, Color, Intersection, Material, Point,
-        PointLight, Ray, Transform, Vector,
+        patterns::TestPattern, shapes::Group, shapes::Plane, shapes::Sphere, Color, Intersection,
+        Material, Normal, Point, PointLight, Ray, Transform, Vector,
     };
 
     #[test]
@@ -220,7 +459,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let shape = w.get_shape_at(0);
         let i = Intersection::new(4.0, shape);
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let c = w.shade_hit(&comps, 5);
         assert_eq!(Color::new(0.38066, 0.47583, 0.2855), c);
     }
@@ -260,15 +499,15 @@ mod tests {
         w.lights.push(light);
 
         let s1 = Sphere::new();
-        w.add_shape(Box::new(s1));
+        w.add_shape(Box::new(s1)).unwrap();
 
         let mut s2 = Sphere::new();
         s2.transform = Transform::new().translation(0.0, 0.0, 10.0).build();
-        w.add_shape(Box::new(s2));
+        w.add_shape(Box::new(s2)).unwrap();
 
         let r = Ray::new(Point::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 1.0));
         let i = Intersection::new(4.0, w.get_shape_at(1));
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let c = w.shade_hit(&comps, 5);
         assert_eq!(c, Color::new(0.1, 0.1, 0.1));
     }
@@ -279,7 +518,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         w.shapes.shape_at_as_mut(0).material_mut().ambient = 1.0;
         let i = Intersection::new(1.0, w.get_shape_at(1));
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let color = w.reflected_color(&comps, 5);
         assert_eq!(color, color::BLACK);
     }
@@ -290,13 +529,13 @@ mod tests {
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
         shape.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
-        w.add_shape(Box::new(shape));
+        w.add_shape(Box::new(shape)).unwrap();
         let r = Ray::new(
             Point::new(0.0, 0.0, -3.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
-        let i = Intersection::new(2_f64.sqrt(), w.get_shape_at(2));
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let i = Intersection::new(Float::sqrt(2.0), w.get_shape_at(2));
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let color = w.reflected_color(&comps, 1);
         assert_eq!(color, Color::new(0.190332, 0.237915, 0.1427492));
     }
@@ -307,15 +546,15 @@ mod tests {
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
         shape.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
-        w.add_shape(Box::new(shape));
+        w.add_shape(Box::new(shape)).unwrap();
         let r = Ray::new(
             Point::new(0.0, 0.0, -3.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
-        let i = Intersection::new(2_f64.sqrt(), w.get_shape_at(2));
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let i = Intersection::new(Float::sqrt(2.0), w.get_shape_at(2));
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let color = w.shade_hit(&comps, 1);
-        assert_eq!(color, Color::new(0.8767577, 0.924340789, 0.829174629));
+        assert_eq!(color, Color::new(0.876_757_7, 0.924_340_8, 0.829_174_6));
     }
 
     #[test]
@@ -328,11 +567,11 @@ mod tests {
         let mut lower = Plane::new();
         lower.material.reflective = 1.0;
         lower.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
-        w.add_shape(Box::new(lower));
+        w.add_shape(Box::new(lower)).unwrap();
         let mut upper = Plane::new();
         upper.material.reflective = 1.0;
         upper.transform = Transform::new().translation(0.0, 1.0, 0.0).build();
-        w.add_shape(Box::new(upper));
+        w.add_shape(Box::new(upper)).unwrap();
         let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
         w.color_at(r, 5);
     }
@@ -343,13 +582,13 @@ mod tests {
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
         shape.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
-        w.add_shape(Box::new(shape));
+        w.add_shape(Box::new(shape)).unwrap();
         let r = Ray::new(
             Point::new(0.0, 0.0, -3.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
-        let i = Intersection::new(2_f64.sqrt(), w.get_shape_at(1));
-        let comps = Intersection::prepare_computations(&i, r, &vec![i], None);
+        let i = Intersection::new(Float::sqrt(2.0), w.get_shape_at(1));
+        let comps = Intersection::prepare_computations(&i, r, &[i], None);
         let color = w.reflected_color(&comps, 0);
         assert_eq!(color, color::BLACK);
     }
@@ -363,7 +602,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let i1 = Intersection::new(4.0, shape);
         let i2 = Intersection::new(6.0, shape);
-        let xs = vec![i1.clone(), i2.clone()];
+        let xs = vec![i1, i2];
         let comps = Intersection::prepare_computations(&i1, r, &xs, None);
         let c = w.refracted_color(&comps, 5);
         assert_eq!(c, color::BLACK);
@@ -383,7 +622,7 @@ mod tests {
         let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let i1 = Intersection::new(4.0, w.get_shape_at(0));
         let i2 = Intersection::new(6.0, w.get_shape_at(0));
-        let xs = vec![i1.clone(), i2.clone()];
+        let xs = vec![i1, i2];
         let comps = Intersection::prepare_computations(&i1, r, &xs, None);
         let c = w.refracted_color(&comps, 0);
         assert_eq!(c, color::BLACK);
@@ -401,12 +640,12 @@ mod tests {
         w.shapes.shape_at_as_mut(0).set_material(m);
 
         let r = Ray::new(
-            Point::new(0.0, 0.0, 2_f64.sqrt() / 2.0),
+            Point::new(0.0, 0.0, Float::sqrt(2.0) / 2.0),
             Vector::new(0.0, 1.0, 0.0),
         );
-        let i1 = Intersection::new(-2_f64.sqrt() / 2.0, w.get_shape_at(0));
-        let i2 = Intersection::new(2_f64.sqrt() / 2.0, w.get_shape_at(0));
-        let xs = vec![i1.clone(), i2.clone()];
+        let i1 = Intersection::new(-Float::sqrt(2.0) / 2.0, w.get_shape_at(0));
+        let i2 = Intersection::new(Float::sqrt(2.0) / 2.0, w.get_shape_at(0));
+        let xs = vec![i1, i2];
         let comps = Intersection::prepare_computations(&i2, r, &xs, None);
         let c = w.refracted_color(&comps, 5);
         assert_eq!(c, color::BLACK);
@@ -433,10 +672,16 @@ mod tests {
         let i2 = Intersection::new(-0.4899, w.get_shape_at(1));
         let i3 = Intersection::new(0.4899, w.get_shape_at(1));
         let i4 = Intersection::new(0.9899, w.get_shape_at(0));
-        let xs = vec![i1.clone(), i2.clone(), i3.clone(), i4.clone()];
+        let xs = vec![i1, i2, i3, i4];
         let comps = Intersection::prepare_computations(&i3, r, &xs, None);
         let c = w.refracted_color(&comps, 5);
-        assert_eq!(c, Color::new(0.0, 0.99888, 0.04725));
+        // the test pattern shows where the under point is, it sits further
+        // below the surface with the larger f32 epsilon
+        if cfg!(feature = "f32") {
+            assert_eq!(c, Color::new(0.0, 0.99787, 0.04747));
+        } else {
+            assert_eq!(c, Color::new(0.0, 0.99888, 0.04725));
+        }
     }
 
     // Chapter 11 Reflection and Refraction
@@ -449,21 +694,21 @@ mod tests {
         floor.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
         floor.material.transparency = 0.5;
         floor.material.refractive_index = 1.5;
-        w.add_shape(Box::new(floor));
+        w.add_shape(Box::new(floor)).unwrap();
 
         let mut ball = Sphere::new();
         ball.material.color = Color::new(1.0, 0.0, 0.0);
         ball.material.ambient = 0.5;
         ball.transform = Transform::new().translation(0.0, -3.5, -0.5).build();
-        w.add_shape(Box::new(ball));
+        w.add_shape(Box::new(ball)).unwrap();
 
         let r = Ray::new(
             Point::new(0.0, 0.0, -3.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
 
-        let i1 = Intersection::new(2_f64.sqrt(), w.get_shape_at(2));
-        let xs = vec![i1.clone()];
+        let i1 = Intersection::new(Float::sqrt(2.0), w.get_shape_at(2));
+        let xs = vec![i1];
 
         let comps = Intersection::prepare_computations(&i1, r, &xs, None);
         let c = w.shade_hit(&comps, 5);
@@ -481,24 +726,443 @@ mod tests {
         floor.material.reflective = 0.5;
         floor.material.transparency = 0.5;
         floor.material.refractive_index = 1.5;
-        w.add_shape(Box::new(floor));
+        w.add_shape(Box::new(floor)).unwrap();
 
         let mut ball = Sphere::new();
         ball.material.color = Color::new(1.0, 0.0, 0.0);
         ball.material.ambient = 0.5;
         ball.transform = Transform::new().translation(0.0, -3.5, -0.5).build();
-        w.add_shape(Box::new(ball));
+        w.add_shape(Box::new(ball)).unwrap();
 
         let r = Ray::new(
             Point::new(0.0, 0.0, -3.0),
-            Vector::new(0.0, -2_f64.sqrt() / 2.0, 2_f64.sqrt() / 2.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
         );
 
-        let i1 = Intersection::new(2_f64.sqrt(), w.get_shape_at(2));
-        let xs = vec![i1.clone()];
+        let i1 = Intersection::new(Float::sqrt(2.0), w.get_shape_at(2));
+        let xs = vec![i1];
 
         let comps = Intersection::prepare_computations(&i1, r, &xs, None);
         let c = w.shade_hit(&comps, 5);
         assert_eq!(c, Color::new(0.93391, 0.69643, 0.69243));
     }
+
+    #[test]
+    fn shading_components_add_up_to_shade_hit() {
+        let mut w = World::default();
+
+        let mut floor = Plane::new();
+        floor.transform = Transform::new().translation(0.0, -1.0, 0.0).build();
+        floor.material.transparency = 0.5;
+        floor.material.refractive_index = 1.5;
+        w.add_shape(Box::new(floor)).unwrap();
+
+        let mut ball = Sphere::new();
+        ball.material.color = Color::new(1.0, 0.0, 0.0);
+        ball.material.ambient = 0.5;
+        ball.transform = Transform::new().translation(0.0, -3.5, -0.5).build();
+        w.add_shape(Box::new(ball)).unwrap();
+
+        let r = Ray::new(
+            Point::new(0.0, 0.0, -3.0),
+            Vector::new(0.0, -Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0),
+        );
+        let i1 = Intersection::new(Float::sqrt(2.0), w.get_shape_at(2));
+        let xs = vec![i1];
+        let comps = Intersection::prepare_computations(&i1, r, &xs, None);
+
+        let shading = w.shade_hit_components(&comps, 5);
+        assert_eq!(shading.color(), w.shade_hit(&comps, 5));
+        assert_eq!(shading.reflected, color::BLACK);
+        assert_eq!(shading.refracted, Color::new(0.25, 0.0, 0.0));
+        assert!(!shading.in_shadow);
+    }
+
+    #[test]
+    fn auxiliary_values_of_a_hit() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let aov = w.aov_at(r, 5).unwrap();
+
+        assert_eq!(aov.color, w.color_at(r, 5));
+        assert_eq!(aov.depth, 4.0);
+        assert_eq!(aov.normal, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(aov.albedo, Color::new(0.8, 1.0, 0.6));
+        assert_eq!(aov.object_id, w.get_shape_at(0).id());
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(w.aov_at(r, 5).is_none());
+    }
+
+    #[test]
+    fn shapes_with_singular_transforms_are_rejected() {
+        let mut w = World::new();
+        let mut s = Sphere::new();
+        s.transform = Transform::new().scaling(0.0, 1.0, 1.0).build();
+        let id = s.id;
+        match w.try_add_shape(Box::new(s)) {
+            Err((e, shape)) => {
+                assert_eq!(e, AddShapeError::Singular(SingularTransform { shape: id }));
+                assert_eq!(shape.id(), id);
+            }
+            Ok(()) => panic!("a singular transform was accepted"),
+        }
+
+        let mut inner = Sphere::new();
+        inner.transform = Transform::new().scaling(1.0, 0.0, 1.0).build();
+        let inner_id = inner.id;
+        let mut g = Group::new();
+        g.add_shape(Box::new(inner)).unwrap();
+        assert_eq!(
+            w.add_shape(Box::new(g)),
+            Err(SingularTransform { shape: inner_id }.into())
+        );
+
+        assert!(w.add_shape(Box::new(Sphere::new())).is_ok());
+        assert_eq!(1, w.shapes.len());
+    }
+
+    #[test]
+    fn shapes_inside_a_group_are_not_added_again() {
+        let mut w = World::new();
+        let mut g = Group::new();
+        let s = Sphere::new();
+        let s_id = s.id;
+        g.add_shape(Box::new(s)).unwrap();
+        let child = g.remove_shape(s_id).unwrap();
+        let mut parented = Sphere::new();
+        parented.parent_id = Some(g.id);
+        let parented_id = parented.id;
+        w.add_shape(Box::new(g)).unwrap();
+        assert_eq!(
+            w.add_shape(Box::new(parented)),
+            Err(HierarchyError::AlreadyParented(parented_id).into())
+        );
+        assert!(w.add_shape(child).is_ok());
+        assert_eq!(2, w.shapes.len());
+    }
+
+    #[test]
+    fn shapes_with_ids_already_in_the_world_are_rejected() {
+        let (mut w, _, s_id) = nested_world();
+        let mut copy = Sphere::new();
+        copy.id = s_id;
+        assert_eq!(
+            w.add_shape(Box::new(copy)),
+            Err(HierarchyError::DuplicateId(s_id).into())
+        );
+
+        let mut g = Group::new();
+        let mut twin = Sphere::new();
+        twin.id = s_id;
+        g.add_shape(Box::new(twin)).unwrap();
+        assert_eq!(
+            w.add_shape(Box::new(g)),
+            Err(HierarchyError::DuplicateId(s_id).into())
+        );
+        assert_eq!(3, w.shapes.len());
+    }
+
+    #[test]
+    fn transforms_made_singular_later_are_found() {
+        let mut w = World::default();
+        assert_eq!(w.validate(), Ok(()));
+        let id = w.get_shape_at(1).id();
+        w.shapes
+            .shape_at_as_mut(1)
+            .set_transform(Transform::new().scaling(0.0, 0.0, 0.0).build());
+        assert_eq!(w.validate(), Err(SingularTransform { shape: id }));
+    }
+
+    #[test]
+    fn setting_a_singular_transform() {
+        let mut s = Sphere::new();
+        let singular = Transform::new().scaling(0.0, 0.0, 0.0).build();
+        assert_eq!(
+            s.try_set_transform(singular),
+            Err(crate::ray_tracing::matrix::MatrixError::Singular)
+        );
+        assert_eq!(s.transform, crate::ray_tracing::matrix::IDENTITY);
+    }
+
+    fn nested_world() -> (World, Uuid, Uuid) {
+        let mut w = World::default();
+        let mut g = Group::new();
+        g.transform = Transform::new().scaling(2.0, 2.0, 2.0).build();
+        let mut s = Sphere::new();
+        s.transform = Transform::new().translation(5.0, 0.0, 0.0).build();
+        let (g_id, s_id) = (g.id, s.id);
+        g.add_shape(Box::new(s)).unwrap();
+        w.add_shape(Box::new(g)).unwrap();
+        (w, g_id, s_id)
+    }
+
+    #[test]
+    fn mutating_a_shape_inside_a_group() {
+        let (mut w, _, s_id) = nested_world();
+        w.get_shape_mut(s_id).unwrap().material_mut().ambient = 1.0;
+        assert_eq!(w.get_shape(s_id).unwrap().material().ambient, 1.0);
+        assert!(w.get_shape_mut(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn removing_shapes() {
+        let (mut w, g_id, s_id) = nested_world();
+        let s = w.remove_shape(s_id).unwrap();
+        assert_eq!(s.id(), s_id);
+        assert_eq!(s.parent_id(), None);
+        assert!(w.get_shape(s_id).is_none());
+        assert!(w.get_shape(g_id).unwrap().children().is_empty());
+
+        let first = w.get_shape_at(0).id();
+        assert!(w.remove_shape(first).is_some());
+        assert_eq!(2, w.shapes.len());
+        assert!(w.remove_shape(first).is_none());
+    }
+
+    #[test]
+    fn replacing_a_shape_keeps_its_place_and_parent() {
+        let (mut w, g_id, s_id) = nested_world();
+        let cube = Box::new(crate::shapes::Cube::new());
+        let cube_id = cube.id();
+        let old = w.replace_shape(s_id, cube).unwrap();
+        assert_eq!(old.id(), s_id);
+        assert_eq!(w.get_shape(cube_id).unwrap().parent_id(), Some(g_id));
+        assert!(w.get_shape(s_id).is_none());
+
+        let first = w.get_shape_at(0).id();
+        let plane = Box::new(Plane::new());
+        let plane_id = plane.id();
+        w.replace_shape(first, plane).unwrap();
+        assert_eq!(w.get_shape_at(0).id(), plane_id);
+
+        assert_eq!(
+            w.replace_shape(first, Box::new(Sphere::new())).err(),
+            Some(HierarchyError::NotFound(first).into())
+        );
+    }
+
+    #[test]
+    fn reparenting_a_shape() {
+        let (mut w, g_id, s_id) = nested_world();
+        let first = w.get_shape_at(0).id();
+        w.reparent(first, Some(g_id)).unwrap();
+        assert_eq!(w.get_shape(first).unwrap().parent_id(), Some(g_id));
+        assert_eq!(w.get_shape(g_id).unwrap().children().len(), 2);
+        assert_eq!(2, w.shapes.len());
+
+        w.reparent(s_id, None).unwrap();
+        assert_eq!(w.get_shape(s_id).unwrap().parent_id(), None);
+        assert_eq!(3, w.shapes.len());
+
+        let inner = Group::new();
+        let inner_id = inner.id;
+        w.add_shape(Box::new(inner)).unwrap();
+        w.reparent(inner_id, Some(g_id)).unwrap();
+        assert_eq!(
+            w.reparent(g_id, Some(inner_id)),
+            Err(HierarchyError::Cycle(inner_id))
+        );
+        assert_eq!(
+            w.reparent(g_id, Some(g_id)),
+            Err(HierarchyError::Cycle(g_id))
+        );
+        assert_eq!(
+            w.reparent(g_id, Some(s_id)),
+            Err(HierarchyError::NotAGroup(s_id))
+        );
+        let missing = Uuid::new_v4();
+        assert_eq!(
+            w.reparent(missing, None),
+            Err(HierarchyError::NotFound(missing))
+        );
+    }
+
+    #[test]
+    fn replacing_with_an_id_already_in_the_world_is_refused() {
+        let (mut w, g_id, s_id) = nested_world();
+        let first = w.get_shape_at(0).id();
+        let mut copy = Sphere::new();
+        copy.id = s_id;
+        assert_eq!(
+            w.replace_shape(first, Box::new(copy)).err(),
+            Some(HierarchyError::DuplicateId(s_id).into())
+        );
+
+        let mut group = Group::new();
+        let mut child = Sphere::new();
+        child.id = first;
+        group.add_shape(Box::new(child)).unwrap();
+        assert_eq!(
+            w.replace_shape(s_id, Box::new(group)).err(),
+            Some(HierarchyError::DuplicateId(first).into())
+        );
+
+        let mut singular = Sphere::new();
+        singular.transform = Transform::new().scaling(0.0, 1.0, 1.0).build();
+        let singular_id = singular.id;
+        assert_eq!(
+            w.replace_shape(first, Box::new(singular)).err(),
+            Some(SingularTransform { shape: singular_id }.into())
+        );
+
+        let mut parented = Sphere::new();
+        parented.parent_id = Some(g_id);
+        let parented_id = parented.id;
+        assert_eq!(
+            w.replace_shape(first, Box::new(parented)).err(),
+            Some(HierarchyError::AlreadyParented(parented_id).into())
+        );
+        assert_eq!(w.get_shape_at(0).id(), first);
+
+        let mut edited = Sphere::new();
+        edited.id = s_id;
+        w.replace_shape(s_id, Box::new(edited)).unwrap();
+        assert_eq!(w.get_shape(s_id).unwrap().parent_id(), Some(g_id));
+    }
+
+    #[test]
+    fn reparenting_a_duplicated_id_is_refused() {
+        let (mut w, g_id, s_id) = nested_world();
+        let mut twin = Sphere::new();
+        twin.id = s_id;
+        w.shapes.add_shape(Box::new(twin));
+        assert_eq!(
+            w.reparent(s_id, Some(g_id)),
+            Err(HierarchyError::DuplicateId(s_id))
+        );
+    }
+
+    #[test]
+    fn reparented_shapes_use_their_new_parent_for_normals() {
+        let mut w = World::new();
+        let mut g = Group::new();
+        g.transform = Transform::new().scaling(1.0, 2.0, 3.0).build();
+        let g_id = g.id;
+        let s = Sphere::new();
+        let s_id = s.id;
+        w.add_shape(Box::new(g)).unwrap();
+        w.add_shape(Box::new(s)).unwrap();
+        w.reparent(s_id, Some(g_id)).unwrap();
+
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = w.intersect(r).unwrap();
+        assert!(crate::float_eq(xs[0].t, 2.0));
+        let n = xs[0]
+            .object
+            .normal_at(Point::new(0.0, 0.0, -3.0), Some(&w.shapes));
+        assert_eq!(n, Normal::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn iterating_shapes_with_world_transforms() {
+        let (w, g_id, s_id) = nested_world();
+        let all: Vec<_> = w.all_with_transforms().collect();
+        assert_eq!(all.len(), 4);
+        let (g, g_transform) = all[2];
+        assert_eq!(g.id(), g_id);
+        assert_eq!(g_transform, Transform::new().scaling(2.0, 2.0, 2.0).build());
+        let (s, s_transform) = all[3];
+        assert_eq!(s.id(), s_id);
+        assert_eq!(
+            s_transform,
+            Transform::new()
+                .translation(5.0, 0.0, 0.0)
+                .scaling(2.0, 2.0, 2.0)
+                .build()
+        );
+    }
+
+    fn room() -> (World, Uuid) {
+        let mut table = Group::new();
+        table.set_name("table").unwrap();
+        for i in 1..=4 {
+            let mut leg = crate::shapes::Cube::new();
+            leg.set_name(&format!("leg{}", i)).unwrap();
+            leg.add_tag("wood");
+            table.add_shape(Box::new(leg)).unwrap();
+        }
+        table.add_tag("wood");
+        let mut lamp = Sphere::new();
+        lamp.set_name("lamp").unwrap();
+        lamp.add_tag("light");
+        let mut room = Group::new();
+        room.set_name("room").unwrap();
+        room.add_shape(Box::new(table)).unwrap();
+        room.add_shape(Box::new(lamp)).unwrap();
+        let floor = Plane::new();
+        let floor_id = floor.id();
+
+        let mut w = World::new();
+        w.add_shape(Box::new(room)).unwrap();
+        w.add_shape(Box::new(floor)).unwrap();
+        (w, floor_id)
+    }
+
+    fn names<'a>(shapes: &[&'a dyn Shape]) -> Vec<&'a str> {
+        shapes.iter().map(|s| s.name().unwrap_or("")).collect()
+    }
+
+    #[test]
+    fn looking_up_shapes_by_name_and_path() {
+        let (w, floor_id) = room();
+        assert_eq!(w.get_shape_by_name("leg3").unwrap().name(), Some("leg3"));
+        assert!(w.get_shape_by_name("chair").is_none());
+
+        let leg = w.get_shape_by_path("room/table/leg1").unwrap();
+        assert_eq!(leg.name(), Some("leg1"));
+        assert_eq!(w.path_of(leg.id()).unwrap(), "room/table/leg1");
+        assert!(w.get_shape_by_path("room/leg1").is_none());
+        assert!(w.get_shape_by_path("table").is_none());
+
+        let floor_path = floor_id.to_string();
+        assert_eq!(w.path_of(floor_id).unwrap(), floor_path);
+        assert_eq!(w.get_shape_by_path(&floor_path).unwrap().id(), floor_id);
+    }
+
+    #[test]
+    fn a_path_leads_to_the_first_sibling_with_the_name() {
+        let (mut w, _) = room();
+        let mut spare = crate::shapes::Cube::new();
+        spare.set_name("leg1").unwrap();
+        let spare_id = spare.id();
+        let table = w.get_shape_by_path("room/table").unwrap().id();
+        w.add_shape(Box::new(spare)).unwrap();
+        w.reparent(spare_id, Some(table)).unwrap();
+
+        let leg = w.get_shape_by_path("room/table/leg1").unwrap();
+        assert_ne!(leg.id(), spare_id);
+        assert_eq!(w.find_shapes("room/table/leg1").len(), 2);
+    }
+
+    #[test]
+    fn querying_shapes_with_globs() {
+        let (w, _) = room();
+        assert_eq!(
+            names(&w.find_shapes("room/table/*")),
+            vec!["leg1", "leg2", "leg3", "leg4"]
+        );
+        assert_eq!(names(&w.find_shapes("room/*")), vec!["table", "lamp"]);
+        assert_eq!(names(&w.find_shapes("**/leg?")).len(), 4);
+        assert_eq!(names(&w.find_shapes("room/**/l*")).len(), 5);
+        assert_eq!(w.find_shapes("**").len(), 8);
+        assert!(w.find_shapes("room/table/leg5").is_empty());
+    }
+
+    #[test]
+    fn querying_shapes_by_tag() {
+        let (mut w, _) = room();
+        assert_eq!(
+            names(&w.shapes_tagged("wood")),
+            vec!["table", "leg1", "leg2", "leg3", "leg4"]
+        );
+        assert_eq!(names(&w.shapes_tagged("light")), vec!["lamp"]);
+
+        let lamp = w.get_shape_by_name("lamp").unwrap().id();
+        let lamp = w.get_shape_mut(lamp).unwrap();
+        lamp.add_tag("light");
+        assert_eq!(lamp.tags().len(), 1);
+        lamp.tags_mut().clear();
+        assert!(w.shapes_tagged("light").is_empty());
+    }
 }
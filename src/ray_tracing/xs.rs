@@ -1,17 +1,17 @@
 // use std::cmp::Ordering;
 use std::cmp::Ordering;
 
-use crate::float_cmp;
 use crate::shapes::Shape;
+use crate::{float_cmp, Float};
 
 #[derive(Debug)]
 pub struct XS {
-    pub t: f64,
+    pub t: Float,
     pub object: Box<dyn Shape>,
 }
 
 impl XS {
-    pub fn new(t: f64, object: Box<dyn Shape>) -> Self {
+    pub fn new(t: Float, object: Box<dyn Shape>) -> Self {
         XS { t, object }
     }
 }